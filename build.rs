@@ -15,8 +15,28 @@ fn main() {
         [
             "GL_OES_EGL_image",
             "GL_OES_EGL_image_external",
+            "GL_EXT_memory_object",
             "GL_EXT_memory_object_fd",
+            "GL_EXT_semaphore",
             "GL_EXT_semaphore_fd",
+            "GL_KHR_debug",
+        ],
+    )
+    .write_bindings(StructGenerator, &mut file)
+    .unwrap();
+
+    // EGL extensions glutin doesn't bind
+    let mut file = File::create(Path::new(&dest).join("egl_bindings.rs")).unwrap();
+
+    Registry::new(
+        Api::Egl,
+        (1, 5),
+        Profile::Core,
+        Fallbacks::All,
+        [
+            "EGL_KHR_image_base",
+            "EGL_EXT_image_dma_buf_import",
+            "EGL_EXT_image_dma_buf_import_modifiers",
         ],
     )
     .write_bindings(StructGenerator, &mut file)
@@ -23,11 +23,13 @@ impl MonitorContext {
         // You could initialize OpenGL resources here if needed
         // For example: VAOs, VBOs, shaders, textures, etc.
 
-        let gl = req.gl;
-        unsafe {
-            // Example: query GL version
-            let version = std::ffi::CStr::from_ptr(gl.GetString(gl::VERSION) as *const i8);
-            println!("Monitor initialized with OpenGL version: {:?}", version);
+        // `gl` is only set for monitors rendering with OpenGL ES (the default backend)
+        if let Some(gl) = req.gl {
+            unsafe {
+                // Example: query GL version
+                let version = std::ffi::CStr::from_ptr(gl.GetString(gl::VERSION) as *const i8);
+                println!("Monitor initialized with OpenGL version: {:?}", version);
+            }
         }
 
         let mut rng = rand::rng();
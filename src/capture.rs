@@ -0,0 +1,201 @@
+use std::path::Path;
+
+use drm::buffer::DrmFourcc;
+use thiserror::Error;
+
+/// Errors that can occur while capturing or saving a frame
+#[derive(Debug, Error)]
+pub enum CaptureError {
+    #[error("IO Error: {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("no frame has been presented yet")]
+    NothingPresented,
+    #[error("can't convert {0:?} pixels to RGBA")]
+    UnsupportedFormat(DrmFourcc),
+    #[error("pixel data is too short for a {width}x{height} image")]
+    TooShort { width: u32, height: u32 },
+}
+
+/// An image read back from a monitor, as 8 bit RGBA rows from top to bottom
+///
+/// Get one with `Monitor::capture_frame()`, or build one from raw pixels with
+/// [`from_pixels`](Self::from_pixels) to compare with it in tests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedFrame {
+    width: u32,
+    height: u32,
+    format: DrmFourcc,
+    rgba: Vec<u8>,
+}
+
+impl CapturedFrame {
+    /// Converts 32 bit pixels to RGBA. `stride` is the length of a row in bytes.
+    ///
+    /// Supports `Xrgb8888`, `Argb8888`, `Xbgr8888` and `Abgr8888`; formats without alpha
+    /// are made opaque.
+    pub fn from_pixels(
+        width: u32,
+        height: u32,
+        format: DrmFourcc,
+        pixels: &[u8],
+        stride: u32,
+    ) -> Result<Self, CaptureError> {
+        // Byte indices of R, G, B and A in a little endian pixel
+        let (r, g, b, a) = match format {
+            DrmFourcc::Xrgb8888 => (2, 1, 0, None),
+            DrmFourcc::Argb8888 => (2, 1, 0, Some(3)),
+            DrmFourcc::Xbgr8888 => (0, 1, 2, None),
+            DrmFourcc::Abgr8888 => (0, 1, 2, Some(3)),
+            _ => return Err(CaptureError::UnsupportedFormat(format)),
+        };
+        let row_length = width as usize * 4;
+        let needed = match height as usize {
+            0 => 0,
+            rows => (rows - 1) * stride as usize + row_length,
+        };
+        if stride < width * 4 || pixels.len() < needed {
+            return Err(CaptureError::TooShort { width, height });
+        }
+
+        let mut rgba = Vec::with_capacity(row_length * height as usize);
+        for row in pixels.chunks(stride as usize).take(height as usize) {
+            for pixel in row[..row_length].chunks_exact(4) {
+                rgba.extend([pixel[r], pixel[g], pixel[b], a.map_or(0xff, |a| pixel[a])]);
+            }
+        }
+        Ok(CapturedFrame {
+            width,
+            height,
+            format,
+            rgba,
+        })
+    }
+
+    /// Returns the size of the image as (width, height).
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Returns the format of the buffer the image was read from.
+    pub fn source_format(&self) -> DrmFourcc {
+        self.format
+    }
+
+    /// Returns the RGBA bytes of the image, row by row from the top.
+    pub fn rgba(&self) -> &[u8] {
+        &self.rgba
+    }
+
+    /// Returns the RGBA value of a pixel, or `None` outside of the image.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let index = (y as usize * self.width as usize + x as usize) * 4;
+        self.rgba[index..index + 4].try_into().ok()
+    }
+
+    /// Encodes the image as a binary PPM (alpha is dropped).
+    pub fn encode_ppm(&self) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for pixel in self.rgba.chunks_exact(4) {
+            ppm.extend_from_slice(&pixel[..3]);
+        }
+        ppm
+    }
+
+    /// Encodes the image as an RGBA PNG.
+    ///
+    /// The image data is stored uncompressed, which keeps this free of dependencies but
+    /// makes files as large as the raw pixels.
+    pub fn encode_png(&self) -> Vec<u8> {
+        // Each row starts with its filter type, 0 (none)
+        let row_length = self.width as usize * 4;
+        let mut raw = Vec::with_capacity((row_length + 1) * self.height as usize);
+        for row in self
+            .rgba
+            .chunks(row_length.max(1))
+            .take(self.height as usize)
+        {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&self.width.to_be_bytes());
+        header.extend_from_slice(&self.height.to_be_bytes());
+        header.extend_from_slice(&[8, 6, 0, 0, 0]); // 8 bit RGBA, no interlacing
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        write_chunk(&mut png, b"IHDR", &header);
+        write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    /// Writes the image to `path` as a binary PPM.
+    pub fn write_ppm(&self, path: impl AsRef<Path>) -> Result<(), CaptureError> {
+        std::fs::write(path, self.encode_ppm())?;
+        Ok(())
+    }
+
+    /// Writes the image to `path` as a PNG.
+    pub fn write_png(&self, path: impl AsRef<Path>) -> Result<(), CaptureError> {
+        std::fs::write(path, self.encode_png())?;
+        Ok(())
+    }
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps `data` in a zlib stream of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 0xffff;
+
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let length = block.len() as u16;
+        zlib.push(last as u8);
+        zlib.extend_from_slice(&length.to_le_bytes());
+        zlib.extend_from_slice(&(!length).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(data).to_be_bytes());
+    zlib
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
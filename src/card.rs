@@ -32,20 +32,70 @@ impl Card {
         options.write(true);
         Ok(Self(options.open(path)?))
     }
-    pub fn open_default_card() -> Self {
-        let gpus = egl::device::Device::query_devices()
-            .expect("Failed to query devices")
+    /// Opens the first DRM card EGL knows about.
+    ///
+    /// Without EGL (e.g. in CPU rendering mode on a system without Mesa), the first
+    /// `/dev/dri/card*` node with modesetting resources is used instead. Returns `Ok(None)`
+    /// if the system has no such card, and the last open error if there are card nodes
+    /// but none could be opened (e.g. for lack of permissions).
+    pub fn open_default_card() -> std::io::Result<Option<Self>> {
+        let gpus: Vec<_> = egl::device::Device::query_devices()
+            .into_iter()
+            .flatten()
             .filter_map(|egl_device| {
                 egl_device
                     .drm_device_node_path()
                     .and_then(|p| p.as_os_str().to_str())
-            });
-        for gpu_file_path in gpus {
+                    .map(str::to_owned)
+            })
+            .collect();
+        let mut last_error = None;
+        for gpu_file_path in &gpus {
             match Self::open(gpu_file_path) {
-                Ok(card) => return card,
-                Err(err) => eprintln!("Error while opening card {gpu_file_path}: {err}"),
+                Ok(card) => return Ok(Some(card)),
+                Err(err) => {
+                    eprintln!("Error while opening card {gpu_file_path}: {err}");
+                    last_error = Some(err);
+                }
             }
         }
-        panic!();
+        match Self::scan_dri_nodes()? {
+            Some(card) => Ok(Some(card)),
+            None => last_error.map_or(Ok(None), Err),
+        }
+    }
+
+    /// Opens the first card node in `/dev/dri` that supports modesetting.
+    ///
+    /// Returns `Ok(None)` if there's no card node or none supports modesetting, and the
+    /// last open error if a node couldn't be opened.
+    fn scan_dri_nodes() -> std::io::Result<Option<Self>> {
+        let entries = match std::fs::read_dir("/dev/dri") {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let mut nodes: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("card"))
+            })
+            .collect();
+        nodes.sort();
+        let mut last_error = None;
+        for path in nodes.iter().filter_map(|path| path.to_str()) {
+            match Self::open(path) {
+                Ok(card) if card.resource_handles().is_ok() => return Ok(Some(card)),
+                Ok(_) => {}
+                Err(err) => {
+                    eprintln!("Error while opening card {path}: {err}");
+                    last_error = Some(err);
+                }
+            }
+        }
+        last_error.map_or(Ok(None), Err)
     }
 }
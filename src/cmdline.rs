@@ -0,0 +1,218 @@
+//! Parsing of `video=` mode overrides from the kernel command line.
+//!
+//! Embedded images often pin outputs from the bootloader, e.g.
+//! `video=HDMI-A-1:1280x720@60` or `video=DP-1:d`. The syntax is described in the
+//! kernel's `Documentation/fb/modedb.rst`:
+//!
+//! ```text
+//! video=<connector>:<xres>x<yres>[M][R][-<bpp>][@<refresh>][i][m][eDd][,<option>...]
+//! ```
+//!
+//! EasyDRM honors the resolution, refresh rate and interlacing when picking a monitor's
+//! default mode, the `e` and `D` flags to set up an output even if no display is
+//! detected, the `d` flag to leave an output off, and the `rotate=` option. Like the
+//! kernel, if the connector doesn't list the requested mode, timings are generated with
+//! CVT (`M`, reduced blanking with `R`) or GTF.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use drm::control;
+
+use crate::config::{ModeSpec, Rotation};
+use crate::modegen::{ModeBuilder, ModeTiming};
+
+/// Default location of the kernel command line
+pub const DEFAULT_KERNEL_CMDLINE_PATH: &str = "/proc/cmdline";
+
+/// Connector state forced by the `e`, `D` and `d` flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForcedState {
+    /// `e`: enable the output even if nothing seems to be connected
+    On,
+    /// `D`: like `e`, and select the digital signal of DVI-I outputs
+    OnDigital,
+    /// `d`: disable the output
+    Off,
+}
+
+/// Mode override for one connector, parsed from a `video=` parameter
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VideoModeHint {
+    /// Requested resolution as (width, height).
+    pub size: Option<(u16, u16)>,
+    /// Requested refresh rate in Hz.
+    pub refresh: Option<u32>,
+    /// Requested bits per pixel (`-<bpp>`).
+    pub bpp: Option<u32>,
+    /// Timings should be computed with VESA CVT (`M`).
+    pub cvt: bool,
+    /// Use reduced blanking with CVT (`R`).
+    pub reduced_blanking: bool,
+    /// Interlaced mode (`i`).
+    pub interlaced: bool,
+    /// Add margins to the mode (`m`).
+    pub margins: bool,
+    /// Forced connector state (`e`, `D`, `d`).
+    pub forced: Option<ForcedState>,
+    /// Rotation from the `rotate=` option.
+    pub rotation: Option<Rotation>,
+}
+
+impl VideoModeHint {
+    /// Parses the part of a `video=` parameter after the connector name,
+    /// e.g. `1280x720@60` or `1920x1080M@60,rotate=90`.
+    ///
+    /// Returns `None` for named modes (`NTSC`, `PAL`) and unparsable specs.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut parts = spec.split(',');
+        let mode = parts.next().unwrap_or_default();
+        let mut hint = VideoModeHint::default();
+
+        let mut rest = mode;
+        if let Some((width, after)) = rest.split_once('x') {
+            let height_len = after
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(after.len());
+            hint.size = Some((width.parse().ok()?, after[..height_len].parse().ok()?));
+            rest = &after[height_len..];
+        }
+
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                'M' if hint.size.is_some() => hint.cvt = true,
+                'R' if hint.size.is_some() => hint.reduced_blanking = true,
+                'i' => hint.interlaced = true,
+                'm' => hint.margins = true,
+                'e' => hint.forced = Some(ForcedState::On),
+                'D' => hint.forced = Some(ForcedState::OnDigital),
+                'd' => hint.forced = Some(ForcedState::Off),
+                '-' | '@' => {
+                    let digits_len = rest[i + 1..]
+                        .find(|c: char| !c.is_ascii_digit())
+                        .unwrap_or(rest.len() - i - 1);
+                    let value = rest[i + 1..i + 1 + digits_len].parse().ok()?;
+                    if c == '-' {
+                        hint.bpp = Some(value);
+                    } else {
+                        hint.refresh = Some(value);
+                    }
+                    for _ in 0..digits_len {
+                        chars.next();
+                    }
+                }
+                _ => return None,
+            }
+        }
+
+        for option in parts {
+            if let Some(degrees) = option.strip_prefix("rotate=") {
+                hint.rotation = match degrees {
+                    "0" => Some(Rotation::Normal),
+                    "90" => Some(Rotation::Rotate90),
+                    "180" => Some(Rotation::Rotate180),
+                    "270" => Some(Rotation::Rotate270),
+                    _ => None,
+                };
+            }
+        }
+
+        Some(hint)
+    }
+
+    /// Returns true if the `e` or `D` flag forces the output on.
+    ///
+    /// Such outputs are set up even when their connector reports no display, at the
+    /// requested mode or 1024x768 if the connector has no modes.
+    pub fn forces_on(&self) -> bool {
+        matches!(self.forced, Some(ForcedState::On | ForcedState::OnDigital))
+    }
+
+    /// Finds the connector mode matching this hint's resolution, refresh rate and
+    /// interlacing, if a resolution was requested.
+    pub fn find_mode(&self, modes: &[control::Mode]) -> Option<control::Mode> {
+        let (width, height) = self.size?;
+        let interlaced = |mode: &control::Mode| {
+            mode.flags().contains(control::ModeFlags::INTERLACE) == self.interlaced
+        };
+        let candidates: Vec<control::Mode> = modes
+            .iter()
+            .copied()
+            .filter(|mode| interlaced(mode))
+            .collect();
+        ModeSpec {
+            width,
+            height,
+            refresh: self.refresh,
+        }
+        .find_in(&candidates)
+    }
+
+    /// Generates timings for this hint's resolution (at 60 Hz if no refresh rate was
+    /// given), with CVT if `M` was set and GTF otherwise.
+    pub fn generate_mode(&self) -> Option<control::Mode> {
+        let (width, height) = self.size?;
+        let timing = match (self.cvt, self.reduced_blanking) {
+            (true, true) => ModeTiming::CvtReducedBlanking,
+            (true, false) => ModeTiming::Cvt,
+            (false, _) => ModeTiming::Gtf,
+        };
+        ModeBuilder::new(width, height, self.refresh.unwrap_or(60) as f64)
+            .timing(timing)
+            .interlaced(self.interlaced)
+            .build()
+            .ok()
+    }
+}
+
+/// All `video=` hints found on a kernel command line
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KernelModeHints {
+    connectors: HashMap<String, VideoModeHint>,
+    global: Option<VideoModeHint>,
+}
+
+impl KernelModeHints {
+    /// Reads and parses a kernel command line file, usually `/proc/cmdline`.
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?))
+    }
+
+    /// Parses the `video=` parameters of a kernel command line.
+    ///
+    /// `video=<connector>:<spec>` applies to one connector, a bare `video=<spec>` applies
+    /// to every connector without its own entry. Later entries override earlier ones.
+    pub fn parse(cmdline: &str) -> Self {
+        let mut hints = KernelModeHints::default();
+        for value in cmdline
+            .split_whitespace()
+            .filter_map(|param| param.strip_prefix("video="))
+        {
+            match value.split_once(':') {
+                Some((connector, spec)) => {
+                    if let Some(hint) = VideoModeHint::parse(spec) {
+                        hints.connectors.insert(connector.to_string(), hint);
+                    }
+                }
+                None => {
+                    if let Some(hint) = VideoModeHint::parse(value) {
+                        hints.global = Some(hint);
+                    }
+                }
+            }
+        }
+        hints
+    }
+
+    /// Returns the hint for a connector (by kernel name, e.g. `HDMI-A-1`), falling back
+    /// to the global `video=` hint.
+    pub fn for_connector(&self, connector_name: &str) -> Option<&VideoModeHint> {
+        self.connectors.get(connector_name).or(self.global.as_ref())
+    }
+
+    /// Returns true if no `video=` hints were found.
+    pub fn is_empty(&self) -> bool {
+        self.connectors.is_empty() && self.global.is_none()
+    }
+}
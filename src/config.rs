@@ -0,0 +1,348 @@
+//! Declarative display configuration.
+//!
+//! A [`DisplayConfig`] is a list of per-output rules, usually loaded from a TOML file:
+//!
+//! ```toml
+//! [[output]]
+//! match = { connector = "HDMI-A-1" }
+//! mode = "1920x1080@60"
+//! rotation = 90
+//! position = [0, 0]
+//!
+//! [[output]]
+//! match = { manufacturer = "DEL", model = "DELL S2721DGF" }
+//! refresh = 144
+//! color = { gamma = 2.2, brightness = 0.9 }
+//!
+//! [[output]]
+//! match = { id = "GSM:7F44:16843009@DP-2" }
+//! enabled = false
+//! ```
+//!
+//! Rules are matched in order as monitors appear (at init and on hotplug); the first
+//! rule whose `match` table fits the monitor is applied. Settings that can't be honored
+//! are reported as [`ConfigIssue`]s on the monitor.
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use drm::control;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::edid::MonitorInfo;
+use crate::monitor_id::MonitorId;
+
+/// Errors that can occur while loading a display configuration
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("IO Error: {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("failed to parse display config: {0}")]
+    Parse(#[from] toml::de::Error),
+}
+
+/// A set of per-output rules. See the [module documentation](self) for the file format.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DisplayConfig {
+    /// Output rules, in matching order.
+    #[serde(default, rename = "output")]
+    pub outputs: Vec<OutputRule>,
+}
+
+impl DisplayConfig {
+    /// Loads a display configuration from a TOML file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Self::from_toml_str(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses a display configuration from a TOML string.
+    pub fn from_toml_str(toml: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Returns the first rule matching the given monitor, if any.
+    pub fn rule_for(&self, id: &MonitorId, info: Option<&MonitorInfo>) -> Option<&OutputRule> {
+        self.outputs
+            .iter()
+            .find(|rule| rule.matcher.matches(id, info))
+    }
+}
+
+/// Settings applied to every output matched by [`OutputMatch`].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputRule {
+    /// Which outputs this rule applies to.
+    #[serde(rename = "match")]
+    pub matcher: OutputMatch,
+    /// Set to `false` to leave the output off. Defaults to `true`.
+    pub enabled: Option<bool>,
+    /// Requested resolution, `"WIDTHxHEIGHT"` or `"WIDTHxHEIGHT@REFRESH"`.
+    pub mode: Option<ModeSpec>,
+    /// Requested refresh rate in Hz, overrides the one in `mode`.
+    pub refresh: Option<u32>,
+    /// Rotation in degrees (0, 90, 180 or 270).
+    pub rotation: Option<Rotation>,
+    /// Position of the output in the application's layout, in pixels.
+    pub position: Option<(i32, i32)>,
+    /// Gamma/brightness/contrast adjustments.
+    pub color: Option<ColorSettings>,
+}
+
+impl OutputRule {
+    /// Combines `mode` and `refresh` into the mode to look for, using the size of the
+    /// default mode if only a refresh rate is given.
+    pub(crate) fn mode_spec(&self, default_mode: &control::Mode) -> Option<ModeSpec> {
+        match (self.mode, self.refresh) {
+            (Some(mode), refresh) => Some(ModeSpec {
+                refresh: refresh.or(mode.refresh),
+                ..mode
+            }),
+            (None, Some(refresh)) => {
+                let (width, height) = default_mode.size();
+                Some(ModeSpec {
+                    width,
+                    height,
+                    refresh: Some(refresh),
+                })
+            }
+            (None, None) => None,
+        }
+    }
+}
+
+/// Criteria selecting the outputs a rule applies to. Every field that is set must match.
+///
+/// An empty matcher matches every output, which is useful for a trailing default rule.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputMatch {
+    /// Kernel connector name, e.g. `HDMI-A-1`.
+    pub connector: Option<String>,
+    /// Full monitor id, as printed by [`MonitorId`]'s `Display` implementation.
+    #[serde(default, deserialize_with = "deserialize_monitor_id")]
+    pub id: Option<MonitorId>,
+    /// EDID manufacturer PNP id, e.g. `DEL`.
+    pub manufacturer: Option<String>,
+    /// EDID product code.
+    pub product_code: Option<u16>,
+    /// EDID serial (serial string or numeric serial).
+    pub serial: Option<String>,
+    /// EDID model name.
+    pub model: Option<String>,
+}
+
+impl OutputMatch {
+    /// Returns true if the monitor with the given id and EDID info matches.
+    pub fn matches(&self, id: &MonitorId, info: Option<&MonitorInfo>) -> bool {
+        let edid = id.edid();
+        self.connector
+            .as_ref()
+            .is_none_or(|connector| connector == id.connector_name())
+            && self.id.as_ref().is_none_or(|wanted| wanted == id)
+            && self
+                .manufacturer
+                .as_ref()
+                .is_none_or(|m| edid.is_some_and(|e| &e.manufacturer == m))
+            && self
+                .product_code
+                .is_none_or(|p| edid.is_some_and(|e| e.product_code == p))
+            && self
+                .serial
+                .as_ref()
+                .is_none_or(|s| edid.is_some_and(|e| &e.serial == s))
+            && self
+                .model
+                .as_ref()
+                .is_none_or(|model| info.and_then(|info| info.model_name.as_ref()) == Some(model))
+    }
+}
+
+fn deserialize_monitor_id<'de, D>(deserializer: D) -> Result<Option<MonitorId>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let id = String::deserialize(deserializer)?;
+    id.parse().map(Some).map_err(serde::de::Error::custom)
+}
+
+/// A requested resolution and optional refresh rate, e.g. `1920x1080@60`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeSpec {
+    pub width: u16,
+    pub height: u16,
+    pub refresh: Option<u32>,
+}
+
+impl ModeSpec {
+    /// Finds the connector mode that best matches this spec.
+    ///
+    /// The resolution must match exactly. Without a refresh rate, the preferred mode is
+    /// used if it has the right size, otherwise the highest refresh rate.
+    pub fn find_in(&self, modes: &[control::Mode]) -> Option<control::Mode> {
+        let mut candidates = modes
+            .iter()
+            .filter(|mode| mode.size() == (self.width, self.height));
+        match self.refresh {
+            Some(refresh) => candidates.find(|mode| mode.vrefresh() == refresh).copied(),
+            None => candidates
+                .max_by_key(|mode| {
+                    (
+                        mode.mode_type().contains(control::ModeTypeFlags::PREFERRED),
+                        mode.vrefresh(),
+                    )
+                })
+                .copied(),
+        }
+    }
+}
+
+impl fmt::Display for ModeSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)?;
+        if let Some(refresh) = self.refresh {
+            write!(f, "@{refresh}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ModeSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("invalid mode {s:?}, expected WIDTHxHEIGHT[@REFRESH]");
+        let (size, refresh) = match s.split_once('@') {
+            Some((size, refresh)) => (size, Some(refresh.parse().map_err(|_| error())?)),
+            None => (s, None),
+        };
+        let (width, height) = size.split_once('x').ok_or_else(error)?;
+        Ok(ModeSpec {
+            width: width.parse().map_err(|_| error())?,
+            height: height.parse().map_err(|_| error())?,
+            refresh,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for ModeSpec {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Output rotation, applied through the primary plane `rotation` property.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    #[default]
+    Normal,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl Rotation {
+    /// Returns the rotation in degrees.
+    pub fn degrees(self) -> u32 {
+        match self {
+            Rotation::Normal => 0,
+            Rotation::Rotate90 => 90,
+            Rotation::Rotate180 => 180,
+            Rotation::Rotate270 => 270,
+        }
+    }
+
+    /// Returns true for 90° and 270°, where the framebuffer has swapped dimensions.
+    pub fn is_transposed(self) -> bool {
+        matches!(self, Rotation::Rotate90 | Rotation::Rotate270)
+    }
+
+    /// Value of the DRM plane `rotation` bitmask property (`DRM_MODE_ROTATE_*`).
+    pub(crate) fn drm_bits(self) -> u64 {
+        match self {
+            Rotation::Normal => 1 << 0,
+            Rotation::Rotate90 => 1 << 1,
+            Rotation::Rotate180 => 1 << 2,
+            Rotation::Rotate270 => 1 << 3,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Rotation {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match u32::deserialize(deserializer)? {
+            0 => Ok(Rotation::Normal),
+            90 => Ok(Rotation::Rotate90),
+            180 => Ok(Rotation::Rotate180),
+            270 => Ok(Rotation::Rotate270),
+            other => Err(serde::de::Error::custom(format!(
+                "invalid rotation {other}, expected 0, 90, 180 or 270"
+            ))),
+        }
+    }
+}
+
+/// Color adjustments, folded into the CRTC gamma ramp.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorSettings {
+    /// Gamma exponent, either a single value or per channel `[r, g, b]`.
+    pub gamma: Gamma,
+    /// Output scale, `1.0` leaves the output unchanged.
+    pub brightness: f32,
+    /// Contrast around mid-gray, `1.0` leaves the output unchanged.
+    pub contrast: f32,
+}
+
+impl Default for ColorSettings {
+    fn default() -> Self {
+        ColorSettings {
+            gamma: Gamma::Uniform(1.0),
+            brightness: 1.0,
+            contrast: 1.0,
+        }
+    }
+}
+
+/// Gamma exponent for all channels or per channel.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Gamma {
+    Uniform(f32),
+    PerChannel([f32; 3]),
+}
+
+impl ColorSettings {
+    /// Builds the red, green and blue gamma ramps for a CRTC with `len` entries.
+    pub fn gamma_ramps(&self, len: usize) -> [Vec<u16>; 3] {
+        let gammas = match self.gamma {
+            Gamma::Uniform(gamma) => [gamma; 3],
+            Gamma::PerChannel(gammas) => gammas,
+        };
+        gammas.map(|gamma| {
+            (0..len)
+                .map(|i| {
+                    let x = i as f32 / (len.max(2) - 1) as f32;
+                    let y = ((x.powf(1.0 / gamma) - 0.5) * self.contrast + 0.5) * self.brightness;
+                    (y.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+                })
+                .collect()
+        })
+    }
+}
+
+/// A setting from a matched [`OutputRule`] that couldn't be applied to a monitor
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum ConfigIssue {
+    #[error("mode {0} is not supported by this monitor, using the default mode")]
+    ModeNotAvailable(ModeSpec),
+    #[error("rotation of {}° is not supported by the primary plane", .0.degrees())]
+    RotationNotSupported(Rotation),
+    #[error("failed to apply color settings: {0}")]
+    ColorNotApplied(String),
+}
@@ -0,0 +1,489 @@
+#[cfg(feature = "gbm")]
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[cfg(feature = "gbm")]
+use drm::Device as _;
+#[cfg(feature = "gbm")]
+use drm::control::{Device as ControlDevice, framebuffer, property};
+use drm::control::{atomic::AtomicModeReq, crtc, plane};
+#[cfg(feature = "gbm")]
+use gbm::{BufferObjectFlags, Format};
+use thiserror::Error;
+
+#[cfg(feature = "gbm")]
+use crate::config::Rotation;
+use crate::device::GpuDevice;
+#[cfg(feature = "gbm")]
+use crate::gles_context::GlesContext;
+use crate::software_cursor::SoftwareCursor;
+
+/// Cursor size used when the driver doesn't report `DRM_CAP_CURSOR_WIDTH/HEIGHT`
+#[cfg(feature = "gbm")]
+const DEFAULT_CURSOR_SIZE: u64 = 64;
+
+/// Buffers per plane cursor: the latest image, the queued one and the one on screen
+#[cfg(feature = "gbm")]
+const CURSOR_BUFFERS: usize = 3;
+
+/// Errors that can occur while updating a monitor's cursor
+#[derive(Debug, Error)]
+pub enum CursorError {
+    #[error("IO Error: {0}")]
+    IOError(#[from] std::io::Error),
+    #[error(
+        "cursor image is {width}x{height}, but the cursor plane is limited to {max_width}x{max_height}"
+    )]
+    ImageTooLarge {
+        width: u32,
+        height: u32,
+        max_width: u32,
+        max_height: u32,
+    },
+    #[error("cursor image has {len} pixels, expected {width}x{height}")]
+    InvalidImage { len: usize, width: u32, height: u32 },
+}
+
+/// How a monitor's cursor is displayed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CursorBackend {
+    /// On the CRTC's dedicated cursor plane.
+    CursorPlane,
+    /// On a free overlay plane, for display controllers without a cursor plane.
+    OverlayPlane,
+    /// Drawn into each frame with a GL pass right before the buffer swap. The cursor
+    /// only moves when the monitor is redrawn.
+    Composited,
+}
+
+/// A monitor's cursor, on a plane if one is available and composited otherwise
+pub(crate) enum Cursor {
+    #[cfg(feature = "gbm")]
+    Plane {
+        cursor: HardwareCursor,
+        overlay: bool,
+    },
+    Composited(SoftwareCursor),
+}
+
+impl Cursor {
+    /// Sets up the cursor on `plane`, falling back to compositing if there's no plane or
+    /// its buffers can't be allocated (always without a GBM device).
+    #[cfg(feature = "gbm")]
+    pub(crate) fn new(
+        device: &Arc<GpuDevice>,
+        plane: Option<plane::Handle>,
+        overlay: bool,
+    ) -> Self {
+        let (Some(plane), Ok(_)) = (plane, device.gbm()) else {
+            return Cursor::Composited(SoftwareCursor::new());
+        };
+        match HardwareCursor::new(Arc::clone(device), plane, overlay) {
+            Ok(cursor) => Cursor::Plane { cursor, overlay },
+            Err(e) => {
+                eprintln!(
+                    "Warning: Failed to set up the cursor plane, compositing the cursor instead: {}",
+                    e
+                );
+                Cursor::Composited(SoftwareCursor::new())
+            }
+        }
+    }
+
+    /// Without the `gbm` feature there are no buffers for a cursor plane, the cursor is
+    /// always composited.
+    #[cfg(not(feature = "gbm"))]
+    pub(crate) fn new(
+        _device: &Arc<GpuDevice>,
+        _plane: Option<plane::Handle>,
+        _overlay: bool,
+    ) -> Self {
+        Cursor::composited()
+    }
+
+    /// Sets up a cursor that is always composited, for monitors without planes.
+    pub(crate) fn composited() -> Self {
+        Cursor::Composited(SoftwareCursor::new())
+    }
+
+    pub(crate) fn backend(&self) -> CursorBackend {
+        match self {
+            #[cfg(feature = "gbm")]
+            Cursor::Plane { overlay: false, .. } => CursorBackend::CursorPlane,
+            #[cfg(feature = "gbm")]
+            Cursor::Plane { overlay: true, .. } => CursorBackend::OverlayPlane,
+            Cursor::Composited(_) => CursorBackend::Composited,
+        }
+    }
+
+    pub(crate) fn size(&self) -> (u32, u32) {
+        match self {
+            #[cfg(feature = "gbm")]
+            Cursor::Plane { cursor, .. } => cursor.size(),
+            Cursor::Composited(cursor) => cursor.size(),
+        }
+    }
+
+    pub(crate) fn position(&self) -> (i32, i32) {
+        match self {
+            #[cfg(feature = "gbm")]
+            Cursor::Plane { cursor, .. } => cursor.position(),
+            Cursor::Composited(cursor) => cursor.position(),
+        }
+    }
+
+    pub(crate) fn is_visible(&self) -> bool {
+        match self {
+            #[cfg(feature = "gbm")]
+            Cursor::Plane { cursor, .. } => cursor.is_visible(),
+            Cursor::Composited(cursor) => cursor.is_visible(),
+        }
+    }
+
+    pub(crate) fn set_image(
+        &mut self,
+        argb: &[u32],
+        width: u32,
+        height: u32,
+        hotspot: (i32, i32),
+    ) -> Result<(), CursorError> {
+        match self {
+            #[cfg(feature = "gbm")]
+            Cursor::Plane { cursor, .. } => cursor.set_image(argb, width, height, hotspot),
+            Cursor::Composited(cursor) => cursor.set_image(argb, width, height, hotspot),
+        }
+    }
+
+    pub(crate) fn move_to(&mut self, x: i32, y: i32) {
+        match self {
+            #[cfg(feature = "gbm")]
+            Cursor::Plane { cursor, .. } => cursor.move_to(x, y),
+            Cursor::Composited(cursor) => cursor.move_to(x, y),
+        }
+    }
+
+    pub(crate) fn hide(&mut self) {
+        match self {
+            #[cfg(feature = "gbm")]
+            Cursor::Plane { cursor, .. } => cursor.hide(),
+            Cursor::Composited(cursor) => cursor.hide(),
+        }
+    }
+
+    /// Returns true if a plane cursor has changes to commit.
+    pub(crate) fn has_pending_plane_update(&self) -> bool {
+        match self {
+            #[cfg(feature = "gbm")]
+            Cursor::Plane { cursor, .. } => cursor.is_dirty(),
+            Cursor::Composited(_) => false,
+        }
+    }
+
+    /// Adds the pending changes of a plane cursor to an atomic request.
+    #[cfg_attr(not(feature = "gbm"), allow(unused_variables))]
+    pub(crate) fn add_to_request(&self, atomic_req: &mut AtomicModeReq, crtc: crtc::Handle) {
+        match self {
+            #[cfg(feature = "gbm")]
+            Cursor::Plane { cursor, .. } => {
+                if cursor.is_dirty() {
+                    cursor.add_to_request(atomic_req, crtc);
+                }
+            }
+            Cursor::Composited(_) => {}
+        }
+    }
+
+    /// Called once the atomic request holding the pending plane update was committed.
+    pub(crate) fn committed(&mut self) {
+        match self {
+            #[cfg(feature = "gbm")]
+            Cursor::Plane { cursor, .. } => {
+                if cursor.is_dirty() {
+                    cursor.committed();
+                }
+            }
+            Cursor::Composited(_) => {}
+        }
+    }
+
+    /// Called when the last commit reached the screen.
+    pub(crate) fn page_flip_complete(&mut self) {
+        match self {
+            #[cfg(feature = "gbm")]
+            Cursor::Plane { cursor, .. } => cursor.page_flip_complete(),
+            Cursor::Composited(_) => {}
+        }
+    }
+
+    /// Returns true if a composited cursor changed and the monitor must be redrawn to
+    /// show it.
+    pub(crate) fn needs_redraw(&self) -> bool {
+        matches!(self, Cursor::Composited(cursor) if cursor.is_dirty())
+    }
+
+    /// Draws a composited cursor into the current frame. Does nothing for plane cursors.
+    #[cfg(feature = "gbm")]
+    pub(crate) fn draw(
+        &mut self,
+        gles_context: &GlesContext,
+        surface_size: (u32, u32),
+        rotation: Rotation,
+    ) {
+        if let Cursor::Composited(cursor) = self {
+            cursor.draw(gles_context, surface_size, rotation);
+        }
+    }
+
+    /// Deletes the GL objects of a composited cursor, with the monitor's context current.
+    #[cfg(feature = "gbm")]
+    pub(crate) fn release_gl(&mut self) {
+        if let Cursor::Composited(cursor) = self {
+            cursor.release_renderer();
+        }
+    }
+}
+
+/// A cursor image buffer and its framebuffer
+#[cfg(feature = "gbm")]
+struct CursorBuffer {
+    bo: gbm::BufferObject<()>,
+    fb: framebuffer::Handle,
+}
+
+/// Hardware cursor state for a monitor's cursor plane (or an overlay plane standing in
+/// for it).
+///
+/// The image is triple buffered: the buffer of the last commit and the one it replaces
+/// stay untouched until the flip completes, so a new image never tears the one being
+/// scanned out. Changes are marked dirty and picked up by the next atomic request for the
+/// monitor; they stay dirty until that request was committed.
+#[cfg(feature = "gbm")]
+pub(crate) struct HardwareCursor {
+    device: Arc<GpuDevice>,
+    plane: plane::Handle,
+    properties: HashMap<String, property::Info>,
+    size: (u32, u32),
+    buffers: Vec<CursorBuffer>,
+    /// Buffer holding the latest image
+    front: usize,
+    /// Buffer handed to the display controller with the last commit
+    queued: Option<usize>,
+    /// Buffer shown before `queued`, free once the last commit completed
+    on_screen: Option<usize>,
+    hotspot: (i32, i32),
+    position: (i32, i32),
+    has_image: bool,
+    visible: bool,
+    dirty: bool,
+}
+
+#[cfg(feature = "gbm")]
+impl HardwareCursor {
+    pub(crate) fn new(
+        device: Arc<GpuDevice>,
+        plane: plane::Handle,
+        overlay: bool,
+    ) -> Result<Self, CursorError> {
+        let card = &device.card;
+        let gbm_device = device.gbm()?;
+        let cap = |cap| {
+            card.get_driver_capability(cap)
+                .unwrap_or(DEFAULT_CURSOR_SIZE)
+        };
+        let size = (
+            cap(drm::DriverCapability::CursorWidth) as u32,
+            cap(drm::DriverCapability::CursorHeight) as u32,
+        );
+        let properties = card.get_properties(plane)?.as_hashmap(card)?;
+
+        // Writable buffers are dumb buffers, which any plane can scan out
+        let usage = if overlay {
+            BufferObjectFlags::SCANOUT | BufferObjectFlags::WRITE
+        } else {
+            BufferObjectFlags::CURSOR | BufferObjectFlags::WRITE
+        };
+        let create_buffer = || -> Result<CursorBuffer, CursorError> {
+            let bo =
+                gbm_device.create_buffer_object::<()>(size.0, size.1, Format::Argb8888, usage)?;
+            let fb = card.add_framebuffer(&bo, 32, 32)?;
+            Ok(CursorBuffer { bo, fb })
+        };
+
+        let mut buffers = Vec::with_capacity(CURSOR_BUFFERS);
+        while buffers.len() < CURSOR_BUFFERS {
+            match create_buffer() {
+                Ok(buffer) => buffers.push(buffer),
+                Err(e) => {
+                    for buffer in buffers {
+                        let _ = card.destroy_framebuffer(buffer.fb);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(HardwareCursor {
+            device,
+            plane,
+            properties,
+            size,
+            buffers,
+            front: 0,
+            queued: None,
+            on_screen: None,
+            hotspot: (0, 0),
+            position: (0, 0),
+            has_image: false,
+            visible: false,
+            dirty: false,
+        })
+    }
+
+    pub(crate) fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub(crate) fn position(&self) -> (i32, i32) {
+        self.position
+    }
+
+    pub(crate) fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Uploads a new image into the back buffer and shows the cursor.
+    pub(crate) fn set_image(
+        &mut self,
+        argb: &[u32],
+        width: u32,
+        height: u32,
+        hotspot: (i32, i32),
+    ) -> Result<(), CursorError> {
+        if width > self.size.0 || height > self.size.1 {
+            return Err(CursorError::ImageTooLarge {
+                width,
+                height,
+                max_width: self.size.0,
+                max_height: self.size.1,
+            });
+        }
+        if argb.len() != width as usize * height as usize {
+            return Err(CursorError::InvalidImage {
+                len: argb.len(),
+                width,
+                height,
+            });
+        }
+
+        // Never write into a buffer that may be on screen. Cursor buffers are always
+        // written whole, so pad the image with transparent pixels
+        let back_index = (0..CURSOR_BUFFERS)
+            .find(|&i| self.queued != Some(i) && self.on_screen != Some(i))
+            .expect("at most two cursor buffers are in use");
+        let back = &mut self.buffers[back_index];
+        let stride = back.bo.stride() as usize;
+        let mut pixels = vec![0u8; stride * self.size.1 as usize];
+        for (y, row) in argb.chunks_exact(width.max(1) as usize).enumerate() {
+            let line = &mut pixels[y * stride..][..row.len() * 4];
+            for (dst, src) in line.chunks_exact_mut(4).zip(row) {
+                dst.copy_from_slice(&src.to_ne_bytes());
+            }
+        }
+        back.bo.write(&pixels)?;
+
+        self.front = back_index;
+        self.hotspot = hotspot;
+        self.has_image = true;
+        self.visible = true;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Moves the cursor hotspot to (x, y) in CRTC coordinates.
+    pub(crate) fn move_to(&mut self, x: i32, y: i32) {
+        if self.position != (x, y) {
+            self.position = (x, y);
+            self.dirty |= self.visible;
+        }
+    }
+
+    pub(crate) fn hide(&mut self) {
+        if self.visible {
+            self.visible = false;
+            self.dirty = true;
+        }
+    }
+
+    /// Adds the cursor plane state to an atomic request.
+    ///
+    /// The cursor stays dirty until [`committed`](Self::committed) is called, so the
+    /// changes are sent again if the commit fails.
+    pub(crate) fn add_to_request(&self, atomic_req: &mut AtomicModeReq, crtc: crtc::Handle) {
+        let mut set = |name: &str, value: property::Value| {
+            if let Some(prop) = self.properties.get(name) {
+                atomic_req.add_property(self.plane, prop.handle(), value);
+            }
+        };
+
+        if self.visible && self.has_image {
+            let (width, height) = self.size;
+            set("CRTC_ID", property::Value::CRTC(Some(crtc)));
+            set(
+                "FB_ID",
+                property::Value::Framebuffer(Some(self.buffers[self.front].fb)),
+            );
+            set("SRC_X", property::Value::UnsignedRange(0));
+            set("SRC_Y", property::Value::UnsignedRange(0));
+            set(
+                "SRC_W",
+                property::Value::UnsignedRange((width as u64) << 16),
+            );
+            set(
+                "SRC_H",
+                property::Value::UnsignedRange((height as u64) << 16),
+            );
+            set(
+                "CRTC_X",
+                property::Value::SignedRange((self.position.0 - self.hotspot.0) as i64),
+            );
+            set(
+                "CRTC_Y",
+                property::Value::SignedRange((self.position.1 - self.hotspot.1) as i64),
+            );
+            set("CRTC_W", property::Value::UnsignedRange(width as u64));
+            set("CRTC_H", property::Value::UnsignedRange(height as u64));
+        } else {
+            set("CRTC_ID", property::Value::CRTC(None));
+            set("FB_ID", property::Value::Framebuffer(None));
+        }
+    }
+
+    /// Marks the state added by the last [`add_to_request`](Self::add_to_request) as
+    /// committed: its buffer is queued for the next vblank and the dirty flag is cleared.
+    pub(crate) fn committed(&mut self) {
+        let shown = (self.visible && self.has_image).then_some(self.front);
+        // Still scanned out until the flip completes
+        self.on_screen = std::mem::replace(&mut self.queued, shown);
+        self.dirty = false;
+    }
+
+    /// Frees the buffer replaced by the last commit, now that it's off screen.
+    pub(crate) fn page_flip_complete(&mut self) {
+        self.on_screen = None;
+    }
+}
+
+#[cfg(feature = "gbm")]
+impl Drop for HardwareCursor {
+    fn drop(&mut self) {
+        // Removing the framebuffers turns the plane off
+        for buffer in &self.buffers {
+            if let Err(e) = self.device.card.destroy_framebuffer(buffer.fb) {
+                eprintln!("Warning: Failed to destroy cursor framebuffer: {}", e);
+            }
+        }
+    }
+}
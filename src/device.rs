@@ -0,0 +1,175 @@
+use std::collections::HashSet;
+#[cfg(feature = "gbm")]
+use std::os::unix::io::AsRawFd;
+use std::sync::{Mutex, PoisonError};
+
+use drm::Device;
+use drm::buffer::DrmFourcc;
+use drm::control::{Device as ControlDevice, PlaneType, crtc, plane};
+#[cfg(feature = "gbm")]
+use gbm::Device as GbmDevice;
+
+use crate::card::Card;
+use crate::monitor::MonitorSetupError;
+
+/// The DRM card and its GBM device, shared by `EasyDRM` and its monitors.
+///
+/// Only the default render backend uses GBM, the device isn't created for the others (or
+/// without the `gbm` feature).
+///
+/// Also keeps track of the overlay planes claimed by monitors (for a cursor or a layer),
+/// so a monitor can allocate overlays on its own without stealing them from another one.
+pub(crate) struct GpuDevice {
+    pub card: Card,
+    #[cfg(feature = "gbm")]
+    gbm: Option<GbmDevice<std::fs::File>>,
+    claimed_planes: Mutex<HashSet<plane::Handle>>,
+}
+
+impl GpuDevice {
+    /// Opens the default card with the atomic and universal planes capabilities, or
+    /// returns `Ok(None)` if the system has no DRM card. The GBM device is only created
+    /// `with_gbm`.
+    ///
+    /// A card that exists but can't be opened is an error, not a reason to go headless.
+    #[cfg_attr(not(feature = "gbm"), allow(unused_variables))]
+    pub(crate) fn open_default(with_gbm: bool) -> std::io::Result<Option<Self>> {
+        // Open DRM card
+        let Some(card) = Card::open_default_card()? else {
+            return Ok(None);
+        };
+
+        // Enable required capabilities
+        card.set_client_capability(drm::ClientCapability::UniversalPlanes, true)
+            .expect("Unable to request UniversalPlanes capability");
+        card.set_client_capability(drm::ClientCapability::Atomic, true)
+            .expect("Unable to request Atomic capability");
+        // Optional: exposes writeback connectors, used for captures rather than as monitors
+        if card
+            .set_client_capability(drm::ClientCapability::WritebackConnectors, true)
+            .is_err()
+        {
+            println!("[INFO] Writeback connectors aren't supported by this driver.");
+        }
+
+        // Create GBM device (needs ownership, so we clone the file descriptor)
+        #[cfg(feature = "gbm")]
+        let gbm = with_gbm.then(|| unsafe {
+            use std::os::unix::io::FromRawFd;
+            GbmDevice::new(std::fs::File::from_raw_fd(libc::dup(card.as_raw_fd())))
+                .expect("Failed to create GBM device")
+        });
+
+        Ok(Some(GpuDevice {
+            card,
+            #[cfg(feature = "gbm")]
+            gbm,
+            claimed_planes: Mutex::new(HashSet::new()),
+        }))
+    }
+
+    /// Returns the GBM device, or an `Unsupported` error if the render backend doesn't use it.
+    #[cfg(feature = "gbm")]
+    pub(crate) fn gbm(&self) -> std::io::Result<&GbmDevice<std::fs::File>> {
+        self.gbm.as_ref().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "GBM is only available with the default render backend",
+            )
+        })
+    }
+
+    /// Marks a plane as used. Returns false if it was already claimed.
+    pub(crate) fn claim_plane(&self, plane: plane::Handle) -> bool {
+        self.claimed_planes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(plane)
+    }
+
+    /// Makes a claimed plane available again.
+    pub(crate) fn release_plane(&self, plane: plane::Handle) {
+        self.claimed_planes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&plane);
+    }
+
+    /// Returns the planes currently claimed by monitors.
+    pub(crate) fn claimed_planes(&self) -> HashSet<plane::Handle> {
+        self.claimed_planes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Returns the planes that can scan out `format`.
+    pub(crate) fn planes_with_format(
+        &self,
+        plane_handles: &[plane::Handle],
+        format: DrmFourcc,
+    ) -> Vec<plane::Handle> {
+        plane_handles
+            .iter()
+            .copied()
+            .filter(|&plane| {
+                self.card
+                    .get_plane(plane)
+                    .is_ok_and(|info| info.formats().contains(&(format as u32)))
+            })
+            .collect()
+    }
+
+    pub(crate) fn find_plane_for_crtc(
+        &self,
+        plane_handles: &[plane::Handle],
+        res: &drm::control::ResourceHandles,
+        crtc_handle: crtc::Handle,
+        plane_type: PlaneType,
+        used_planes: &HashSet<plane::Handle>,
+    ) -> Result<Option<plane::Handle>, MonitorSetupError> {
+        for plane_handle in plane_handles {
+            if used_planes.contains(plane_handle) {
+                continue;
+            }
+
+            let Ok(plane_info) = self.card.get_plane(*plane_handle) else {
+                continue;
+            };
+            let compatible_crtcs = res.filter_crtcs(plane_info.possible_crtcs());
+            if !compatible_crtcs.contains(&crtc_handle) {
+                continue;
+            }
+
+            if self.plane_is_type(*plane_handle, plane_type)? {
+                return Ok(Some(*plane_handle));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn plane_is_type(
+        &self,
+        plane_handle: plane::Handle,
+        plane_type: PlaneType,
+    ) -> Result<bool, MonitorSetupError> {
+        let properties = self.card.get_properties(plane_handle)?;
+        for (&id, &value) in properties.iter() {
+            let Ok(info) = self.card.get_property(id) else {
+                continue;
+            };
+
+            if info
+                .name()
+                .to_str()
+                .map(|name| name == "type")
+                .unwrap_or(false)
+            {
+                return Ok(value == (plane_type as u32).into());
+            }
+        }
+
+        Ok(false)
+    }
+}
@@ -0,0 +1,172 @@
+use std::os::fd::{BorrowedFd, RawFd};
+
+use drm::buffer::{DrmFourcc, DrmModifier, Handle, PlanarBuffer};
+use drm::control::{self, FbCmd2Flags, framebuffer};
+use gbm::BufferObjectFlags;
+use thiserror::Error;
+
+/// Errors that can occur while importing a dma-buf
+#[derive(Debug, Error)]
+pub enum DmaBufError {
+    #[error("IO Error: {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("invalid dma-buf descriptor: {0}")]
+    InvalidDescriptor(&'static str),
+}
+
+/// One plane of a dma-buf image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmaBufPlane {
+    /// dma-buf file descriptor holding the plane. Planes may share a descriptor. It's
+    /// only borrowed during the import, the caller keeps ownership.
+    pub fd: RawFd,
+    /// Offset of the plane in the dma-buf, in bytes.
+    pub offset: u32,
+    /// Length of a row of the plane, in bytes.
+    pub stride: u32,
+}
+
+/// Description of a dma-buf image produced outside of EasyDRM, e.g. by a video decoder,
+/// a camera or another GPU
+///
+/// # Example
+///
+/// ```ignore
+/// // NV12 frame from a decoder: luma and chroma planes in the same buffer
+/// let desc = DmaBufDescriptor {
+///     width: 1920,
+///     height: 1080,
+///     format: DrmFourcc::Nv12,
+///     modifier: Some(DrmModifier::Linear),
+///     planes: vec![
+///         DmaBufPlane { fd, offset: 0, stride: 1920 },
+///         DmaBufPlane { fd, offset: 1920 * 1088, stride: 1920 },
+///     ],
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DmaBufDescriptor {
+    /// Width of the image in pixels.
+    pub width: u32,
+    /// Height of the image in pixels.
+    pub height: u32,
+    /// Pixel format, e.g. `Nv12` or `Argb8888`.
+    pub format: DrmFourcc,
+    /// Memory layout, or `None` for the driver's implicit layout.
+    pub modifier: Option<DrmModifier>,
+    /// The planes of the image, one to four of them.
+    pub planes: Vec<DmaBufPlane>,
+}
+
+impl DmaBufDescriptor {
+    pub(crate) fn validate(&self) -> Result<(), DmaBufError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(DmaBufError::InvalidDescriptor("image is empty"));
+        }
+        if !(1..=4).contains(&self.planes.len()) {
+            return Err(DmaBufError::InvalidDescriptor(
+                "an image has one to four planes",
+            ));
+        }
+        if self.planes.iter().any(|plane| plane.fd < 0) {
+            return Err(DmaBufError::InvalidDescriptor("invalid file descriptor"));
+        }
+        Ok(())
+    }
+}
+
+/// Identifies a dma-buf handed to EasyDRM, to know when it's released
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DmaBufId(pub(crate) u64);
+
+/// A dma-buf imported for scanout
+pub(crate) struct ImportedDmaBuf {
+    pub bo: gbm::BufferObject<()>,
+    pub fb: framebuffer::Handle,
+}
+
+/// Imports a dma-buf with `gbm_bo_import` and adds a framebuffer for it.
+pub(crate) fn import_for_scanout(
+    card: &impl control::Device,
+    gbm_device: &gbm::Device<std::fs::File>,
+    desc: &DmaBufDescriptor,
+) -> Result<ImportedDmaBuf, DmaBufError> {
+    desc.validate()?;
+
+    let mut fds = [None; 4];
+    let mut strides = [0; 4];
+    let mut offsets = [0; 4];
+    for (i, plane) in desc.planes.iter().enumerate() {
+        fds[i] = Some(unsafe { BorrowedFd::borrow_raw(plane.fd) });
+        strides[i] = plane.stride as i32;
+        offsets[i] = plane.offset as i32;
+    }
+    let bo = gbm_device.import_buffer_object_from_dma_buf_with_modifiers::<()>(
+        desc.planes.len() as u32,
+        fds,
+        desc.width,
+        desc.height,
+        desc.format,
+        BufferObjectFlags::SCANOUT,
+        strides,
+        offsets,
+        desc.modifier.unwrap_or(DrmModifier::Invalid),
+    )?;
+
+    // Describe the framebuffer with the producer's layout rather than what GBM reports
+    let planes = ImportedPlanes { bo: &bo, desc };
+    let flags = if planes.modifier().is_some() {
+        FbCmd2Flags::MODIFIERS
+    } else {
+        FbCmd2Flags::empty()
+    };
+    let fb = card.add_planar_framebuffer(&planes, flags)?;
+    Ok(ImportedDmaBuf { bo, fb })
+}
+
+/// The GEM handles of an imported buffer object with the layout of its descriptor
+struct ImportedPlanes<'a> {
+    bo: &'a gbm::BufferObject<()>,
+    desc: &'a DmaBufDescriptor,
+}
+
+impl PlanarBuffer for ImportedPlanes<'_> {
+    fn size(&self) -> (u32, u32) {
+        (self.desc.width, self.desc.height)
+    }
+
+    fn format(&self) -> DrmFourcc {
+        self.desc.format
+    }
+
+    fn modifier(&self) -> Option<DrmModifier> {
+        self.desc
+            .modifier
+            .filter(|&modifier| modifier != DrmModifier::Invalid)
+    }
+
+    fn pitches(&self) -> [u32; 4] {
+        let mut pitches = [0; 4];
+        for (pitch, plane) in pitches.iter_mut().zip(&self.desc.planes) {
+            *pitch = plane.stride;
+        }
+        pitches
+    }
+
+    fn handles(&self) -> [Option<Handle>; 4] {
+        let mut handles = [None; 4];
+        for (i, handle) in handles.iter_mut().take(self.desc.planes.len()).enumerate() {
+            let raw = unsafe { self.bo.handle_for_plane(i as i32).u32_ };
+            *handle = std::num::NonZeroU32::new(raw).map(Handle::from);
+        }
+        handles
+    }
+
+    fn offsets(&self) -> [u32; 4] {
+        let mut offsets = [0; 4];
+        for (offset, plane) in offsets.iter_mut().zip(&self.desc.planes) {
+            *offset = plane.offset;
+        }
+        offsets
+    }
+}
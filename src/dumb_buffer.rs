@@ -0,0 +1,195 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use drm::buffer::{Buffer, DrmFourcc, DrmModifier};
+use drm::control::dumbbuffer::{DumbBuffer, DumbMapping};
+use drm::control::{Device as ControlDevice, framebuffer};
+use thiserror::Error;
+
+use crate::capture::{CaptureError, CapturedFrame};
+use crate::device::GpuDevice;
+use crate::formats::ScanoutFormat;
+use crate::render_backend::{BackendFrame, RenderBackend, RenderBackendError};
+
+/// Pixel format of dumb buffers, the one every display controller can scan out
+const DUMB_BUFFER_FORMAT: DrmFourcc = DrmFourcc::Xrgb8888;
+
+/// Errors that can occur while drawing to a monitor with the CPU
+#[derive(Debug, Error)]
+pub enum DumbBufferError {
+    #[error("IO Error: {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("the monitor renders with OpenGL ES, not to dumb buffers")]
+    NotCpuRendered,
+}
+
+/// The back buffer of a monitor rendered by the CPU, mapped for writing
+///
+/// Dereferences to the `u32` pixels of the buffer, row by row from the top. Rows are
+/// [`stride`](Self::stride) pixels apart, which may be more than the width. Pixels are
+/// in [`format`](Self::format), `0x00RRGGBB` for `Xrgb8888`.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut pixels = monitor.pixels_mut()?;
+/// let (width, stride) = (pixels.width() as usize, pixels.stride() as usize);
+/// for row in pixels.chunks_mut(stride) {
+///     row[..width].fill(0x00ff_0000); // red
+/// }
+/// ```
+pub struct Pixels<'a> {
+    mapping: DumbMapping<'a>,
+    size: (u32, u32),
+    stride: u32,
+    format: DrmFourcc,
+}
+
+impl Pixels<'_> {
+    /// Returns the width of the buffer in pixels.
+    pub fn width(&self) -> u32 {
+        self.size.0
+    }
+
+    /// Returns the height of the buffer in pixels.
+    pub fn height(&self) -> u32 {
+        self.size.1
+    }
+
+    /// Returns the distance between the starts of two rows, in pixels.
+    pub fn stride(&self) -> u32 {
+        self.stride
+    }
+
+    /// Returns the pixel format of the buffer.
+    pub fn format(&self) -> DrmFourcc {
+        self.format
+    }
+
+    /// Returns the pixels of row `y`, without the padding at the end of the row.
+    pub fn row_mut(&mut self, y: u32) -> Option<&mut [u32]> {
+        if y >= self.size.1 {
+            return None;
+        }
+        let (width, stride) = (self.size.0 as usize, self.stride as usize);
+        Some(&mut self[y as usize * stride..][..width])
+    }
+}
+
+impl Deref for Pixels<'_> {
+    type Target = [u32];
+
+    fn deref(&self) -> &[u32] {
+        // Mappings are page aligned, so there's no unaligned prefix
+        let (_, pixels, _) = unsafe { self.mapping.align_to::<u32>() };
+        pixels
+    }
+}
+
+impl DerefMut for Pixels<'_> {
+    fn deref_mut(&mut self) -> &mut [u32] {
+        let (_, pixels, _) = unsafe { self.mapping.align_to_mut::<u32>() };
+        pixels
+    }
+}
+
+/// Double-buffered dumb buffers a monitor renders to with the CPU, without EGL or GBM
+pub(crate) struct DumbBuffers {
+    device: Arc<GpuDevice>,
+    buffers: [(DumbBuffer, framebuffer::Handle); 2],
+    /// Index of the buffer being drawn
+    back: usize,
+    /// Set once a buffer was presented
+    has_front: bool,
+}
+
+impl DumbBuffers {
+    pub(crate) fn new(device: Arc<GpuDevice>, width: u16, height: u16) -> std::io::Result<Self> {
+        let create_buffer = || -> std::io::Result<(DumbBuffer, framebuffer::Handle)> {
+            let buffer = device.card.create_dumb_buffer(
+                (width.into(), height.into()),
+                DUMB_BUFFER_FORMAT,
+                32,
+            )?;
+            match device.card.add_framebuffer(&buffer, 24, 32) {
+                Ok(fb) => Ok((buffer, fb)),
+                Err(e) => {
+                    let _ = device.card.destroy_dumb_buffer(buffer);
+                    Err(e)
+                }
+            }
+        };
+        let first = create_buffer()?;
+        let second = match create_buffer() {
+            Ok(second) => second,
+            Err(e) => {
+                let _ = device.card.destroy_framebuffer(first.1);
+                let _ = device.card.destroy_dumb_buffer(first.0);
+                return Err(e);
+            }
+        };
+        Ok(DumbBuffers {
+            device,
+            buffers: [first, second],
+            back: 0,
+            has_front: false,
+        })
+    }
+
+    /// Maps the back buffer.
+    pub(crate) fn map_back(&mut self) -> std::io::Result<Pixels<'_>> {
+        let buffer = &mut self.buffers[self.back].0;
+        let (size, pitch, format) = (buffer.size(), buffer.pitch(), buffer.format());
+        let mapping = self.device.card.map_dumb_buffer(buffer)?;
+        Ok(Pixels {
+            mapping,
+            size,
+            stride: pitch / 4,
+            format,
+        })
+    }
+}
+
+impl RenderBackend for DumbBuffers {
+    fn scanout_format(&self) -> ScanoutFormat {
+        ScanoutFormat {
+            format: DUMB_BUFFER_FORMAT,
+            modifier: Some(DrmModifier::Linear),
+            explicit_modifier: false,
+        }
+    }
+
+    /// The back buffer becomes the front buffer. The CPU is done writing, so there's no
+    /// fence.
+    fn present(&mut self) -> Result<BackendFrame, RenderBackendError> {
+        let fb = self.buffers[self.back].1;
+        self.back = 1 - self.back;
+        self.has_front = true;
+        Ok(BackendFrame::new(fb, None))
+    }
+
+    fn read_frame(&self) -> Result<CapturedFrame, CaptureError> {
+        if !self.has_front {
+            return Err(CaptureError::NothingPresented);
+        }
+        // Handles are plain ids, mapping a copy maps the same buffer
+        let mut buffer = self.buffers[1 - self.back].0;
+        let (width, height) = buffer.size();
+        let (pitch, format) = (buffer.pitch(), buffer.format());
+        let mapping = self.device.card.map_dumb_buffer(&mut buffer)?;
+        CapturedFrame::from_pixels(width, height, format, &mapping, pitch)
+    }
+}
+
+impl Drop for DumbBuffers {
+    fn drop(&mut self) {
+        for (buffer, fb) in self.buffers {
+            if let Err(e) = self.device.card.destroy_framebuffer(fb) {
+                eprintln!("Warning: Failed to destroy dumb buffer framebuffer: {}", e);
+            }
+            if let Err(e) = self.device.card.destroy_dumb_buffer(buffer) {
+                eprintln!("Warning: Failed to destroy dumb buffer: {}", e);
+            }
+        }
+    }
+}
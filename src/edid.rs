@@ -0,0 +1,448 @@
+//! Pure Rust EDID parser.
+//!
+//! Decodes the connector `EDID` property blob (base block plus CTA-861 and
+//! DisplayID extension blocks) into a [`MonitorInfo`] describing the physical
+//! display: who made it, what it is called, how big it is and which color/HDR/VRR
+//! capabilities it advertises.
+
+use thiserror::Error;
+
+const EDID_BLOCK_SIZE: usize = 128;
+const EDID_HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+
+const EXTENSION_TAG_CTA: u8 = 0x02;
+const EXTENSION_TAG_DISPLAYID: u8 = 0x70;
+
+/// Errors that can occur while parsing an EDID blob
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum EdidError {
+    #[error("EDID blob is too short ({0} bytes)")]
+    TooShort(usize),
+    #[error("EDID blob has an invalid header")]
+    InvalidHeader,
+    #[error("EDID base block checksum mismatch")]
+    ChecksumMismatch,
+}
+
+/// Information about a physical display, decoded from its EDID.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorInfo {
+    /// Three letter PNP manufacturer id (e.g. `"DEL"`, `"SAM"`).
+    pub manufacturer: String,
+    /// Manufacturer assigned product code.
+    pub product_code: u16,
+    /// Numeric serial number, `None` if the display reports zero.
+    pub serial_number: Option<u32>,
+    /// Serial number string descriptor, if present.
+    pub serial_string: Option<String>,
+    /// Model name (display product name descriptor), if present.
+    pub model_name: Option<String>,
+    /// Week/year of manufacture or model year.
+    pub manufacture_date: ManufactureDate,
+    /// Physical image size in millimeters as (width, height).
+    ///
+    /// `None` for projectors or displays that don't report a size.
+    pub physical_size_mm: Option<(u32, u32)>,
+    /// EDID structure version as (version, revision), e.g. `(1, 4)`.
+    pub edid_version: (u8, u8),
+    /// Supported color encodings, bit depth and colorimetry.
+    pub color_formats: ColorFormats,
+    /// HDR static metadata from the CTA-861 extension, if advertised.
+    pub hdr: Option<HdrStaticMetadata>,
+    /// Variable refresh rate range, if advertised.
+    pub vrr_range: Option<VrrRange>,
+}
+
+/// Manufacture date reported in the EDID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManufactureDate {
+    /// The display doesn't report a date.
+    Unknown,
+    /// Year of manufacture and, optionally, the week (1-54).
+    Manufactured { week: Option<u8>, year: u16 },
+    /// The year the model was released (week byte set to `0xFF`).
+    ModelYear(u16),
+}
+
+/// Color encodings and colorimetry supported by the display
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ColorFormats {
+    /// RGB 4:4:4 (always supported by digital displays)
+    pub rgb444: bool,
+    /// YCbCr 4:4:4
+    pub ycbcr444: bool,
+    /// YCbCr 4:2:2
+    pub ycbcr422: bool,
+    /// YCbCr 4:2:0 (for at least some video modes)
+    pub ycbcr420: bool,
+    /// Bits per color component, if the display reports it.
+    pub bits_per_component: Option<u8>,
+    /// BT.2020 RGB colorimetry
+    pub bt2020_rgb: bool,
+    /// BT.2020 YCbCr colorimetry
+    pub bt2020_ycc: bool,
+    /// DCI-P3 colorimetry
+    pub dci_p3: bool,
+}
+
+/// HDR static metadata (CTA-861.3 HDR Static Metadata Data Block)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HdrStaticMetadata {
+    /// Traditional gamma, SDR luminance range
+    pub eotf_sdr: bool,
+    /// Traditional gamma, HDR luminance range
+    pub eotf_hdr: bool,
+    /// SMPTE ST 2084 (PQ)
+    pub eotf_pq: bool,
+    /// Hybrid Log-Gamma
+    pub eotf_hlg: bool,
+    /// Desired content max luminance in cd/m², if reported.
+    pub max_luminance: Option<f32>,
+    /// Desired content max frame-average luminance in cd/m², if reported.
+    pub max_frame_average_luminance: Option<f32>,
+    /// Desired content min luminance in cd/m², if reported.
+    pub min_luminance: Option<f32>,
+}
+
+/// Range of refresh rates (in Hz) the display can be driven at continuously
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VrrRange {
+    pub min_hz: u32,
+    pub max_hz: u32,
+}
+
+impl MonitorInfo {
+    /// Parses a raw EDID blob, as read from the connector `EDID` property.
+    ///
+    /// The base block must be valid; extension blocks with a bad checksum or an
+    /// unknown tag are ignored.
+    pub fn from_edid(edid: &[u8]) -> Result<Self, EdidError> {
+        if edid.len() < EDID_BLOCK_SIZE {
+            return Err(EdidError::TooShort(edid.len()));
+        }
+        let base = &edid[..EDID_BLOCK_SIZE];
+        if base[..8] != EDID_HEADER {
+            return Err(EdidError::InvalidHeader);
+        }
+        if !checksum_ok(base) {
+            return Err(EdidError::ChecksumMismatch);
+        }
+
+        let mut info = parse_base_block(base);
+
+        let extension_count = base[126] as usize;
+        for block in edid[EDID_BLOCK_SIZE..]
+            .chunks_exact(EDID_BLOCK_SIZE)
+            .take(extension_count)
+        {
+            if !checksum_ok(block) {
+                continue;
+            }
+            match block[0] {
+                EXTENSION_TAG_CTA => parse_cta_block(block, &mut info),
+                EXTENSION_TAG_DISPLAYID => parse_displayid_block(block, &mut info),
+                _ => {}
+            }
+        }
+
+        Ok(info)
+    }
+}
+
+fn checksum_ok(block: &[u8]) -> bool {
+    block.iter().fold(0u8, |acc, &b| acc.wrapping_add(b)) == 0
+}
+
+/// Decodes the compressed 3-letter PNP id (three 5-bit letters, 'A' = 1)
+fn decode_pnp_id(bytes: [u8; 2]) -> String {
+    let raw = u16::from_be_bytes(bytes);
+    [(raw >> 10) & 0x1F, (raw >> 5) & 0x1F, raw & 0x1F]
+        .iter()
+        .map(|&c| {
+            if (1..=26).contains(&c) {
+                (b'A' + c as u8 - 1) as char
+            } else {
+                '?'
+            }
+        })
+        .collect()
+}
+
+/// Decodes a 13-byte display descriptor string (terminated by `0x0A`, padded with spaces)
+fn decode_descriptor_string(bytes: &[u8]) -> Option<String> {
+    let end = bytes.iter().position(|&b| b == 0x0A).unwrap_or(bytes.len());
+    let text: String = bytes[..end]
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '?'
+            }
+        })
+        .collect();
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn parse_base_block(base: &[u8]) -> MonitorInfo {
+    let manufacturer = decode_pnp_id([base[8], base[9]]);
+    let product_code = u16::from_le_bytes([base[10], base[11]]);
+    let serial = u32::from_le_bytes([base[12], base[13], base[14], base[15]]);
+    let edid_version = (base[18], base[19]);
+
+    let year = 1990 + base[17] as u16;
+    let manufacture_date = match base[16] {
+        0xFF => ManufactureDate::ModelYear(year),
+        _ if base[17] == 0 => ManufactureDate::Unknown,
+        0 => ManufactureDate::Manufactured { week: None, year },
+        week => ManufactureDate::Manufactured {
+            week: Some(week),
+            year,
+        },
+    };
+
+    let mut color_formats = ColorFormats::default();
+    let digital = base[20] & 0x80 != 0;
+    if digital {
+        color_formats.rgb444 = true;
+        if edid_version >= (1, 4) {
+            color_formats.bits_per_component = match (base[20] >> 4) & 0x07 {
+                1 => Some(6),
+                2 => Some(8),
+                3 => Some(10),
+                4 => Some(12),
+                5 => Some(14),
+                6 => Some(16),
+                _ => None,
+            };
+            let encodings = (base[24] >> 3) & 0x03;
+            color_formats.ycbcr444 = encodings & 0x01 != 0;
+            color_formats.ycbcr422 = encodings & 0x02 != 0;
+        }
+    }
+
+    let mut info = MonitorInfo {
+        manufacturer,
+        product_code,
+        serial_number: (serial != 0).then_some(serial),
+        serial_string: None,
+        model_name: None,
+        manufacture_date,
+        physical_size_mm: None,
+        edid_version,
+        color_formats,
+        hdr: None,
+        vrr_range: None,
+    };
+
+    // Screen size in centimeters; overridden below by the more precise size of
+    // the first detailed timing descriptor, if any.
+    if base[21] != 0 && base[22] != 0 {
+        info.physical_size_mm = Some((base[21] as u32 * 10, base[22] as u32 * 10));
+    }
+
+    let mut dtd_size = None;
+    for descriptor in base[54..126].chunks_exact(18) {
+        if descriptor[0] != 0 || descriptor[1] != 0 {
+            // Detailed timing descriptor
+            if dtd_size.is_none() {
+                let width = descriptor[12] as u32 | ((descriptor[14] as u32 & 0xF0) << 4);
+                let height = descriptor[13] as u32 | ((descriptor[14] as u32 & 0x0F) << 8);
+                if width != 0 && height != 0 {
+                    dtd_size = Some((width, height));
+                }
+            }
+            continue;
+        }
+
+        match descriptor[3] {
+            0xFF => info.serial_string = decode_descriptor_string(&descriptor[5..18]),
+            0xFC => info.model_name = decode_descriptor_string(&descriptor[5..18]),
+            // Only continuous-frequency digital displays can be driven anywhere in the range
+            0xFD if digital && base[24] & 0x01 != 0 => {
+                info.vrr_range = parse_range_limits(descriptor, edid_version)
+            }
+            _ => {}
+        }
+    }
+    if dtd_size.is_some() {
+        info.physical_size_mm = dtd_size;
+    }
+
+    info
+}
+
+/// Parses the vertical rate range out of a display range limits descriptor
+fn parse_range_limits(descriptor: &[u8], edid_version: (u8, u8)) -> Option<VrrRange> {
+    let offsets = if edid_version >= (1, 4) {
+        descriptor[4]
+    } else {
+        0
+    };
+    let mut min_hz = descriptor[5] as u32;
+    let mut max_hz = descriptor[6] as u32;
+    if offsets & 0x01 != 0 {
+        min_hz += 255;
+    }
+    if offsets & 0x02 != 0 {
+        max_hz += 255;
+    }
+    (min_hz != 0 && max_hz > min_hz).then_some(VrrRange { min_hz, max_hz })
+}
+
+fn parse_cta_block(block: &[u8], info: &mut MonitorInfo) {
+    let dtd_offset = (block[2] as usize).min(127);
+    if block[1] >= 2 {
+        info.color_formats.ycbcr444 |= block[3] & 0x20 != 0;
+        info.color_formats.ycbcr422 |= block[3] & 0x10 != 0;
+    }
+    if block[1] < 3 || dtd_offset < 4 {
+        return;
+    }
+
+    let mut pos = 4;
+    while pos < dtd_offset {
+        let header = block[pos];
+        let tag = header >> 5;
+        let len = (header & 0x1F) as usize;
+        let Some(payload) = block.get(pos + 1..pos + 1 + len) else {
+            break;
+        };
+        pos += 1 + len;
+
+        match tag {
+            // Vendor specific data block
+            3 if len >= 3 => parse_cta_vendor_block(payload, info),
+            // Extended tag
+            7 if len >= 1 => match payload[0] {
+                // Colorimetry data block
+                5 if len >= 2 => {
+                    info.color_formats.bt2020_ycc |= payload[1] & 0x40 != 0;
+                    info.color_formats.bt2020_rgb |= payload[1] & 0x80 != 0;
+                    if len >= 3 {
+                        info.color_formats.dci_p3 |= payload[2] & 0x80 != 0;
+                    }
+                }
+                // HDR static metadata data block
+                6 if len >= 3 => info.hdr = Some(parse_hdr_static_metadata(&payload[1..])),
+                // YCbCr 4:2:0 video data block / capability map data block
+                14 | 15 => info.color_formats.ycbcr420 = true,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+fn parse_cta_vendor_block(payload: &[u8], info: &mut MonitorInfo) {
+    let oui = u32::from_le_bytes([payload[0], payload[1], payload[2], 0]);
+    match oui {
+        // HDMI Forum: VRRmin in byte 9, VRRmax split between bytes 9 and 10
+        // (counting the block header as byte 0)
+        0xC45DD8 if payload.len() >= 10 => {
+            let min_hz = (payload[8] & 0x3F) as u32;
+            let max_hz = (((payload[8] & 0xC0) as u32) << 2) | payload[9] as u32;
+            if min_hz != 0 && max_hz > min_hz {
+                info.vrr_range = Some(VrrRange { min_hz, max_hz });
+            }
+        }
+        // AMD FreeSync
+        0x00001A if payload.len() >= 7 => {
+            let (min_hz, max_hz) = (payload[5] as u32, payload[6] as u32);
+            if info.vrr_range.is_none() && min_hz != 0 && max_hz > min_hz {
+                info.vrr_range = Some(VrrRange { min_hz, max_hz });
+            }
+        }
+        _ => {}
+    }
+}
+
+fn parse_hdr_static_metadata(payload: &[u8]) -> HdrStaticMetadata {
+    let eotfs = payload[0];
+    let luminance = |cv: Option<&u8>| {
+        cv.filter(|&&cv| cv != 0)
+            .map(|&cv| 50.0 * 2f32.powf(cv as f32 / 32.0))
+    };
+    let max_luminance = luminance(payload.get(2));
+    let min_luminance = payload
+        .get(4)
+        .zip(max_luminance)
+        .map(|(&cv, max)| max * (cv as f32 / 255.0).powi(2) / 100.0);
+
+    HdrStaticMetadata {
+        eotf_sdr: eotfs & 0x01 != 0,
+        eotf_hdr: eotfs & 0x02 != 0,
+        eotf_pq: eotfs & 0x04 != 0,
+        eotf_hlg: eotfs & 0x08 != 0,
+        max_luminance,
+        max_frame_average_luminance: luminance(payload.get(3)),
+        min_luminance,
+    }
+}
+
+fn parse_displayid_block(block: &[u8], info: &mut MonitorInfo) {
+    // Byte 0 is the EDID extension tag, the DisplayID section starts right after it.
+    let section = &block[1..];
+    let version = section[0] >> 4;
+    let section_len = (section[1] as usize).min(section.len() - 5);
+    let data = &section[4..4 + section_len];
+
+    let mut pos = 0;
+    while pos + 3 <= data.len() {
+        let tag = data[pos];
+        let revision = data[pos + 1];
+        let len = data[pos + 2] as usize;
+        let Some(payload) = data.get(pos + 3..pos + 3 + len) else {
+            break;
+        };
+        pos += 3 + len;
+
+        match (version, tag) {
+            // Product identification (1.x: PNP id, 2.x: IEEE OUI)
+            (1, 0x00) | (2, 0x20) if len >= 12 => {
+                let name_len = payload[11] as usize;
+                if info.model_name.is_none() {
+                    info.model_name = payload
+                        .get(12..12 + name_len)
+                        .and_then(decode_descriptor_string);
+                }
+            }
+            // Display parameters, image size in 0.1 mm. More precise than the base
+            // block sizes, so it takes precedence over them.
+            (1, 0x01) if len >= 4 => {
+                if let Some(size) = displayid_image_size(payload, false) {
+                    info.physical_size_mm = Some(size);
+                }
+            }
+            // Display parameters, image size in 0.1 mm or 1 mm (revision bit 7)
+            (2, 0x21) if len >= 4 => {
+                if let Some(size) = displayid_image_size(payload, revision & 0x80 != 0) {
+                    info.physical_size_mm = Some(size);
+                }
+            }
+            // Adaptive-Sync data block, use the first descriptor
+            (2, 0x2B) if len >= 6 => {
+                let min_hz = payload[2] as u32;
+                let max_hz = (u16::from_le_bytes([payload[3], payload[4]]) & 0x3FF) as u32 + 1;
+                if info.vrr_range.is_none() && min_hz != 0 && max_hz > min_hz {
+                    info.vrr_range = Some(VrrRange { min_hz, max_hz });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn displayid_image_size(payload: &[u8], millimeters: bool) -> Option<(u32, u32)> {
+    let width = u16::from_le_bytes([payload[0], payload[1]]) as u32;
+    let height = u16::from_le_bytes([payload[2], payload[3]]) as u32;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    if millimeters {
+        Some((width, height))
+    } else {
+        Some((width / 10, height / 10))
+    }
+}
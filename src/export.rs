@@ -0,0 +1,218 @@
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+
+use drm::buffer::{DrmFourcc, DrmModifier};
+
+use crate::dmabuf::{DmaBufDescriptor, DmaBufPlane};
+
+/// Most frames a monitor keeps exported at once.
+///
+/// Exported buffers are taken from the monitor's GBM surface, which only has a few, so
+/// holding more would leave nothing to render to.
+pub const MAX_EXPORTED_FRAMES: usize = 2;
+
+/// A frame rendered by a monitor, exported as a dma-buf
+///
+/// Returned by `Monitor::take_exported_frames()`. The file descriptors can be sent to
+/// another process (e.g. over a Unix socket with `SCM_RIGHTS`) or imported into another
+/// API. The buffer stays reserved for the consumer until
+/// `Monitor::release_exported_frame()` is called with its [`sequence`](Self::sequence);
+/// dropping the `ExportedFrame` only closes this process's descriptors.
+#[derive(Debug)]
+pub struct ExportedFrame {
+    sequence: u64,
+    width: u32,
+    height: u32,
+    format: DrmFourcc,
+    modifier: Option<DrmModifier>,
+    planes: Vec<ExportedPlane>,
+    fence: Option<OwnedFd>,
+}
+
+#[derive(Debug)]
+struct ExportedPlane {
+    fd: OwnedFd,
+    offset: u32,
+    stride: u32,
+}
+
+impl ExportedFrame {
+    /// Returns the number of the frame, increasing with each exported frame.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Returns the size of the frame as (width, height).
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Returns the pixel format of the frame.
+    pub fn format(&self) -> DrmFourcc {
+        self.format
+    }
+
+    /// Returns the memory layout of the frame, or `None` if the driver doesn't tell.
+    pub fn modifier(&self) -> Option<DrmModifier> {
+        self.modifier
+    }
+
+    /// Returns the dma-buf file descriptor of each plane.
+    pub fn fds(&self) -> impl Iterator<Item = BorrowedFd<'_>> {
+        self.planes.iter().map(|plane| plane.fd.as_fd())
+    }
+
+    /// Returns a sync file that signals once the frame is fully rendered, if the driver
+    /// supports native fences. Wait on it before reading the buffer.
+    pub fn fence(&self) -> Option<BorrowedFd<'_>> {
+        self.fence.as_ref().map(|fence| fence.as_fd())
+    }
+
+    /// Takes ownership of the fence, e.g. to hand it to another API.
+    pub fn take_fence(&mut self) -> Option<OwnedFd> {
+        self.fence.take()
+    }
+
+    /// Describes the frame as a [`DmaBufDescriptor`].
+    ///
+    /// The descriptor borrows this frame's file descriptors, so it must not outlive it.
+    pub fn descriptor(&self) -> DmaBufDescriptor {
+        DmaBufDescriptor {
+            width: self.width,
+            height: self.height,
+            format: self.format,
+            modifier: self.modifier,
+            planes: self
+                .planes
+                .iter()
+                .map(|plane| DmaBufPlane {
+                    fd: plane.fd.as_raw_fd(),
+                    offset: plane.offset,
+                    stride: plane.stride,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The ring of buffers a monitor has exported and the consumer hasn't released yet
+pub(crate) struct FrameExporter {
+    capacity: usize,
+    next_sequence: u64,
+    held: Vec<(u64, gbm::BufferObject<()>)>,
+    /// Sequence of the held buffer currently on screen
+    front: Option<u64>,
+    ready: Vec<ExportedFrame>,
+}
+
+impl FrameExporter {
+    pub(crate) fn new() -> Self {
+        FrameExporter {
+            capacity: 0,
+            next_sequence: 0,
+            held: Vec::new(),
+            front: None,
+            ready: Vec::new(),
+        }
+    }
+
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.min(MAX_EXPORTED_FRAMES);
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Exports the buffer of a frame that was just committed, if there's room in the
+    /// ring. Returns the buffer back if it wasn't exported, for the monitor to keep
+    /// while it's on screen.
+    pub(crate) fn present(
+        &mut self,
+        bo: gbm::BufferObject<()>,
+        fence_fd: Option<BorrowedFd<'_>>,
+    ) -> Option<gbm::BufferObject<()>> {
+        self.front = None;
+        if self.held.len() >= self.capacity {
+            return Some(bo);
+        }
+        match self.export(&bo, fence_fd) {
+            Ok(sequence) => {
+                self.held.push((sequence, bo));
+                self.front = Some(sequence);
+                None
+            }
+            Err(e) => {
+                eprintln!("Warning: Failed to export frame: {}", e);
+                Some(bo)
+            }
+        }
+    }
+
+    fn export(
+        &mut self,
+        bo: &gbm::BufferObject<()>,
+        fence_fd: Option<BorrowedFd<'_>>,
+    ) -> std::io::Result<u64> {
+        let planes = (0..bo.plane_count() as i32)
+            .map(|plane| {
+                let fd = bo
+                    .fd_for_plane(plane)
+                    .map_err(|e| std::io::Error::other(e.to_string()))?;
+                Ok(ExportedPlane {
+                    fd,
+                    offset: bo.offset(plane),
+                    stride: bo.stride_for_plane(plane),
+                })
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let fence = fence_fd.map(|fd| fd.try_clone_to_owned()).transpose()?;
+
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.ready.push(ExportedFrame {
+            sequence,
+            width: bo.width(),
+            height: bo.height(),
+            format: bo.format(),
+            modifier: Some(bo.modifier()).filter(|&m| m != DrmModifier::Invalid),
+            planes,
+            fence,
+        });
+        Ok(sequence)
+    }
+
+    /// Gives up an exported buffer.
+    pub(crate) fn release(&mut self, sequence: u64) -> Released {
+        let Some(index) = self.held.iter().position(|(held, _)| *held == sequence) else {
+            return Released::NotHeld;
+        };
+        let (_, bo) = self.held.remove(index);
+        if self.front == Some(sequence) {
+            self.front = None;
+            Released::OnScreen(bo)
+        } else {
+            Released::Freed
+        }
+    }
+
+    /// Returns the exported buffer currently on screen, if any.
+    pub(crate) fn front_buffer(&self) -> Option<&gbm::BufferObject<()>> {
+        let front = self.front?;
+        self.held
+            .iter()
+            .find(|(sequence, _)| *sequence == front)
+            .map(|(_, bo)| bo)
+    }
+
+    pub(crate) fn take_ready(&mut self) -> Vec<ExportedFrame> {
+        std::mem::take(&mut self.ready)
+    }
+}
+
+/// Outcome of [`FrameExporter::release`]
+pub(crate) enum Released {
+    NotHeld,
+    Freed,
+    /// The buffer is still scanned out and must be kept until the next frame replaces it
+    OnScreen(gbm::BufferObject<()>),
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use drm::buffer::{DrmFourcc, DrmModifier};
+use drm::control::{self, plane, property};
+#[cfg(feature = "gbm")]
+use drm::control::{FbCmd2Flags, framebuffer};
+
+/// Pixel format and memory layout of a monitor's scanout buffers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanoutFormat {
+    /// Pixel format, e.g. `Xrgb8888`.
+    pub format: DrmFourcc,
+    /// Memory layout (linear, tiled or compressed) reported by GBM, or `None` if the
+    /// driver doesn't tell.
+    pub modifier: Option<DrmModifier>,
+    /// True if the layout was negotiated from the plane's `IN_FORMATS` and the renderer's
+    /// modifiers, false if GBM picked an implicit layout.
+    pub explicit_modifier: bool,
+}
+
+/// The formats and modifiers a plane can scan out, from its `IN_FORMATS` property
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlaneFormats {
+    modifiers: HashMap<u32, Vec<DrmModifier>>,
+}
+
+impl PlaneFormats {
+    /// Reads the `IN_FORMATS` blob of a plane.
+    ///
+    /// Returns `None` if the driver doesn't expose it (no modifier support).
+    pub(crate) fn read(
+        card: &impl control::Device,
+        plane: plane::Handle,
+        properties: &HashMap<String, property::Info>,
+    ) -> std::io::Result<Option<Self>> {
+        let Some(prop) = properties.get("IN_FORMATS") else {
+            return Ok(None);
+        };
+        let values = card.get_properties(plane)?;
+        let Some((_, &blob_id)) = values.iter().find(|(id, _)| **id == prop.handle()) else {
+            return Ok(None);
+        };
+        let blob = card.get_property_blob(blob_id)?;
+        Ok(Self::from_in_formats_blob(&blob))
+    }
+
+    /// Parses an `IN_FORMATS` blob (`struct drm_format_modifier_blob`).
+    ///
+    /// Returns `None` if the blob is truncated.
+    pub fn from_in_formats_blob(blob: &[u8]) -> Option<Self> {
+        let u32_at = |offset: usize| {
+            blob.get(offset..offset + 4)
+                .and_then(|bytes| bytes.try_into().ok())
+                .map(u32::from_ne_bytes)
+        };
+        let u64_at = |offset: usize| {
+            blob.get(offset..offset + 8)
+                .and_then(|bytes| bytes.try_into().ok())
+                .map(u64::from_ne_bytes)
+        };
+
+        let count_formats = u32_at(8)? as usize;
+        let formats_offset = u32_at(12)? as usize;
+        let count_modifiers = u32_at(16)? as usize;
+        let modifiers_offset = u32_at(20)? as usize;
+
+        let formats = (0..count_formats)
+            .map(|i| u32_at(formats_offset + i * 4))
+            .collect::<Option<Vec<u32>>>()?;
+        let mut modifiers: HashMap<u32, Vec<DrmModifier>> =
+            formats.iter().map(|&format| (format, Vec::new())).collect();
+
+        // Each `struct drm_format_modifier` applies a modifier to the formats selected by
+        // a 64 bit mask, starting at `offset` in the format list
+        for i in 0..count_modifiers {
+            let entry = modifiers_offset + i * 24;
+            let mask = u64_at(entry)?;
+            let offset = u32_at(entry + 8)? as usize;
+            let modifier = u64_at(entry + 16)?;
+            for bit in (0..64).filter(|bit| mask & (1 << bit) != 0) {
+                if let Some(format) = formats.get(offset + bit) {
+                    modifiers
+                        .entry(*format)
+                        .or_default()
+                        .push(DrmModifier::from(modifier));
+                }
+            }
+        }
+
+        Some(PlaneFormats { modifiers })
+    }
+
+    /// Returns the formats the plane can scan out.
+    pub fn formats(&self) -> impl Iterator<Item = DrmFourcc> + '_ {
+        self.modifiers
+            .keys()
+            .filter_map(|&format| DrmFourcc::try_from(format).ok())
+    }
+
+    /// Returns the modifiers the plane accepts for `format`.
+    pub fn modifiers(&self, format: DrmFourcc) -> &[DrmModifier] {
+        self.modifiers
+            .get(&(format as u32))
+            .map_or(&[], |modifiers| modifiers.as_slice())
+    }
+
+    /// Returns true if the plane can scan out `format` with `modifier`.
+    pub fn supports(&self, format: DrmFourcc, modifier: DrmModifier) -> bool {
+        self.modifiers(format).contains(&modifier)
+    }
+}
+
+/// Adds a framebuffer for a rendered buffer, passing its modifier to the kernel if the
+/// surface was created with explicit modifiers.
+#[cfg(feature = "gbm")]
+pub(crate) fn add_scanout_framebuffer(
+    card: &impl control::Device,
+    bo: &gbm::BufferObject<()>,
+    depth: u32,
+    explicit_modifier: bool,
+) -> std::io::Result<framebuffer::Handle> {
+    if explicit_modifier && bo.modifier() != DrmModifier::Invalid {
+        card.add_planar_framebuffer(bo, FbCmd2Flags::MODIFIERS)
+    } else {
+        card.add_framebuffer(bo, depth, 32)
+    }
+}
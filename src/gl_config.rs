@@ -0,0 +1,197 @@
+use std::fmt;
+
+use drm::buffer::DrmFourcc;
+use glutin::api::egl;
+use glutin::config::{Api, GlConfig as _};
+use glutin::context::{
+    ContextApi, ContextAttributes, ContextAttributesBuilder, Robustness, Version,
+};
+use raw_window_handle::RawWindowHandle;
+
+/// Attributes of the EGL config and OpenGL ES context a monitor renders with
+///
+/// Pass it to [`EasyDRMOptions::gl_config`](crate::EasyDRMOptions::gl_config), or to
+/// [`EasyDRMOptions::monitor_gl_config`](crate::EasyDRMOptions::monitor_gl_config) for
+/// some monitors only. Sizes are minimums, except [`samples`](Self::samples) which must
+/// match exactly. Only configs whose visual matches the scanout format are considered;
+/// if none is left, setup fails with `GlesContextError::NoMatchingConfig` listing them.
+///
+/// # Example
+///
+/// ```ignore
+/// let options = EasyDRMOptions::new().gl_config(
+///     GlConfig::new().depth_bits(24).stencil_bits(8).gles_version(3, 0),
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GlConfig {
+    samples: u8,
+    depth_bits: u8,
+    stencil_bits: u8,
+    alpha: bool,
+    srgb: bool,
+    version: Option<(u8, u8)>,
+    debug: bool,
+    robust: bool,
+}
+
+impl GlConfig {
+    /// Creates the default config: single sampled, no depth or stencil buffer, no alpha,
+    /// any GLES version.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of MSAA samples of the surface, `0` for none (the default).
+    pub fn samples(mut self, samples: u8) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// Sets the minimum number of depth bits.
+    pub fn depth_bits(mut self, bits: u8) -> Self {
+        self.depth_bits = bits;
+        self
+    }
+
+    /// Sets the minimum number of stencil bits.
+    pub fn stencil_bits(mut self, bits: u8) -> Self {
+        self.stencil_bits = bits;
+        self
+    }
+
+    /// Requests an alpha channel. Monitors then scan out `Argb8888` instead of
+    /// `Xrgb8888`.
+    pub fn alpha(mut self, alpha: bool) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    /// Requests an sRGB capable config and surface.
+    pub fn srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    /// Requests a specific OpenGL ES version, e.g. `(3, 2)`. By default the driver picks.
+    pub fn gles_version(mut self, major: u8, minor: u8) -> Self {
+        self.version = Some((major, minor));
+        self
+    }
+
+    /// Requests a debug context.
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// Requests a robust context, which is lost on GPU resets instead of crashing.
+    pub fn robust(mut self, robust: bool) -> Self {
+        self.robust = robust;
+        self
+    }
+
+    /// Returns the format monitors scan out with this config.
+    pub(crate) fn scanout_format(&self) -> DrmFourcc {
+        if self.alpha {
+            DrmFourcc::Argb8888
+        } else {
+            DrmFourcc::Xrgb8888
+        }
+    }
+
+    pub(crate) fn is_srgb(&self) -> bool {
+        self.srgb
+    }
+
+    /// Returns true if `config` has the requested attributes, ignoring its visual.
+    pub(crate) fn accepts(&self, config: &impl glutin::config::GlConfig) -> bool {
+        let api = match self.version {
+            Some((major, _)) if major >= 3 => Api::GLES3,
+            _ => Api::GLES2,
+        };
+        config.api().contains(api)
+            && config.num_samples() == self.samples
+            && config.depth_size() >= self.depth_bits
+            && config.stencil_size() >= self.stencil_bits
+            && (!self.alpha || config.alpha_size() > 0)
+            && (!self.srgb || config.srgb_capable())
+    }
+
+    /// Returns the context attributes, sharing objects with `share_with` if set.
+    pub(crate) fn context_attributes(
+        &self,
+        window: Option<RawWindowHandle>,
+        share_with: Option<&egl::context::NotCurrentContext>,
+    ) -> ContextAttributes {
+        let robustness = if self.robust {
+            Robustness::RobustLoseContextOnReset
+        } else {
+            Robustness::NotRobust
+        };
+        let mut builder = ContextAttributesBuilder::new();
+        if let Some(context) = share_with {
+            builder = builder.with_sharing(context);
+        }
+        builder
+            .with_context_api(ContextApi::Gles(
+                self.version
+                    .map(|(major, minor)| Version::new(major, minor)),
+            ))
+            .with_debug(self.debug)
+            .with_robustness(robustness)
+            .build(window)
+    }
+}
+
+impl fmt::Display for GlConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.version {
+            Some((major, minor)) => write!(f, "GLES {}.{}", major, minor)?,
+            None => write!(f, "GLES")?,
+        }
+        if self.samples > 0 {
+            write!(f, ", {}x MSAA", self.samples)?;
+        }
+        if self.depth_bits > 0 {
+            write!(f, ", depth {}", self.depth_bits)?;
+        }
+        if self.stencil_bits > 0 {
+            write!(f, ", stencil {}", self.stencil_bits)?;
+        }
+        for (set, name) in [
+            (self.alpha, "alpha"),
+            (self.srgb, "sRGB"),
+            (self.debug, "debug"),
+            (self.robust, "robust"),
+        ] {
+            if set {
+                write!(f, ", {}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Describes an EGL config for `GlesContextError::NoMatchingConfig`.
+pub(crate) fn describe_config(config: &egl::config::Config) -> String {
+    let visual = config.native_visual();
+    let format = match DrmFourcc::try_from(visual) {
+        Ok(format) => format.to_string(),
+        Err(_) if visual == 0 => "no visual".to_string(),
+        Err(_) => format!("visual {:#x}", visual),
+    };
+    let mut description = format!(
+        "{} ({}x MSAA, depth {}, stencil {}, alpha {}",
+        format,
+        config.num_samples(),
+        config.depth_size(),
+        config.stencil_size(),
+        config.alpha_size(),
+    );
+    if config.srgb_capable() {
+        description.push_str(", sRGB");
+    }
+    description.push(')');
+    description
+}
@@ -0,0 +1,199 @@
+use std::ffi::{CStr, c_char, c_void};
+use std::fmt;
+use std::sync::Arc;
+
+use drm::control::connector;
+
+use crate::gl::{self, types::GLenum};
+
+/// Where a GL debug message comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GlDebugSource {
+    /// The OpenGL ES API, e.g. an invalid argument.
+    Api,
+    /// The window system (EGL).
+    WindowSystem,
+    /// The shader compiler or linker.
+    ShaderCompiler,
+    /// A tool such as a debugger.
+    ThirdParty,
+    /// The application itself, through `glDebugMessageInsertKHR`.
+    Application,
+    Other,
+}
+
+/// What a GL debug message is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GlDebugType {
+    /// A GL error, the call had no effect.
+    Error,
+    DeprecatedBehavior,
+    UndefinedBehavior,
+    Portability,
+    Performance,
+    /// An annotation, e.g. from `glDebugMessageInsertKHR`.
+    Marker,
+    PushGroup,
+    PopGroup,
+    Other,
+}
+
+/// How important a GL debug message is, from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GlDebugSeverity {
+    Notification,
+    Low,
+    Medium,
+    /// Errors and undefined behavior.
+    High,
+}
+
+/// A message from the driver's `KHR_debug` output, see
+/// [`EasyDRMOptions::gl_debug_output`](crate::EasyDRMOptions::gl_debug_output)
+///
+/// Its `Display` output reads like `[HDMI-A-1] GL error (API, high): ...`.
+#[derive(Debug, Clone, Copy)]
+pub struct GlDebugMessage<'a> {
+    /// Connector of the monitor whose context produced the message.
+    pub connector: connector::Handle,
+    /// Kernel name of that connector, e.g. `HDMI-A-1` or `VIRTUAL-1`.
+    pub connector_name: &'a str,
+    pub source: GlDebugSource,
+    pub kind: GlDebugType,
+    pub severity: GlDebugSeverity,
+    /// Driver specific message id.
+    pub id: u32,
+    pub message: &'a str,
+}
+
+impl fmt::Display for GlDebugMessage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            GlDebugType::Error => "error",
+            GlDebugType::DeprecatedBehavior => "deprecated behavior",
+            GlDebugType::UndefinedBehavior => "undefined behavior",
+            GlDebugType::Portability => "portability issue",
+            GlDebugType::Performance => "performance issue",
+            GlDebugType::Marker => "marker",
+            GlDebugType::PushGroup => "push group",
+            GlDebugType::PopGroup => "pop group",
+            GlDebugType::Other => "message",
+        };
+        let source = match self.source {
+            GlDebugSource::Api => "API",
+            GlDebugSource::WindowSystem => "window system",
+            GlDebugSource::ShaderCompiler => "shader compiler",
+            GlDebugSource::ThirdParty => "third party",
+            GlDebugSource::Application => "application",
+            GlDebugSource::Other => "other",
+        };
+        let severity = match self.severity {
+            GlDebugSeverity::Notification => "notification",
+            GlDebugSeverity::Low => "low",
+            GlDebugSeverity::Medium => "medium",
+            GlDebugSeverity::High => "high",
+        };
+        write!(
+            f,
+            "[{}] GL {} ({}, {}): {}",
+            self.connector_name, kind, source, severity, self.message
+        )
+    }
+}
+
+/// Receives the GL debug messages of every monitor, see
+/// [`EasyDRMOptions::gl_debug_output`](crate::EasyDRMOptions::gl_debug_output)
+pub type GlDebugLogger = Arc<dyn Fn(&GlDebugMessage<'_>) + Send + Sync>;
+
+/// The monitor a context's debug messages are tagged with, passed to the driver's
+/// callback as its user parameter
+pub(crate) struct GlDebugOutput {
+    connector: connector::Handle,
+    connector_name: String,
+    logger: GlDebugLogger,
+}
+
+impl GlDebugOutput {
+    pub(crate) fn new(
+        connector: connector::Handle,
+        connector_name: &str,
+        logger: &GlDebugLogger,
+    ) -> Self {
+        GlDebugOutput {
+            connector,
+            connector_name: connector_name.to_string(),
+            logger: Arc::clone(logger),
+        }
+    }
+
+    /// Installs the callback in the context `gl` is current in. Returns false if the
+    /// driver doesn't support `GL_KHR_debug`.
+    ///
+    /// `self` is handed to the driver, it must not move and outlive the context.
+    pub(crate) fn install(&self, gl: &gl::Gles2) -> bool {
+        if !gl.DebugMessageCallbackKHR.is_loaded() {
+            return false;
+        }
+        unsafe {
+            gl.Enable(gl::DEBUG_OUTPUT_KHR);
+            // Report messages on the thread making the faulty call, so they can be traced
+            gl.Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS_KHR);
+            gl.DebugMessageCallbackKHR(Some(debug_callback), (self as *const GlDebugOutput).cast());
+        }
+        true
+    }
+}
+
+extern "system" fn debug_callback(
+    source: GLenum,
+    kind: GLenum,
+    id: u32,
+    severity: GLenum,
+    length: i32,
+    message: *const c_char,
+    user_param: *mut c_void,
+) {
+    let output = unsafe { &*(user_param as *const GlDebugOutput) };
+    let message = match usize::try_from(length) {
+        Ok(length) => unsafe { std::slice::from_raw_parts(message.cast::<u8>(), length) },
+        // A negative length means the message is null terminated
+        Err(_) => unsafe { CStr::from_ptr(message) }.to_bytes(),
+    };
+    let message = String::from_utf8_lossy(message);
+    let message = GlDebugMessage {
+        connector: output.connector,
+        connector_name: &output.connector_name,
+        source: match source {
+            gl::DEBUG_SOURCE_API_KHR => GlDebugSource::Api,
+            gl::DEBUG_SOURCE_WINDOW_SYSTEM_KHR => GlDebugSource::WindowSystem,
+            gl::DEBUG_SOURCE_SHADER_COMPILER_KHR => GlDebugSource::ShaderCompiler,
+            gl::DEBUG_SOURCE_THIRD_PARTY_KHR => GlDebugSource::ThirdParty,
+            gl::DEBUG_SOURCE_APPLICATION_KHR => GlDebugSource::Application,
+            _ => GlDebugSource::Other,
+        },
+        kind: match kind {
+            gl::DEBUG_TYPE_ERROR_KHR => GlDebugType::Error,
+            gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR_KHR => GlDebugType::DeprecatedBehavior,
+            gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR_KHR => GlDebugType::UndefinedBehavior,
+            gl::DEBUG_TYPE_PORTABILITY_KHR => GlDebugType::Portability,
+            gl::DEBUG_TYPE_PERFORMANCE_KHR => GlDebugType::Performance,
+            gl::DEBUG_TYPE_MARKER_KHR => GlDebugType::Marker,
+            gl::DEBUG_TYPE_PUSH_GROUP_KHR => GlDebugType::PushGroup,
+            gl::DEBUG_TYPE_POP_GROUP_KHR => GlDebugType::PopGroup,
+            _ => GlDebugType::Other,
+        },
+        severity: match severity {
+            gl::DEBUG_SEVERITY_HIGH_KHR => GlDebugSeverity::High,
+            gl::DEBUG_SEVERITY_MEDIUM_KHR => GlDebugSeverity::Medium,
+            gl::DEBUG_SEVERITY_LOW_KHR => GlDebugSeverity::Low,
+            _ => GlDebugSeverity::Notification,
+        },
+        id,
+        message: &message,
+    };
+    // Unwinding into the driver would abort
+    let logger = &output.logger;
+    if std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| logger(&message))).is_err() {
+        eprintln!("Warning: GL debug logger panicked on: {}", message);
+    }
+}
@@ -1,21 +1,32 @@
 use std::{ffi::CString, num::NonZero, ptr::NonNull};
 
-use drm::control;
-use gbm::{AsRaw, BufferObjectFlags, Device as GbmDevice};
+use gbm::{AsRaw, BufferObjectFlags, Device as GbmDevice, Modifier};
 use glutin::api::egl;
-use glutin::config::ConfigTemplateBuilder;
-use glutin::context::ContextAttributesBuilder;
+use glutin::config::GlConfig as _;
+use glutin::config::{Api, ConfigSurfaceTypes, ConfigTemplateBuilder};
+use glutin::context::{AsRawContext, RawContext};
+use glutin::display::{AsRawDisplay, RawDisplay};
 use glutin::prelude::*;
-use glutin::surface::{SurfaceAttributesBuilder, WindowSurface};
+use glutin::surface::{PbufferSurface, SurfaceAttributesBuilder, WindowSurface};
 use raw_window_handle::{GbmDisplayHandle, GbmWindowHandle, RawDisplayHandle, RawWindowHandle};
 use thiserror::Error;
 
+use crate::capture::{CaptureError, CapturedFrame};
+use crate::gl_config::{GlConfig, describe_config};
+use crate::gl_debug::GlDebugOutput;
+use crate::share_group::ShareGroup;
+
 #[derive(Debug, Error)]
 pub enum GlesContextError {
     #[error("Failed to create EGL display")]
     DisplayCreationFailed,
     #[error("No suitable EGL config found")]
     NoConfigFound,
+    #[error("No EGL config matches {requested}, available configs: {}", available.join("; "))]
+    NoMatchingConfig {
+        requested: GlConfig,
+        available: Vec<String>,
+    },
     #[error("Failed to create GBM surface")]
     GbmSurfaceCreationFailed,
     #[error("Failed to create EGL surface: {0}")]
@@ -24,45 +35,127 @@ pub enum GlesContextError {
     EglContextCreationFailed(String),
     #[error("Failed to make context current")]
     MakeCurrentFailed,
+    #[error("The context renders offscreen and has no buffers to scan out")]
+    Offscreen,
+    #[error("The monitor's render backend has no OpenGL ES context")]
+    NoContext,
+    #[error("Contexts aren't shared, enable EasyDRMOptions::shared_gl_contexts()")]
+    NotShared,
+}
+
+/// The EGL surface a context renders to
+enum ContextSurface {
+    /// Window surface on a GBM surface, whose buffers are scanned out
+    Window(egl::surface::Surface<WindowSurface>),
+    /// Pbuffer of a headless context, read back with `glReadPixels`
+    Pbuffer(egl::surface::Surface<PbufferSurface>),
 }
 
 pub struct GlesContext {
     display: egl::display::Display,
-    surface: egl::surface::Surface<WindowSurface>,
+    surface: ContextSurface,
     context: egl::context::PossiblyCurrentContext,
-    gbm_surface: gbm::Surface<()>,
+    gbm_surface: Option<gbm::Surface<()>>,
+    format: gbm::Format,
+    explicit_modifier: bool,
     gl: crate::gl::Gles2,
+    /// Read by the driver's debug callback, so it's dropped after the context
+    debug_output: Option<Box<GlDebugOutput>>,
 }
 
 impl GlesContext {
-    /// Creates a new OpenGL ES context for the given monitor mode
+    /// Creates a new OpenGL ES context rendering to a surface of the given size, in the
+    /// scanout format of `gl_config`
     pub fn new(
         gbm_device: &GbmDevice<std::fs::File>,
-        mode: &control::Mode,
+        width: u16,
+        height: u16,
+        gl_config: &GlConfig,
+        share_group: Option<&ShareGroup>,
     ) -> Result<Self, GlesContextError> {
-        let (width, height) = mode.size();
+        let format = gl_config.scanout_format();
+        Self::create(
+            gbm_device,
+            width,
+            height,
+            format,
+            None,
+            gl_config,
+            share_group,
+        )
+    }
 
-        // Create EGL display from GBM device
-        let raw_display_handle = RawDisplayHandle::Gbm(GbmDisplayHandle::new(
-            NonNull::new(gbm_device.as_raw() as *mut std::ffi::c_void)
-                .ok_or(GlesContextError::DisplayCreationFailed)?,
-        ));
+    /// Creates a new OpenGL ES context whose buffers use one of `scanout_modifiers`,
+    /// typically the modifiers a plane lists in `IN_FORMATS` for `format`.
+    ///
+    /// The modifiers are intersected with the ones EGL can render to, so GBM can pick a
+    /// tiled or compressed layout. Falls back to an implicit layout (like
+    /// [`new`](Self::new)) if nothing is left.
+    pub fn with_modifiers(
+        gbm_device: &GbmDevice<std::fs::File>,
+        width: u16,
+        height: u16,
+        format: gbm::Format,
+        scanout_modifiers: &[Modifier],
+        gl_config: &GlConfig,
+        share_group: Option<&ShareGroup>,
+    ) -> Result<Self, GlesContextError> {
+        Self::create(
+            gbm_device,
+            width,
+            height,
+            format,
+            Some(scanout_modifiers),
+            gl_config,
+            share_group,
+        )
+    }
 
-        let display = unsafe { egl::display::Display::new(raw_display_handle) }
-            .map_err(|_| GlesContextError::DisplayCreationFailed)?;
+    fn create(
+        gbm_device: &GbmDevice<std::fs::File>,
+        width: u16,
+        height: u16,
+        format: gbm::Format,
+        scanout_modifiers: Option<&[Modifier]>,
+        gl_config: &GlConfig,
+        share_group: Option<&ShareGroup>,
+    ) -> Result<Self, GlesContextError> {
+        // Shared contexts must live on the display of their group
+        let display = match share_group {
+            Some(group) => group.display().clone(),
+            None => gbm_display(gbm_device)?,
+        };
 
-        // Find best EGL config
-        let config = find_egl_config(&display)?;
+        let config = find_egl_config(&display, Some(format), gl_config)?;
+
+        // Only keep the modifiers both the plane and the renderer support
+        let modifiers: Vec<Modifier> = match scanout_modifiers {
+            Some(scanout_modifiers) => renderable_modifiers(&display, format)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|modifier| scanout_modifiers.contains(modifier))
+                .collect(),
+            None => Vec::new(),
+        };
 
         // Create GBM surface
-        let gbm_surface = gbm_device
-            .create_surface::<()>(
+        let explicit_modifier = !modifiers.is_empty();
+        let gbm_surface = if explicit_modifier {
+            gbm_device.create_surface_with_modifiers::<()>(
+                width.into(),
+                height.into(),
+                format,
+                modifiers.into_iter(),
+            )
+        } else {
+            gbm_device.create_surface::<()>(
                 width.into(),
                 height.into(),
-                gbm::Format::Xrgb8888,
+                format,
                 BufferObjectFlags::SCANOUT | BufferObjectFlags::RENDERING,
             )
-            .map_err(|_| GlesContextError::GbmSurfaceCreationFailed)?;
+        }
+        .map_err(|_| GlesContextError::GbmSurfaceCreationFailed)?;
 
         // Create EGL window surface
         let raw_window_handle = RawWindowHandle::Gbm(GbmWindowHandle::new(
@@ -74,13 +167,15 @@ impl GlesContext {
             display
                 .create_window_surface(
                     &config,
-                    &SurfaceAttributesBuilder::<WindowSurface>::new().build(
-                        raw_window_handle,
-                        NonZero::new(width as u32)
-                            .ok_or(GlesContextError::GbmSurfaceCreationFailed)?,
-                        NonZero::new(height as u32)
-                            .ok_or(GlesContextError::GbmSurfaceCreationFailed)?,
-                    ),
+                    &SurfaceAttributesBuilder::<WindowSurface>::new()
+                        .with_srgb(Some(gl_config.is_srgb()))
+                        .build(
+                            raw_window_handle,
+                            NonZero::new(width as u32)
+                                .ok_or(GlesContextError::GbmSurfaceCreationFailed)?,
+                            NonZero::new(height as u32)
+                                .ok_or(GlesContextError::GbmSurfaceCreationFailed)?,
+                        ),
                 )
                 .map_err(|e| GlesContextError::EglSurfaceCreationFailed(e.to_string()))?
         };
@@ -90,7 +185,10 @@ impl GlesContext {
             display
                 .create_context(
                     &config,
-                    &ContextAttributesBuilder::new().build(Some(raw_window_handle)),
+                    &gl_config.context_attributes(
+                        Some(raw_window_handle),
+                        share_group.map(ShareGroup::root),
+                    ),
                 )
                 .map_err(|e| GlesContextError::EglContextCreationFailed(e.to_string()))?
                 .make_current(&surface)
@@ -105,35 +203,190 @@ impl GlesContext {
 
         Ok(GlesContext {
             display,
-            surface,
+            surface: ContextSurface::Window(surface),
             context,
-            gbm_surface,
+            gbm_surface: Some(gbm_surface),
+            format,
+            explicit_modifier,
             gl,
+            debug_output: None,
+        })
+    }
+
+    /// Creates a headless OpenGL ES context rendering to a `width`x`height` pbuffer on
+    /// `egl_device`, without any DRM card or GBM device
+    ///
+    /// Its frames can't be scanned out; read them with [`read_frame`](Self::read_frame).
+    pub fn offscreen(
+        egl_device: &egl::device::Device,
+        width: u16,
+        height: u16,
+        gl_config: &GlConfig,
+        share_group: Option<&ShareGroup>,
+    ) -> Result<Self, GlesContextError> {
+        let display = match share_group {
+            Some(group) => group.display().clone(),
+            None => device_display(egl_device)?,
+        };
+
+        // Pbuffers have no scanout format to match
+        let config = find_egl_config(&display, None, gl_config)?;
+
+        let surface = unsafe {
+            display
+                .create_pbuffer_surface(
+                    &config,
+                    &SurfaceAttributesBuilder::<PbufferSurface>::new()
+                        .with_srgb(Some(gl_config.is_srgb()))
+                        .build(
+                            NonZero::new(width as u32).ok_or(
+                                GlesContextError::EglSurfaceCreationFailed(
+                                    "zero width".to_string(),
+                                ),
+                            )?,
+                            NonZero::new(height as u32).ok_or(
+                                GlesContextError::EglSurfaceCreationFailed(
+                                    "zero height".to_string(),
+                                ),
+                            )?,
+                        ),
+                )
+                .map_err(|e| GlesContextError::EglSurfaceCreationFailed(e.to_string()))?
+        };
+
+        let context = unsafe {
+            display
+                .create_context(
+                    &config,
+                    &gl_config.context_attributes(None, share_group.map(ShareGroup::root)),
+                )
+                .map_err(|e| GlesContextError::EglContextCreationFailed(e.to_string()))?
+                .make_current(&surface)
+                .map_err(|_| GlesContextError::MakeCurrentFailed)?
+        };
+
+        let gl = crate::gl::Gles2::load_with(|symbol| {
+            let c_symbol = CString::new(symbol).unwrap();
+            display.get_proc_address(&c_symbol)
+        });
+
+        Ok(GlesContext {
+            display,
+            surface: ContextSurface::Pbuffer(surface),
+            context,
+            gbm_surface: None,
+            format: gbm::Format::Xbgr8888,
+            explicit_modifier: false,
+            gl,
+            debug_output: None,
         })
     }
 
     /// Makes this context current for OpenGL operations
     pub fn make_current(&self) -> Result<(), GlesContextError> {
-        self.context
-            .make_current(&self.surface)
-            .map_err(|_| GlesContextError::MakeCurrentFailed)?;
+        match &self.surface {
+            ContextSurface::Window(surface) => self.context.make_current(surface),
+            ContextSurface::Pbuffer(surface) => self.context.make_current(surface),
+        }
+        .map_err(|_| GlesContextError::MakeCurrentFailed)?;
+        Ok(())
+    }
+
+    /// Forwards the driver's `KHR_debug` messages of this context to `output`'s logger.
+    /// Only warns if the driver doesn't support it.
+    pub(crate) fn enable_debug_output(
+        &mut self,
+        output: GlDebugOutput,
+    ) -> Result<(), GlesContextError> {
+        self.make_current()?;
+        let output = Box::new(output);
+        if !output.install(&self.gl) {
+            eprintln!("Warning: GL_KHR_debug is not supported, GL debug output is disabled");
+            return Ok(());
+        }
+        self.debug_output = Some(output);
+        Ok(())
+    }
+
+    /// Releases this context from the calling thread if it's current there, so another
+    /// thread can make it current.
+    pub(crate) fn release_current(&self) -> Result<(), GlesContextError> {
+        if self.context.is_current() {
+            self.context
+                .make_not_current_in_place()
+                .map_err(|_| GlesContextError::MakeCurrentFailed)?;
+        }
         Ok(())
     }
 
     /// Swaps buffers and returns the new buffer object for presentation
     pub fn swap_buffers(&mut self) -> Result<gbm::BufferObject<()>, GlesContextError> {
+        let (ContextSurface::Window(surface), Some(gbm_surface)) =
+            (&self.surface, &self.gbm_surface)
+        else {
+            return Err(GlesContextError::Offscreen);
+        };
+
         // Swap EGL buffers
-        self.surface
+        surface
             .swap_buffers(&self.context)
             .map_err(|_| GlesContextError::MakeCurrentFailed)?;
 
         // Lock front buffer from GBM surface
-        let bo = unsafe { self.gbm_surface.lock_front_buffer() }
+        let bo = unsafe { gbm_surface.lock_front_buffer() }
             .map_err(|_| GlesContextError::GbmSurfaceCreationFailed)?;
 
         Ok(bo)
     }
 
+    /// Returns true for headless contexts created with [`offscreen`](Self::offscreen).
+    pub fn is_offscreen(&self) -> bool {
+        matches!(self.surface, ContextSurface::Pbuffer(_))
+    }
+
+    /// Reads what was rendered to the default framebuffer. The context must be current.
+    pub(crate) fn read_frame(&self) -> Result<CapturedFrame, CaptureError> {
+        let (width, height) = match &self.surface {
+            ContextSurface::Window(surface) => (surface.width(), surface.height()),
+            ContextSurface::Pbuffer(surface) => (surface.width(), surface.height()),
+        };
+        let (width, height) = (width.unwrap_or(0), height.unwrap_or(0));
+        let row_length = width as usize * 4;
+        let mut pixels = vec![0u8; row_length * height as usize];
+        unsafe {
+            let mut previous = 0;
+            self.gl
+                .GetIntegerv(crate::gl::READ_FRAMEBUFFER_BINDING, &mut previous);
+            self.gl.BindFramebuffer(crate::gl::READ_FRAMEBUFFER, 0);
+            self.gl.PixelStorei(crate::gl::PACK_ALIGNMENT, 4);
+            self.gl.ReadPixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                crate::gl::RGBA,
+                crate::gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut std::ffi::c_void,
+            );
+            self.gl
+                .BindFramebuffer(crate::gl::READ_FRAMEBUFFER, previous as u32);
+        }
+        // GL rows start at the bottom
+        let flipped: Vec<u8> = pixels
+            .chunks(row_length.max(1))
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        CapturedFrame::from_pixels(
+            width,
+            height,
+            gbm::Format::Xbgr8888,
+            &flipped,
+            row_length as u32,
+        )
+    }
+
     /// Gets a function pointer for loading OpenGL functions
     pub fn get_proc_address(&self, symbol: &str) -> *const std::ffi::c_void {
         let c_symbol = CString::new(symbol).unwrap();
@@ -145,26 +398,232 @@ impl GlesContext {
         &self.gl
     }
 
-    /// Gets a reference to the EGL display for advanced operations (like fences)
-    pub(crate) fn display(&self) -> &egl::display::Display {
-        &self.display
+    /// Returns the pixel format of the surface's buffers
+    pub fn format(&self) -> gbm::Format {
+        self.format
+    }
+
+    /// Returns true if the surface was created with explicit modifiers, so framebuffers
+    /// must be added with the buffer's modifier
+    pub fn has_explicit_modifier(&self) -> bool {
+        self.explicit_modifier
+    }
+
+    /// Returns the raw `EGLDisplay` of this context.
+    pub(crate) fn raw_display(&self) -> Option<*const std::ffi::c_void> {
+        match self.display.raw_display() {
+            RawDisplay::Egl(egl_display) => Some(egl_display),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    /// Loads the EGL extension functions glutin doesn't expose.
+    pub(crate) fn egl_extensions(&self) -> crate::egl::Egl {
+        load_egl_extensions(&self.display)
+    }
+
+    /// Returns a token to check later whether this context is current.
+    pub(crate) fn token(&self) -> ContextToken {
+        let context = match self.context.raw_context() {
+            RawContext::Egl(context) => context,
+            #[allow(unreachable_patterns)]
+            _ => std::ptr::null(),
+        };
+        ContextToken {
+            egl: self.egl_extensions(),
+            context,
+        }
+    }
+
+    /// Creates an EGL fence for GPU->DRM synchronization
+    /// Returns (fence_fd, sync_object)
+    pub(crate) fn create_native_fence(&self) -> Result<(i32, *mut std::ffi::c_void), String> {
+        unsafe {
+            // Get EGL bindings from glutin display
+            let egl = self.display.egl();
+
+            const EGL_SYNC_FENCE_KHR: u32 = 0x3144;
+
+            // Create EGL sync fence
+            let sync = egl.CreateSyncKHR(
+                egl.GetCurrentDisplay(),
+                EGL_SYNC_FENCE_KHR,
+                std::ptr::null(),
+            );
+
+            if sync.is_null() {
+                return Err("Failed to create EGL sync".to_string());
+            }
+
+            // Duplicate as a native fence FD for DRM
+            let fence_fd = egl.DupNativeFenceFDANDROID(egl.GetCurrentDisplay(), sync);
+
+            if fence_fd < 0 {
+                return Err(format!("Failed to duplicate fence FD: fence_fd={fence_fd}"));
+            }
+
+            Ok((fence_fd, sync as *mut std::ffi::c_void))
+        }
+    }
+
+    /// Destroys an EGL sync object
+    pub(crate) fn destroy_sync(&self, sync: *mut std::ffi::c_void) {
+        unsafe {
+            // Get EGL bindings from glutin display
+            let egl = self.display.egl();
+            let egl_display = egl.GetCurrentDisplay();
+
+            // Destroy the sync object
+            egl.DestroySyncKHR(egl_display, sync);
+        }
     }
 }
 
 // GlesContext uses RAII - all fields are automatically dropped
 
-/// Finds the best EGL config with the highest number of samples
-fn find_egl_config(
+/// Identifies a context, so GL objects created in it are only deleted while it's current
+pub(crate) struct ContextToken {
+    egl: crate::egl::Egl,
+    context: crate::egl::types::EGLContext,
+}
+
+impl ContextToken {
+    pub(crate) fn is_current(&self) -> bool {
+        unsafe { self.egl.GetCurrentContext() == self.context }
+    }
+}
+
+/// Returns the modifiers EGL can render `format` with (leaving out external-only ones),
+/// or `None` if the driver doesn't support `EGL_EXT_image_dma_buf_import_modifiers`
+fn renderable_modifiers(
     display: &egl::display::Display,
+    format: gbm::Format,
+) -> Option<Vec<Modifier>> {
+    let egl_display = match display.raw_display() {
+        RawDisplay::Egl(egl_display) => egl_display,
+        #[allow(unreachable_patterns)]
+        _ => return None,
+    };
+    let egl = load_egl_extensions(display);
+    if !egl.QueryDmaBufModifiersEXT.is_loaded() {
+        return None;
+    }
+
+    unsafe {
+        let mut count = 0;
+        if egl.QueryDmaBufModifiersEXT(
+            egl_display,
+            format as i32,
+            0,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut count,
+        ) == 0
+        {
+            return None;
+        }
+        let mut modifiers = vec![0u64; count as usize];
+        let mut external_only = vec![0u32; count as usize];
+        if egl.QueryDmaBufModifiersEXT(
+            egl_display,
+            format as i32,
+            count,
+            modifiers.as_mut_ptr(),
+            external_only.as_mut_ptr(),
+            &mut count,
+        ) == 0
+        {
+            return None;
+        }
+        Some(
+            modifiers
+                .into_iter()
+                .zip(external_only)
+                .take(count as usize)
+                .filter(|&(_, external_only)| external_only == 0)
+                .map(|(modifier, _)| Modifier::from(modifier))
+                .collect(),
+        )
+    }
+}
+
+/// Opens the EGL display of a GBM device.
+pub(crate) fn gbm_display(
+    gbm_device: &GbmDevice<std::fs::File>,
+) -> Result<egl::display::Display, GlesContextError> {
+    let raw_display_handle = RawDisplayHandle::Gbm(GbmDisplayHandle::new(
+        NonNull::new(gbm_device.as_raw() as *mut std::ffi::c_void)
+            .ok_or(GlesContextError::DisplayCreationFailed)?,
+    ));
+    unsafe { egl::display::Display::new(raw_display_handle) }
+        .map_err(|_| GlesContextError::DisplayCreationFailed)
+}
+
+/// Opens the EGL display of a headless EGL device.
+pub(crate) fn device_display(
+    egl_device: &egl::device::Device,
+) -> Result<egl::display::Display, GlesContextError> {
+    unsafe { egl::display::Display::with_device(egl_device, None) }
+        .map_err(|_| GlesContextError::DisplayCreationFailed)
+}
+
+fn load_egl_extensions(display: &egl::display::Display) -> crate::egl::Egl {
+    crate::egl::Egl::load_with(|symbol| {
+        let c_symbol = CString::new(symbol).unwrap();
+        display.get_proc_address(&c_symbol)
+    })
+}
+
+/// Finds the EGL config with the attributes of `gl_config` and the fewest extra bits
+///
+/// For window surfaces (`format` is set), configs whose native visual matches the GBM
+/// surface format are required, as EGL refuses to create a window surface with a
+/// mismatched config on most drivers. Drivers that don't report visuals get any config.
+pub(crate) fn find_egl_config(
+    display: &egl::display::Display,
+    format: Option<gbm::Format>,
+    gl_config: &GlConfig,
 ) -> Result<egl::config::Config, GlesContextError> {
-    unsafe { display.find_configs(ConfigTemplateBuilder::new().build()) }
+    let surface_type = match format {
+        Some(_) => ConfigSurfaceTypes::WINDOW,
+        None => ConfigSurfaceTypes::PBUFFER,
+    };
+    let template = ConfigTemplateBuilder::new()
+        .with_surface_type(surface_type)
+        .with_api(Api::GLES2)
+        .build();
+    let configs: Vec<egl::config::Config> = unsafe { display.find_configs(template) }
         .map_err(|_| GlesContextError::NoConfigFound)?
-        .reduce(|config, acc| {
-            if config.num_samples() > acc.num_samples() {
-                config
-            } else {
-                acc
-            }
+        .collect();
+    let has_matching_visual = |format: gbm::Format| {
+        configs
+            .iter()
+            .any(|config| config.native_visual() == format as u32)
+    };
+    let compatible: Vec<&egl::config::Config> = match format {
+        Some(format) if has_matching_visual(format) => configs
+            .iter()
+            .filter(|config| config.native_visual() == format as u32)
+            .collect(),
+        _ => configs.iter().collect(),
+    };
+    compatible
+        .iter()
+        .filter(|config| gl_config.accepts(**config))
+        .min_by_key(|config| {
+            (
+                config.depth_size(),
+                config.stencil_size(),
+                config.alpha_size(),
+            )
+        })
+        .map(|&config| config.clone())
+        .ok_or_else(|| GlesContextError::NoMatchingConfig {
+            requested: *gl_config,
+            available: compatible
+                .iter()
+                .map(|config| describe_config(config))
+                .collect(),
         })
-        .ok_or(GlesContextError::NoConfigFound)
 }
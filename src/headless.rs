@@ -0,0 +1,32 @@
+use glutin::api::egl;
+
+/// The EGL device virtual monitors render with when there's no DRM card
+///
+/// Contexts are created on the device with `EGL_EXT_platform_device` and render to
+/// pbuffers, so this works on machines without `/dev/dri`, e.g. CI runners that only
+/// have Mesa's llvmpipe.
+pub(crate) struct HeadlessDevice {
+    egl_device: egl::device::Device,
+}
+
+impl HeadlessDevice {
+    /// Picks an EGL device, preferring the software one (llvmpipe) since hardware devices
+    /// may be unusable without access to their render node.
+    pub(crate) fn open() -> Option<Self> {
+        let mut devices: Vec<egl::device::Device> =
+            egl::device::Device::query_devices().ok()?.collect();
+        devices.sort_by_key(|device| !device.extensions().contains("EGL_MESA_device_software"));
+        let egl_device = devices
+            .into_iter()
+            .find(|device| unsafe { egl::display::Display::with_device(device, None) }.is_ok())?;
+        println!(
+            "[INFO] Rendering headless on EGL device {}",
+            egl_device.name().unwrap_or("(unnamed)")
+        );
+        Some(HeadlessDevice { egl_device })
+    }
+
+    pub(crate) fn egl_device(&self) -> &egl::device::Device {
+        &self.egl_device
+    }
+}
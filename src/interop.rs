@@ -0,0 +1,325 @@
+use std::os::fd::{IntoRawFd, OwnedFd};
+
+use thiserror::Error;
+
+use crate::gl;
+use crate::gles_context::{ContextToken, GlesContext, GlesContextError};
+
+/// Errors that can occur while sharing memory or semaphores with Vulkan
+#[derive(Debug, Error)]
+pub enum InteropError {
+    #[error("failed to make the context current: {0}")]
+    GlesContextError(#[from] GlesContextError),
+    #[error("{0} is not supported by the driver")]
+    ExtensionMissing(&'static str),
+    #[error("{0} failed with GL error {1:#x}")]
+    GlError(&'static str, u32),
+}
+
+/// Vulkan image memory exported with `vkGetMemoryFdKHR` as an opaque fd
+///
+/// The image must be a 2D, single mip level, optimal tiling image created with
+/// `VkExternalMemoryImageCreateInfo` (`OPAQUE_FD`), with a format matching
+/// `internal_format` (e.g. `VK_FORMAT_R8G8B8A8_UNORM` for `gl::RGBA8`).
+#[derive(Debug)]
+pub struct VulkanImageDescriptor {
+    /// Memory fd, whose ownership passes to the GL driver.
+    pub fd: OwnedFd,
+    /// Size of the whole memory allocation, `VkMemoryAllocateInfo::allocationSize`.
+    pub allocation_size: u64,
+    /// Offset of the image in the allocation.
+    pub offset: u64,
+    /// Width of the image in pixels.
+    pub width: u32,
+    /// Height of the image in pixels.
+    pub height: u32,
+    /// Sized GL format of the image, e.g. `gl::RGBA8`.
+    pub internal_format: u32,
+    /// True if the memory was allocated with `VkMemoryDedicatedAllocateInfo`.
+    pub dedicated: bool,
+}
+
+/// Layout of an image shared with Vulkan, matching the `VkImageLayout` of the same name
+///
+/// Pass the layout the Vulkan side left the image in when waiting on a semaphore, and
+/// the layout it expects when signaling one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageLayout {
+    General,
+    ColorAttachment,
+    ShaderReadOnly,
+    TransferSrc,
+    TransferDst,
+}
+
+impl ImageLayout {
+    fn gl_layout(self) -> gl::types::GLenum {
+        match self {
+            ImageLayout::General => gl::LAYOUT_GENERAL_EXT,
+            ImageLayout::ColorAttachment => gl::LAYOUT_COLOR_ATTACHMENT_EXT,
+            ImageLayout::ShaderReadOnly => gl::LAYOUT_SHADER_READ_ONLY_EXT,
+            ImageLayout::TransferSrc => gl::LAYOUT_TRANSFER_SRC_EXT,
+            ImageLayout::TransferDst => gl::LAYOUT_TRANSFER_DST_EXT,
+        }
+    }
+}
+
+/// A Vulkan image imported into a monitor's OpenGL ES context
+///
+/// Get one with `Monitor::import_vulkan_image()` and copy it to the screen with
+/// `Monitor::blit_external_image()`, or sample [`texture`](Self::texture) yourself.
+/// Drop it while the monitor's context is current, otherwise its GL objects are leaked.
+pub struct ExternalImage {
+    gl: gl::Gles2,
+    context: ContextToken,
+    memory: gl::types::GLuint,
+    texture: gl::types::GLuint,
+    framebuffer: gl::types::GLuint,
+    size: (u32, u32),
+}
+
+impl ExternalImage {
+    /// Imports the memory of a Vulkan image into `gles_context`, which is made current.
+    pub(crate) fn import(
+        gles_context: &GlesContext,
+        desc: VulkanImageDescriptor,
+    ) -> Result<Self, InteropError> {
+        let gl = gles_context.gl();
+        if !gl.ImportMemoryFdEXT.is_loaded() || !gl.TexStorageMem2DEXT.is_loaded() {
+            return Err(InteropError::ExtensionMissing("GL_EXT_memory_object_fd"));
+        }
+        gles_context.make_current()?;
+
+        let mut image = ExternalImage {
+            gl: gl.clone(),
+            context: gles_context.token(),
+            memory: 0,
+            texture: 0,
+            framebuffer: 0,
+            size: (desc.width, desc.height),
+        };
+        unsafe {
+            gl.CreateMemoryObjectsEXT(1, &mut image.memory);
+            if desc.dedicated {
+                let dedicated = gl::TRUE as i32;
+                gl.MemoryObjectParameterivEXT(
+                    image.memory,
+                    gl::DEDICATED_MEMORY_OBJECT_EXT,
+                    &dedicated,
+                );
+            }
+            // A successful import hands the fd over to the driver
+            let fd = desc.fd.into_raw_fd();
+            gl.ImportMemoryFdEXT(
+                image.memory,
+                desc.allocation_size,
+                gl::HANDLE_TYPE_OPAQUE_FD_EXT,
+                fd,
+            );
+            if let Err(e) = check_error(gl, "glImportMemoryFdEXT") {
+                libc::close(fd);
+                return Err(e);
+            }
+
+            gl.GenTextures(1, &mut image.texture);
+            gl.BindTexture(gl::TEXTURE_2D, image.texture);
+            gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl.TexStorageMem2DEXT(
+                gl::TEXTURE_2D,
+                1,
+                desc.internal_format,
+                desc.width as i32,
+                desc.height as i32,
+                image.memory,
+                desc.offset,
+            );
+            gl.BindTexture(gl::TEXTURE_2D, 0);
+            check_error(gl, "glTexStorageMem2DEXT")?;
+
+            // Read framebuffer used to blit the image
+            let mut previous = 0;
+            gl.GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous);
+            gl.GenFramebuffers(1, &mut image.framebuffer);
+            gl.BindFramebuffer(gl::READ_FRAMEBUFFER, image.framebuffer);
+            gl.FramebufferTexture2D(
+                gl::READ_FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                image.texture,
+                0,
+            );
+            let status = gl.CheckFramebufferStatus(gl::READ_FRAMEBUFFER);
+            gl.BindFramebuffer(gl::READ_FRAMEBUFFER, previous as u32);
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(InteropError::GlError("glCheckFramebufferStatus", status));
+            }
+        }
+        Ok(image)
+    }
+
+    /// Returns the OpenGL texture backed by the image's memory.
+    pub fn texture(&self) -> u32 {
+        self.texture
+    }
+
+    /// Returns the size of the image as (width, height).
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Copies the image to the default framebuffer, flipping it vertically since Vulkan
+    /// images start at the top row and GL framebuffers at the bottom one.
+    pub(crate) fn blit(&self, target_size: (u32, u32)) -> Result<(), InteropError> {
+        let gl = &self.gl;
+        let (width, height) = self.size;
+        let filter = if self.size == target_size {
+            gl::NEAREST
+        } else {
+            gl::LINEAR
+        };
+        unsafe {
+            let (mut read, mut draw) = (0, 0);
+            gl.GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut read);
+            gl.GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut draw);
+            gl.BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer);
+            gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+            gl.BlitFramebuffer(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                0,
+                target_size.1 as i32,
+                target_size.0 as i32,
+                0,
+                gl::COLOR_BUFFER_BIT,
+                filter,
+            );
+            gl.BindFramebuffer(gl::READ_FRAMEBUFFER, read as u32);
+            gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, draw as u32);
+            check_error(gl, "glBlitFramebuffer")
+        }
+    }
+}
+
+impl Drop for ExternalImage {
+    fn drop(&mut self) {
+        if !self.context.is_current() {
+            eprintln!(
+                "Warning: External image {} dropped without its context current, leaking it",
+                self.texture
+            );
+            return;
+        }
+        unsafe {
+            self.gl.DeleteFramebuffers(1, &self.framebuffer);
+            self.gl.DeleteTextures(1, &self.texture);
+            self.gl.DeleteMemoryObjectsEXT(1, &self.memory);
+        }
+    }
+}
+
+/// A Vulkan semaphore imported into a monitor's OpenGL ES context
+///
+/// Export it on the Vulkan side with `vkGetSemaphoreFdKHR` (`OPAQUE_FD`) and import it
+/// with `Monitor::import_vulkan_semaphore()`. Drop it while the monitor's context is
+/// current, otherwise it's leaked.
+///
+/// # Example
+///
+/// ```ignore
+/// // Vulkan signals `rendered` after drawing to `image` and waits on `blitted` before
+/// // drawing to it again
+/// monitor.make_current()?;
+/// rendered.wait(&image, ImageLayout::ColorAttachment);
+/// monitor.blit_external_image(&image)?;
+/// blitted.signal(&image, ImageLayout::ColorAttachment);
+/// ```
+pub struct ExternalSemaphore {
+    gl: gl::Gles2,
+    context: ContextToken,
+    semaphore: gl::types::GLuint,
+}
+
+impl ExternalSemaphore {
+    /// Imports a semaphore fd into `gles_context`, which is made current.
+    pub(crate) fn import(gles_context: &GlesContext, fd: OwnedFd) -> Result<Self, InteropError> {
+        let gl = gles_context.gl();
+        if !gl.ImportSemaphoreFdEXT.is_loaded() {
+            return Err(InteropError::ExtensionMissing("GL_EXT_semaphore_fd"));
+        }
+        gles_context.make_current()?;
+
+        let mut semaphore = ExternalSemaphore {
+            gl: gl.clone(),
+            context: gles_context.token(),
+            semaphore: 0,
+        };
+        unsafe {
+            gl.GenSemaphoresEXT(1, &mut semaphore.semaphore);
+            let fd = fd.into_raw_fd();
+            gl.ImportSemaphoreFdEXT(semaphore.semaphore, gl::HANDLE_TYPE_OPAQUE_FD_EXT, fd);
+            if let Err(e) = check_error(gl, "glImportSemaphoreFdEXT") {
+                libc::close(fd);
+                return Err(e);
+            }
+        }
+        Ok(semaphore)
+    }
+
+    /// Makes the GL commands that follow wait until Vulkan signals the semaphore, with
+    /// `image` in `layout`. The monitor's context must be current.
+    pub fn wait(&self, image: &ExternalImage, layout: ImageLayout) {
+        let layout = layout.gl_layout();
+        unsafe {
+            self.gl.WaitSemaphoreEXT(
+                self.semaphore,
+                0,
+                std::ptr::null(),
+                1,
+                &image.texture,
+                &layout,
+            );
+        }
+    }
+
+    /// Signals the semaphore once the GL commands issued so far are done, leaving
+    /// `image` in `layout` for Vulkan. The monitor's context must be current.
+    pub fn signal(&self, image: &ExternalImage, layout: ImageLayout) {
+        let layout = layout.gl_layout();
+        unsafe {
+            self.gl.SignalSemaphoreEXT(
+                self.semaphore,
+                0,
+                std::ptr::null(),
+                1,
+                &image.texture,
+                &layout,
+            );
+            self.gl.Flush();
+        }
+    }
+}
+
+impl Drop for ExternalSemaphore {
+    fn drop(&mut self) {
+        if !self.context.is_current() {
+            eprintln!(
+                "Warning: External semaphore {} dropped without its context current, leaking it",
+                self.semaphore
+            );
+            return;
+        }
+        unsafe {
+            self.gl.DeleteSemaphoresEXT(1, &self.semaphore);
+        }
+    }
+}
+
+fn check_error(gl: &gl::Gles2, call: &'static str) -> Result<(), InteropError> {
+    match unsafe { gl.GetError() } {
+        gl::NO_ERROR => Ok(()),
+        error => Err(InteropError::GlError(call, error)),
+    }
+}
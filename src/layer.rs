@@ -0,0 +1,570 @@
+use std::collections::HashMap;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::Arc;
+
+use drm::control::{
+    Device as ControlDevice, atomic::AtomicModeReq, crtc, framebuffer, plane, property,
+};
+use thiserror::Error;
+
+use crate::device::GpuDevice;
+use crate::dmabuf::{DmaBufDescriptor, DmaBufError, DmaBufId, import_for_scanout};
+use crate::gl_config::GlConfig;
+use crate::gl_debug::GlDebugOutput;
+use crate::gles_context::{GlesContext, GlesContextError};
+use crate::share_group::ShareGroup;
+
+/// Errors that can occur while allocating or updating an overlay [`Layer`]
+#[derive(Debug, Error)]
+pub enum LayerError {
+    #[error("IO Error: {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("no free overlay plane is compatible with this monitor's CRTC")]
+    NoOverlayAvailable,
+    #[error("failed to create OpenGL ES context: {0}")]
+    GlesContextError(#[from] GlesContextError),
+    #[error("failed to import dma-buf: {0}")]
+    DmaBuf(#[from] DmaBufError),
+    #[error("this layer shows dma-bufs and has no OpenGL ES surface")]
+    NoSurface,
+    #[error("DRM error: {0}")]
+    DrmError(String),
+    #[error("the overlay plane doesn't support changing `{0}`")]
+    PropertyNotSupported(&'static str),
+    #[error("zpos {zpos} is outside of the plane's range {min}..={max}")]
+    ZposOutOfRange { zpos: u64, min: u64, max: u64 },
+}
+
+/// A rendered or imported buffer and its framebuffer
+struct LayerBuffer {
+    bo: gbm::BufferObject<()>,
+    fb: framebuffer::Handle,
+    size: (u32, u32),
+    /// Set for imported dma-bufs, to tell the producer when it's released
+    dmabuf: Option<DmaBufId>,
+}
+
+/// An overlay plane of a monitor, showing frames from its own OpenGL ES context or
+/// dma-bufs imported from a video decoder or another GPU.
+///
+/// Layers are scanned out on top of (or below, see [`set_zpos`](Self::set_zpos)) the
+/// primary plane and blended by the display controller, so content that updates at its
+/// own pace, like a video, doesn't force the rest of the screen to be redrawn.
+///
+/// Get one with `Monitor::request_overlay()`, whose surface is ARGB8888 with
+/// premultiplied alpha, or `Monitor::request_dmabuf_overlay()` for a layer that only
+/// shows dma-bufs (see [`present_dmabuf`](Self::present_dmabuf)). Changes are committed
+/// atomically with the monitor's next
+/// `EasyDRM::swap_buffers()`, which also commits a layer that was redrawn or moved while
+/// the primary plane wasn't.
+///
+/// Dropping the layer (with `Monitor::release_overlay()`) turns the plane off and makes
+/// it available again.
+///
+/// # Example
+///
+/// ```ignore
+/// let plane = monitor.request_overlay(1280, 720)?.plane();
+///
+/// // In the render loop
+/// if monitor.can_render() {
+///     let video = monitor.layer_mut(plane).unwrap();
+///     video.make_current()?;
+///     draw_video_frame(video.gl());
+/// }
+/// ```
+pub struct Layer {
+    device: Arc<GpuDevice>,
+    plane: plane::Handle,
+    properties: HashMap<String, property::Info>,
+    gles_context: Option<GlesContext>,
+    size: (u16, u16),
+    position: (i32, i32),
+    display_size: Option<(u32, u32)>,
+    zpos: Option<u64>,
+    alpha: Option<u16>,
+    visible: bool,
+    was_drawn: bool,
+    dirty: bool,
+    /// Set while the layer's changes are in an atomic request whose result isn't known
+    requested: bool,
+    /// Presented frame or imported dma-buf waiting for a successful commit
+    pending: Option<LayerBuffer>,
+    /// Buffer handed to the display controller with the last commit
+    queued: Option<LayerBuffer>,
+    /// Buffer shown before `queued`, free once the last commit completed
+    on_screen: Option<LayerBuffer>,
+    next_dmabuf_id: u64,
+    released_dmabufs: Vec<DmaBufId>,
+    /// Signals when the frame in `pending` (if it was drawn) finished rendering
+    previous_fence: Option<OwnedFd>,
+    previous_sync: Option<*mut std::ffi::c_void>,
+}
+
+impl Layer {
+    /// Creates the layer's surface and claims `plane`, which must be unclaimed.
+    ///
+    /// The context is created like the monitor's, from `gl_config` with an alpha channel
+    /// and in its share group.
+    pub(crate) fn new(
+        device: Arc<GpuDevice>,
+        plane: plane::Handle,
+        (width, height): (u16, u16),
+        gl_config: &GlConfig,
+        share_group: Option<&ShareGroup>,
+        debug_output: Option<GlDebugOutput>,
+    ) -> Result<Self, LayerError> {
+        let mut gles_context = GlesContext::new(
+            device.gbm()?,
+            width,
+            height,
+            &gl_config.alpha(true),
+            share_group,
+        )?;
+        if let Some(output) = debug_output {
+            gles_context.enable_debug_output(output)?;
+        }
+        Self::with_context(device, plane, Some(gles_context), (width, height))
+    }
+
+    /// Creates a layer without an OpenGL surface, for dma-bufs, and claims `plane`.
+    pub(crate) fn for_dmabuf(
+        device: Arc<GpuDevice>,
+        plane: plane::Handle,
+    ) -> Result<Self, LayerError> {
+        Self::with_context(device, plane, None, (0, 0))
+    }
+
+    fn with_context(
+        device: Arc<GpuDevice>,
+        plane: plane::Handle,
+        gles_context: Option<GlesContext>,
+        size: (u16, u16),
+    ) -> Result<Self, LayerError> {
+        let properties = device
+            .card
+            .get_properties(plane)?
+            .as_hashmap(&device.card)?;
+        device.claim_plane(plane);
+
+        Ok(Layer {
+            device,
+            plane,
+            properties,
+            gles_context,
+            size,
+            position: (0, 0),
+            display_size: None,
+            zpos: None,
+            alpha: None,
+            visible: true,
+            was_drawn: false,
+            dirty: false,
+            requested: false,
+            pending: None,
+            queued: None,
+            on_screen: None,
+            next_dmabuf_id: 0,
+            released_dmabufs: Vec::new(),
+            previous_fence: None,
+            previous_sync: None,
+        })
+    }
+
+    /// Returns the overlay plane backing this layer.
+    pub fn plane(&self) -> plane::Handle {
+        self.plane
+    }
+
+    /// Returns the size of the layer's content as (width, height): its OpenGL surface,
+    /// or the last dma-buf presented on it.
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    /// Returns true if the layer has an OpenGL surface to render to.
+    pub fn has_surface(&self) -> bool {
+        self.gles_context.is_some()
+    }
+
+    /// Makes this layer's OpenGL context current and marks the layer as drawn.
+    ///
+    /// Like `Monitor::make_current()`, only render while the monitor can render. Call
+    /// `Monitor::make_current()` again before drawing to the primary plane. The drawn
+    /// frame replaces a dma-buf presented since the last commit.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LayerError::NoSurface`] for layers created with
+    /// `Monitor::request_dmabuf_overlay()`.
+    pub fn make_current(&mut self) -> Result<(), LayerError> {
+        self.gles_context
+            .as_ref()
+            .ok_or(LayerError::NoSurface)?
+            .make_current()?;
+        if let Some(pending) = self.pending.take() {
+            self.release_buffer(pending);
+        }
+        self.was_drawn = true;
+        Ok(())
+    }
+
+    /// Gets a reference to the OpenGL ES bindings of this layer's context.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the layer has no OpenGL surface, see [`has_surface`](Self::has_surface).
+    pub fn gl(&self) -> &crate::gl::Gles2 {
+        self.gles_context
+            .as_ref()
+            .expect("dma-buf layers have no OpenGL ES context")
+            .gl()
+    }
+
+    /// Gets a function pointer for loading OpenGL functions in this layer's context.
+    ///
+    /// Returns a null pointer if the layer has no OpenGL surface.
+    pub fn get_proc_address(&self, symbol: &str) -> *const std::ffi::c_void {
+        self.gles_context
+            .as_ref()
+            .map_or(std::ptr::null(), |context| context.get_proc_address(symbol))
+    }
+
+    /// Shows a dma-buf on this layer with the next commit, without copying it.
+    ///
+    /// The buffer is imported with `gbm_bo_import` and scanned out directly, so its format
+    /// and modifier must be supported by the plane (NV12 for video on most display
+    /// controllers). It must stay untouched until EasyDRM releases it: that happens once
+    /// the flip to a newer buffer completed, or right away if it's replaced before being
+    /// committed. Collect the released buffers with
+    /// [`take_released_dmabufs`](Self::take_released_dmabufs) to hand them back to the
+    /// producer.
+    ///
+    /// The file descriptors are only borrowed during the call.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let id = video.present_dmabuf(&frame.descriptor())?;
+    /// in_flight.insert(id, frame);
+    ///
+    /// // After `poll_events()`
+    /// for id in video.take_released_dmabufs() {
+    ///     decoder.recycle(in_flight.remove(&id).unwrap());
+    /// }
+    /// ```
+    pub fn present_dmabuf(&mut self, desc: &DmaBufDescriptor) -> Result<DmaBufId, LayerError> {
+        let size = (
+            u16::try_from(desc.width)
+                .map_err(|_| DmaBufError::InvalidDescriptor("image is too large"))?,
+            u16::try_from(desc.height)
+                .map_err(|_| DmaBufError::InvalidDescriptor("image is too large"))?,
+        );
+        let imported = import_for_scanout(&self.device.card, self.device.gbm()?, desc)?;
+
+        let id = DmaBufId(self.next_dmabuf_id);
+        self.next_dmabuf_id += 1;
+        let buffer = LayerBuffer {
+            bo: imported.bo,
+            fb: imported.fb,
+            size: (desc.width, desc.height),
+            dmabuf: Some(id),
+        };
+        if let Some(replaced) = self.pending.replace(buffer) {
+            self.release_buffer(replaced);
+        }
+        self.size = size;
+        self.was_drawn = false;
+        Ok(id)
+    }
+
+    /// Returns the dma-bufs EasyDRM stopped using since the last call, in the order they
+    /// were released. Their producer may reuse them.
+    ///
+    /// Buffers still in use when the layer is dropped are released without being reported.
+    pub fn take_released_dmabufs(&mut self) -> Vec<DmaBufId> {
+        std::mem::take(&mut self.released_dmabufs)
+    }
+
+    /// Returns the position of the layer's top-left corner, in pixels of the mode.
+    pub fn position(&self) -> (i32, i32) {
+        self.position
+    }
+
+    /// Moves the layer's top-left corner to (x, y), in pixels of the mode.
+    ///
+    /// The layer may extend past the edges of the screen if the driver supports it.
+    pub fn set_position(&mut self, x: i32, y: i32) {
+        if self.position != (x, y) {
+            self.position = (x, y);
+            self.dirty = true;
+        }
+    }
+
+    /// Returns the size the layer is shown at on screen, by default the size of its
+    /// content.
+    pub fn display_size(&self) -> (u32, u32) {
+        self.display_size
+            .unwrap_or((self.size.0 as u32, self.size.1 as u32))
+    }
+
+    /// Shows the layer at `width`x`height`, scaling the surface if it has a different
+    /// size. Not every display controller can scale overlay planes.
+    pub fn set_display_size(&mut self, width: u32, height: u32) {
+        if self.display_size != Some((width, height)) {
+            self.display_size = Some((width, height));
+            self.dirty = true;
+        }
+    }
+
+    /// Returns the stacking position set with [`set_zpos`](Self::set_zpos), or `None`
+    /// if the driver's default is used.
+    pub fn zpos(&self) -> Option<u64> {
+        self.zpos
+    }
+
+    /// Returns the range of stacking positions the plane accepts, if it can be changed.
+    pub fn zpos_range(&self) -> Option<(u64, u64)> {
+        let prop = self.properties.get("zpos").filter(|prop| prop.mutable())?;
+        match prop.value_type() {
+            property::ValueType::UnsignedRange(min, max) => Some((min, max)),
+            _ => None,
+        }
+    }
+
+    /// Sets the stacking position of the layer. Planes with a higher zpos are shown on
+    /// top; a zpos below the primary plane's puts the layer under it, visible through
+    /// the transparent parts of the primary plane.
+    pub fn set_zpos(&mut self, zpos: u64) -> Result<(), LayerError> {
+        let (min, max) = self
+            .zpos_range()
+            .ok_or(LayerError::PropertyNotSupported("zpos"))?;
+        if !(min..=max).contains(&zpos) {
+            return Err(LayerError::ZposOutOfRange { zpos, min, max });
+        }
+        if self.zpos != Some(zpos) {
+            self.zpos = Some(zpos);
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    /// Returns the opacity of the whole layer, from 0.0 to 1.0.
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+            .map_or(1.0, |alpha| alpha as f32 / u16::MAX as f32)
+    }
+
+    /// Sets the opacity of the whole layer, from 0.0 (transparent) to 1.0 (opaque), on
+    /// top of the per-pixel alpha of the surface.
+    pub fn set_alpha(&mut self, alpha: f32) -> Result<(), LayerError> {
+        if !self.properties.contains_key("alpha") {
+            return Err(LayerError::PropertyNotSupported("alpha"));
+        }
+        let alpha = (alpha.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16;
+        if self.alpha != Some(alpha) {
+            self.alpha = Some(alpha);
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    /// Returns true if the layer is shown.
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Shows the layer. Layers are visible once they were drawn at least once.
+    pub fn show(&mut self) {
+        if !self.visible {
+            self.visible = true;
+            self.dirty = true;
+        }
+    }
+
+    /// Turns the plane off without giving it up.
+    pub fn hide(&mut self) {
+        if self.visible {
+            self.visible = false;
+            self.dirty = true;
+        }
+    }
+
+    /// Returns true if the layer was drawn or changed since it was last committed.
+    pub(crate) fn needs_commit(&self) -> bool {
+        self.was_drawn || self.dirty || self.pending.is_some()
+    }
+
+    /// Releases the buffer replaced by the last commit, now that it's off screen.
+    pub(crate) fn page_flip_complete(&mut self) {
+        if let Some(old) = self.on_screen.take() {
+            self.release_buffer(old);
+        }
+    }
+
+    /// Adds the layer's plane state to an atomic request, presenting a newly drawn frame.
+    /// Nothing changes on screen until [`Layer::commit_succeeded`] is called.
+    pub(crate) fn add_to_request(
+        &mut self,
+        atomic_req: &mut AtomicModeReq,
+        crtc: crtc::Handle,
+    ) -> Result<(), LayerError> {
+        if self.was_drawn {
+            let buffer = self.present()?;
+            if let Some(replaced) = self.pending.replace(buffer) {
+                self.release_buffer(replaced);
+            }
+        }
+        self.requested = true;
+        // A frame drawn with GL has to wait for rendering, an imported dma-buf doesn't
+        let fence_fd = match &self.pending {
+            Some(buffer) if buffer.dmabuf.is_none() => {
+                self.previous_fence.as_ref().map(AsRawFd::as_raw_fd)
+            }
+            _ => None,
+        };
+        let display_size = self.display_size();
+
+        let mut set = |name: &str, value: property::Value| {
+            if let Some(prop) = self.properties.get(name) {
+                atomic_req.add_property(self.plane, prop.handle(), value);
+            }
+        };
+
+        let shown = self.pending.as_ref().or(self.queued.as_ref());
+        match shown.map(|buffer| (buffer.fb, buffer.size)) {
+            Some((fb, (width, height))) if self.visible => {
+                let (display_width, display_height) = display_size;
+                set("CRTC_ID", property::Value::CRTC(Some(crtc)));
+                set("FB_ID", property::Value::Framebuffer(Some(fb)));
+                set("SRC_X", property::Value::UnsignedRange(0));
+                set("SRC_Y", property::Value::UnsignedRange(0));
+                set(
+                    "SRC_W",
+                    property::Value::UnsignedRange((width as u64) << 16),
+                );
+                set(
+                    "SRC_H",
+                    property::Value::UnsignedRange((height as u64) << 16),
+                );
+                set(
+                    "CRTC_X",
+                    property::Value::SignedRange(self.position.0 as i64),
+                );
+                set(
+                    "CRTC_Y",
+                    property::Value::SignedRange(self.position.1 as i64),
+                );
+                set(
+                    "CRTC_W",
+                    property::Value::UnsignedRange(display_width as u64),
+                );
+                set(
+                    "CRTC_H",
+                    property::Value::UnsignedRange(display_height as u64),
+                );
+                if let Some(zpos) = self.zpos {
+                    set("zpos", property::Value::UnsignedRange(zpos));
+                }
+                if let Some(alpha) = self.alpha {
+                    set("alpha", property::Value::UnsignedRange(alpha as u64));
+                }
+                if let Some(fence_fd) = fence_fd {
+                    set("IN_FENCE_FD", property::Value::SignedRange(fence_fd as i64));
+                }
+            }
+            _ => {
+                set("CRTC_ID", property::Value::CRTC(None));
+                set("FB_ID", property::Value::Framebuffer(None));
+            }
+        }
+        Ok(())
+    }
+
+    /// Called once the atomic request the layer was added to has been committed, so the
+    /// new buffer is queued and the one it replaces gets released after the page flip.
+    pub(crate) fn commit_succeeded(&mut self) {
+        if !std::mem::take(&mut self.requested) {
+            return;
+        }
+        if let Some(buffer) = self.pending.take() {
+            // Released on the page flip that takes the queued buffer off screen
+            if let Some(old) = self.on_screen.take() {
+                self.release_buffer(old);
+            }
+            self.on_screen = self.queued.replace(buffer);
+        }
+        self.dirty = false;
+    }
+
+    /// Called when the atomic request the layer was added to was rejected, so the new
+    /// buffer and changed properties go into the next commit instead.
+    pub(crate) fn commit_failed(&mut self) {
+        self.requested = false;
+    }
+
+    /// Swaps the layer's buffers and returns the new frame, keeping a fence that signals
+    /// when rendering is done in `previous_fence`.
+    fn present(&mut self) -> Result<LayerBuffer, LayerError> {
+        let gles_context = self.gles_context.as_mut().ok_or(LayerError::NoSurface)?;
+        gles_context.make_current()?;
+        let bo = gles_context
+            .swap_buffers()
+            .map_err(|e| LayerError::DrmError(format!("Failed to swap buffers: {}", e)))?;
+
+        // Cleanup previous fence and sync object
+        self.previous_fence = None;
+        if let Some(old_sync) = self.previous_sync.take() {
+            gles_context.destroy_sync(old_sync);
+        }
+
+        let (fence_fd, sync) = gles_context
+            .create_native_fence()
+            .map_err(|e| LayerError::DrmError(format!("Failed to create fence: {}", e)))?;
+        self.previous_fence = Some(unsafe { OwnedFd::from_raw_fd(fence_fd) });
+        self.previous_sync = Some(sync);
+
+        let fb = self
+            .device
+            .card
+            .add_framebuffer(&bo, 32, 32)
+            .map_err(|e| LayerError::DrmError(format!("Failed to add framebuffer: {}", e)))?;
+
+        let size = (bo.width(), bo.height());
+        self.was_drawn = false;
+        Ok(LayerBuffer {
+            bo,
+            fb,
+            size,
+            dmabuf: None,
+        })
+    }
+
+    fn release_buffer(&mut self, buffer: LayerBuffer) {
+        if let Err(e) = self.device.card.destroy_framebuffer(buffer.fb) {
+            eprintln!("Warning: Failed to destroy overlay framebuffer: {}", e);
+        }
+        drop(buffer.bo);
+        self.released_dmabufs.extend(buffer.dmabuf);
+    }
+}
+
+impl Drop for Layer {
+    fn drop(&mut self) {
+        // Removing the framebuffers turns the plane off
+        for buffer in [
+            self.pending.take(),
+            self.queued.take(),
+            self.on_screen.take(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            self.release_buffer(buffer);
+        }
+        if let (Some(sync), Some(gles_context)) = (self.previous_sync.take(), &self.gles_context) {
+            gles_context.destroy_sync(sync);
+        }
+        self.device.release_plane(self.plane);
+    }
+}
@@ -12,12 +12,35 @@
 //! - **Per-monitor user context** - attach custom data (Skia, Cairo, etc.)
 //! - **Refresh-rate grouping metadata** for diagnostics or custom scheduling strategies
 //! - **Atomic commits** with proper fence synchronization
+//! - **Cursor API** on the cursor plane, an overlay plane or composited with GL
+//! - **Overlay layers** with their own GL surface, position, zpos and alpha
+//! - **Zero-copy dma-buf scanout** of decoder or GPU frames on overlay layers, with release notification
+//! - **Virtual monitors** rendered offscreen with a simulated vblank
+//! - **Headless backend** on an EGL device (e.g. llvmpipe) for machines without a DRM card
+//! - **Configurable EGL configs**: MSAA, depth/stencil, alpha, sRGB, GLES version, debug and robust contexts
+//! - **Shared GL contexts** across monitors, plus resource contexts for loader threads
+//! - **GL debug output** forwarded to your logger, tagged with the monitor's connector
+//! - **Render threads** per monitor, handing frames back for the combined atomic commit
+//! - **Pluggable render backends**: OpenGL ES on GBM by default, dumb buffers or your own
+//! - **CPU rendering** to double-buffered dumb buffers, without EGL or GBM
+//! - **Vulkan rendering** (`vulkan` feature) to GBM buffers imported as `VkImage`s
+//! - **Writeback capture** of the composed output, usable for pixel tests on vkms
+//! - **Screenshots** of the presented frame, savable as PNG or PPM
+//! - **Frame export** as dma-bufs for screen recorders and remote viewers
+//! - **Vulkan interop**: blit Vulkan images to the screen, synchronized with shared semaphores
+//! - **dma-buf textures** through `EGLImage`, including multi-plane YUV as `GL_TEXTURE_EXTERNAL_OES`
 //! - **3-state mode management** for efficient mode setting
+//! - **Custom modes** from CVT/GTF timings or X11 modelines
+//! - **Declarative display configuration** (TOML) with per-output rules
+//! - **Kernel `video=` overrides** honored when picking default modes
 //!
 //! ## Basic Usage
 //!
-//! ```no_run
-//! use easydrm::EasyDRM;
+//! Drawing with OpenGL ES needs the `gbm` feature (on by default).
+//!
+#![cfg_attr(feature = "gbm", doc = "```no_run")]
+#![cfg_attr(not(feature = "gbm"), doc = "```ignore")]
+//! use easydrm::{EasyDRM, gl};
 //!
 //! // Initialize without custom context
 //! let mut easydrm = EasyDRM::init_empty().unwrap();
@@ -27,7 +50,7 @@
 //!     easydrm.poll_events().unwrap();
 //!
 //!     // Render to each monitor that's ready
-//!     for monitor in easydrm.monitors() {
+//!     for monitor in easydrm.monitors_mut() {
 //!         if monitor.can_render() {
 //!             monitor.make_current().unwrap();
 //!             let gl = monitor.gl();
@@ -56,13 +79,13 @@
 //!         "Monitor init: {}x{}, get symbol ptr example: {:?}",
 //!         req.width,
 //!         req.height,
-//!         (req.get_proc_address)("glCreateShader")
+//!         req.get_proc_address.map(|load| load("glCreateShader"))
 //!     );
 //!     MyContext { frame_count: 0 }
 //! })
 //! .unwrap();
 //!
-//! for monitor in easydrm.monitors() {
+//! for monitor in easydrm.monitors_mut() {
 //!     let ctx = monitor.context_mut();
 //!     ctx.frame_count += 1;
 //! }
@@ -71,26 +94,136 @@
 use std::collections::{HashMap, HashSet};
 use std::os::fd::{AsFd, BorrowedFd, RawFd};
 use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::sync::Arc;
+#[cfg(feature = "gbm")]
+use std::time::{Duration, Instant};
 
-use drm::Device;
+use drm::buffer::DrmFourcc;
 use drm::control::atomic::AtomicModeReq;
 use drm::control::{
     AtomicCommitFlags, Device as ControlDevice, Event, PlaneType, connector, crtc, plane,
 };
-use gbm::Device as GbmDevice;
 use nix::poll::{PollFd, PollFlags, PollTimeout, poll};
 use thiserror::Error;
 
-use crate::card::Card;
+use crate::cmdline::DEFAULT_KERNEL_CMDLINE_PATH;
+use crate::device::GpuDevice;
+use crate::dumb_buffer::DumbBuffers;
+#[cfg(feature = "gbm")]
+use crate::gl_debug::GlDebugOutput;
+#[cfg(feature = "gbm")]
+use crate::gles_context::GlesContext;
+#[cfg(feature = "gbm")]
+use crate::headless::HeadlessDevice;
+#[cfg(feature = "gbm")]
+use crate::monitor::enable_gl_debug_output;
 use crate::monitor::{MonitorResourceAllocation, MonitorSetupError};
-
+#[cfg(feature = "gbm")]
+use crate::render_backend::GbmEglBackend;
+#[cfg(feature = "gbm")]
+use crate::render_thread::FrameWaker;
+use crate::share_group::ShareGroup;
+#[cfg(feature = "gbm")]
+use crate::writeback::WritebackJob;
+
+/// Connector handles of virtual monitors start here, far above any real DRM object id
+const VIRTUAL_CONNECTOR_BASE: u32 = 0xF000_0000;
+
+mod capture;
 mod card;
+mod cmdline;
+mod config;
+mod cursor;
+mod device;
+#[cfg(feature = "gbm")]
+mod dmabuf;
+mod dumb_buffer;
+mod edid;
+#[cfg(feature = "gbm")]
+mod export;
+mod formats;
+// Only OpenGL ES monitors use these beyond the option builders
+#[cfg_attr(not(feature = "gbm"), allow(dead_code))]
+mod gl_config;
+#[cfg_attr(not(feature = "gbm"), allow(dead_code))]
+mod gl_debug;
+#[cfg(feature = "gbm")]
 mod gles_context;
+#[cfg(feature = "gbm")]
+mod headless;
 mod hotplug;
+#[cfg(feature = "gbm")]
+mod interop;
+#[cfg(feature = "gbm")]
+mod layer;
+mod modegen;
 mod monitor;
+mod monitor_id;
+mod output;
+mod render_backend;
+#[cfg(feature = "gbm")]
+mod render_thread;
+#[cfg(feature = "gbm")]
+mod share_group;
+#[cfg(not(feature = "gbm"))]
+mod share_group {
+    /// Without the `gbm` feature there are no OpenGL ES contexts to share
+    pub(crate) enum ShareGroup {}
+}
+mod software_cursor;
+#[cfg(feature = "gbm")]
+mod texture;
+mod virtual_output;
+#[cfg(feature = "vulkan")]
+mod vulkan;
+mod writeback;
 
 // Public API exports
+pub use capture::{CaptureError, CapturedFrame};
+pub use cmdline::{ForcedState, KernelModeHints, VideoModeHint};
+pub use config::{
+    ColorSettings, ConfigError, ConfigIssue, DisplayConfig, Gamma, ModeSpec, OutputMatch,
+    OutputRule, Rotation,
+};
+pub use cursor::{CursorBackend, CursorError};
+#[cfg(feature = "gbm")]
+pub use dmabuf::{DmaBufDescriptor, DmaBufError, DmaBufId, DmaBufPlane};
+pub use dumb_buffer::{DumbBufferError, Pixels};
+pub use edid::{
+    ColorFormats, EdidError, HdrStaticMetadata, ManufactureDate, MonitorInfo, VrrRange,
+};
+#[cfg(feature = "gbm")]
+pub use export::{ExportedFrame, MAX_EXPORTED_FRAMES};
+pub use formats::{PlaneFormats, ScanoutFormat};
+pub use gl_config::GlConfig;
+pub use gl_debug::{GlDebugLogger, GlDebugMessage, GlDebugSeverity, GlDebugSource, GlDebugType};
+#[cfg(feature = "gbm")]
+pub use gles_context::GlesContextError;
+#[cfg(feature = "gbm")]
+pub use interop::{
+    ExternalImage, ExternalSemaphore, ImageLayout, InteropError, VulkanImageDescriptor,
+};
+#[cfg(feature = "gbm")]
+pub use layer::{Layer, LayerError};
+pub use modegen::{ModeBuilder, ModeGenError, ModeTiming, parse_modeline};
 pub use monitor::Monitor;
+pub use monitor_id::{EdidIdentity, MonitorId, ParseMonitorIdError};
+pub use output::Subconnector;
+pub use render_backend::{
+    BackendFrame, RenderBackend, RenderBackendError, RenderBackendFactory, RenderBackendKind,
+    RenderBackendRequest,
+};
+#[cfg(feature = "gbm")]
+pub use render_thread::{CurrentRenderThread, RenderThread, RenderThreadError};
+#[cfg(feature = "gbm")]
+pub use share_group::ResourceContext;
+#[cfg(feature = "gbm")]
+pub use texture::{Texture, TextureError};
+#[cfg(feature = "vulkan")]
+pub use vulkan::{VulkanBackend, VulkanDevice};
+#[cfg(feature = "gbm")]
+pub use writeback::WritebackError;
 
 /// OpenGL ES bindings generated at build time
 #[allow(clippy::all, warnings)]
@@ -98,16 +231,300 @@ pub mod gl {
     include!(concat!(env!("OUT_DIR"), "/gl_bindings.rs"));
 }
 
+/// EGL bindings generated at build time, for the extensions glutin doesn't expose
+#[allow(clippy::all, warnings, non_camel_case_types)]
+pub(crate) mod egl {
+    pub type khronos_utime_nanoseconds_t = khronos_uint64_t;
+    pub type khronos_uint64_t = u64;
+    pub type khronos_ssize_t = std::ffi::c_long;
+    pub type EGLint = i32;
+    pub type EGLNativeDisplayType = *const std::ffi::c_void;
+    pub type EGLNativePixmapType = *const std::ffi::c_void;
+    pub type EGLNativeWindowType = *const std::ffi::c_void;
+    pub type NativeDisplayType = EGLNativeDisplayType;
+    pub type NativePixmapType = EGLNativePixmapType;
+    pub type NativeWindowType = EGLNativeWindowType;
+
+    include!(concat!(env!("OUT_DIR"), "/egl_bindings.rs"));
+}
+
 /// Data provided to the user callback when creating a per-monitor context.
 pub struct MonitorContextCreationRequest<'a> {
-    /// OpenGL ES bindings for this monitor.
-    pub gl: &'a crate::gl::Gles2,
+    /// OpenGL ES bindings for this monitor, `None` unless it renders with the default
+    /// OpenGL ES backend.
+    pub gl: Option<&'a crate::gl::Gles2>,
     /// Width of the monitor surface in pixels.
     pub width: usize,
     /// Height of the monitor surface in pixels.
     pub height: usize,
-    /// Helper to load extra GL symbols (useful for third-party loaders).
-    pub get_proc_address: &'a dyn Fn(&str) -> *const std::ffi::c_void,
+    /// Helper to load extra GL symbols (useful for third-party loaders), `None` when `gl`
+    /// is.
+    pub get_proc_address: Option<&'a dyn Fn(&str) -> *const std::ffi::c_void>,
+    /// The backend producing the monitor's frames. Downcast a custom one through
+    /// [`Any`](std::any::Any) to set up its resources.
+    pub render_backend: &'a dyn RenderBackend,
+}
+
+impl MonitorContextCreationRequest<'_> {
+    /// Returns the Vulkan backend of the monitor, whose device and images the context
+    /// renders with. `None` unless monitors render with [`RenderBackendKind::Vulkan`].
+    #[cfg(feature = "vulkan")]
+    pub fn vulkan(&self) -> Option<&VulkanBackend> {
+        (self.render_backend as &dyn std::any::Any).downcast_ref()
+    }
+}
+
+/// Options controlling how EasyDRM sets up monitors.
+///
+/// Pass to [`EasyDRM::init_with_options`]; [`EasyDRM::init`] uses the defaults.
+#[derive(Clone)]
+pub struct EasyDRMOptions {
+    pub(crate) display_config: Option<DisplayConfig>,
+    pub(crate) kernel_cmdline_path: Option<PathBuf>,
+    pub(crate) headless: bool,
+    pub(crate) render_backend: RenderBackendKind,
+    pub(crate) gl_config: GlConfig,
+    pub(crate) monitor_gl_configs: Vec<(OutputMatch, GlConfig)>,
+    pub(crate) shared_gl_contexts: bool,
+    pub(crate) gl_debug_logger: Option<GlDebugLogger>,
+    pub(crate) virtual_monitors: Vec<(u16, u16, f64)>,
+}
+
+impl std::fmt::Debug for EasyDRMOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EasyDRMOptions")
+            .field("display_config", &self.display_config)
+            .field("kernel_cmdline_path", &self.kernel_cmdline_path)
+            .field("headless", &self.headless)
+            .field("render_backend", &self.render_backend)
+            .field("gl_config", &self.gl_config)
+            .field("monitor_gl_configs", &self.monitor_gl_configs)
+            .field("shared_gl_contexts", &self.shared_gl_contexts)
+            .field("gl_debug_output", &self.gl_debug_logger.is_some())
+            .field("virtual_monitors", &self.virtual_monitors)
+            .finish()
+    }
+}
+
+impl Default for EasyDRMOptions {
+    fn default() -> Self {
+        EasyDRMOptions {
+            display_config: None,
+            kernel_cmdline_path: Some(PathBuf::from(DEFAULT_KERNEL_CMDLINE_PATH)),
+            headless: false,
+            render_backend: RenderBackendKind::default(),
+            gl_config: GlConfig::default(),
+            monitor_gl_configs: Vec::new(),
+            shared_gl_contexts: false,
+            gl_debug_logger: None,
+            virtual_monitors: Vec::new(),
+        }
+    }
+}
+
+impl EasyDRMOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies the rules of a [`DisplayConfig`] to monitors as they are discovered,
+    /// at initialization and on hotplug.
+    pub fn display_config(mut self, config: DisplayConfig) -> Self {
+        self.display_config = Some(config);
+        self
+    }
+
+    /// Sets the EGL config and context attributes of every monitor's OpenGL ES context,
+    /// see [`GlConfig`]. Defaults to a single sampled config without depth or stencil.
+    pub fn gl_config(mut self, config: GlConfig) -> Self {
+        self.gl_config = config;
+        self
+    }
+
+    /// Uses `config` instead of the one from [`gl_config`](Self::gl_config) for monitors
+    /// matching `matcher`. The first matching override wins.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = EasyDRMOptions::new().monitor_gl_config(
+    ///     OutputMatch { connector: Some("HDMI-A-1".into()), ..Default::default() },
+    ///     GlConfig::new().samples(4),
+    /// );
+    /// ```
+    pub fn monitor_gl_config(mut self, matcher: OutputMatch, config: GlConfig) -> Self {
+        self.monitor_gl_configs.push((matcher, config));
+        self
+    }
+
+    /// Creates every monitor's OpenGL ES context in one EGL share group, so textures,
+    /// buffers and shaders uploaded once are usable on every screen, including monitors
+    /// plugged in later. `EasyDRM::create_resource_context()` then gives contexts in the
+    /// same group for loading assets on other threads.
+    ///
+    /// Container objects (framebuffers, vertex arrays) are never shared. Overrides from
+    /// [`monitor_gl_config`](Self::monitor_gl_config) must keep the GLES version and
+    /// robustness of [`gl_config`](Self::gl_config), EGL refuses to share otherwise.
+    pub fn shared_gl_contexts(mut self) -> Self {
+        self.shared_gl_contexts = true;
+        self
+    }
+
+    /// Creates debug contexts and forwards their `KHR_debug` messages (GL errors, shader
+    /// compiler output, performance warnings...) to `logger`, tagged with the monitor's
+    /// connector. Messages are delivered synchronously, on the thread making the faulty
+    /// call, so a backtrace taken in the logger points at it.
+    ///
+    /// Debug contexts can be much slower, enable this to diagnose issues. Drivers
+    /// without `GL_KHR_debug` only print a warning. Resource contexts don't log.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = EasyDRMOptions::new().gl_debug_output(|message| {
+    ///     if message.severity >= GlDebugSeverity::Medium {
+    ///         log::error!("{}", message);
+    ///     }
+    /// });
+    /// ```
+    pub fn gl_debug_output(
+        mut self,
+        logger: impl Fn(&GlDebugMessage<'_>) + Send + Sync + 'static,
+    ) -> Self {
+        self.gl_debug_logger = Some(Arc::new(logger));
+        self
+    }
+
+    /// Returns the GL config of monitors without an override, and of the share group.
+    #[cfg(feature = "gbm")]
+    pub(crate) fn default_gl_config(&self) -> GlConfig {
+        self.with_debug(self.gl_config)
+    }
+
+    /// Returns the GL config of the monitor `id`.
+    #[cfg(feature = "gbm")]
+    pub(crate) fn gl_config_for(&self, id: &MonitorId, info: Option<&MonitorInfo>) -> GlConfig {
+        self.monitor_gl_configs
+            .iter()
+            .find(|(matcher, _)| matcher.matches(id, info))
+            .map_or(self.default_gl_config(), |&(_, config)| {
+                self.with_debug(config)
+            })
+    }
+
+    /// Forces debug contexts when a debug logger is set.
+    #[cfg(feature = "gbm")]
+    fn with_debug(&self, config: GlConfig) -> GlConfig {
+        match self.gl_debug_logger {
+            Some(_) => config.debug(true),
+            None => config,
+        }
+    }
+
+    /// Returns the debug output of the monitor on `connector`, if a logger is set.
+    #[cfg(feature = "gbm")]
+    pub(crate) fn gl_debug_output_for(
+        &self,
+        connector: connector::Handle,
+        connector_name: &str,
+    ) -> Option<GlDebugOutput> {
+        let logger = self.gl_debug_logger.as_ref()?;
+        Some(GlDebugOutput::new(connector, connector_name, logger))
+    }
+
+    /// Reads `video=` mode overrides from this file instead of `/proc/cmdline`.
+    ///
+    /// See [`KernelModeHints`] for the supported syntax.
+    pub fn kernel_cmdline_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.kernel_cmdline_path = Some(path.into());
+        self
+    }
+
+    /// Ignores `video=` mode overrides on the kernel command line.
+    pub fn ignore_kernel_cmdline(mut self) -> Self {
+        self.kernel_cmdline_path = None;
+        self
+    }
+
+    /// Renders headless even if a DRM card is available, without touching real outputs.
+    ///
+    /// EasyDRM goes headless on its own when the system has no DRM card; a card that
+    /// exists but can't be opened (e.g. for lack of permissions) fails `init` instead.
+    /// Contexts are then created on an EGL device (Mesa's llvmpipe on machines without a
+    /// GPU) and only virtual monitors exist; read their frames with
+    /// `Monitor::capture_frame()`. Without the `gbm` feature there is no headless mode and
+    /// `init` fails with [`EasyDRMError::NoDevice`].
+    pub fn headless(mut self) -> Self {
+        self.headless = true;
+        self
+    }
+
+    /// Renders with the CPU: monitors scan out double-buffered dumb buffers, drawn
+    /// through `Monitor::pixels_mut()`, instead of having an OpenGL ES context.
+    ///
+    /// Neither EGL nor GBM is used, so this works on display controllers without a GPU
+    /// (e.g. simple SPI panels or `simpledrm`); it's the default backend of builds without
+    /// the `gbm` feature. It needs a DRM card; the user context
+    /// constructor gets no GL bindings (`gl` and `get_proc_address` are `None`).
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut easydrm = EasyDRM::init_with_options(EasyDRMOptions::new().cpu_rendering(), |_| ())?;
+    /// for monitor in easydrm.monitors() {
+    ///     let mut pixels = monitor.pixels_mut()?;
+    ///     for y in 0..pixels.height() {
+    ///         pixels.row_mut(y).unwrap().fill(0x00ff_ffff); // white
+    ///     }
+    /// }
+    /// easydrm.swap_buffers()?;
+    /// ```
+    pub fn cpu_rendering(self) -> Self {
+        self.render_backend(RenderBackendKind::DumbBuffers)
+    }
+
+    /// Picks the [`RenderBackend`] producing the frames of every monitor, including
+    /// virtual and hot-plugged ones. Defaults to OpenGL ES on GBM.
+    ///
+    /// Only the default backend can run headless, the others need a DRM card.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let factory: RenderBackendFactory = Arc::new(|request| {
+    ///     Ok(Box::new(MyVulkanBackend::new(request.drm_fd, request.width, request.height)?)
+    ///         as Box<dyn RenderBackend>)
+    /// });
+    /// let easydrm = EasyDRM::init_with_options(
+    ///     EasyDRMOptions::new().render_backend(RenderBackendKind::Custom(factory)),
+    ///     |_| (),
+    /// )?;
+    /// ```
+    pub fn render_backend(mut self, kind: RenderBackendKind) -> Self {
+        self.render_backend = kind;
+        self
+    }
+
+    /// Adds a virtual monitor at initialization, like
+    /// [`EasyDRM::add_virtual_monitor`]. Can be called several times.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// // Two fake outputs for a CI test, without any GPU
+    /// let easydrm = EasyDRM::init_with_options(
+    ///     EasyDRMOptions::new()
+    ///         .headless()
+    ///         .virtual_monitor(1920, 1080, 60.0)
+    ///         .virtual_monitor(1280, 720, 30.0),
+    ///     |_| (),
+    /// )?;
+    /// ```
+    pub fn virtual_monitor(mut self, width: u16, height: u16, refresh: f64) -> Self {
+        self.virtual_monitors.push((width, height, refresh));
+        self
+    }
 }
 
 #[derive(Debug, Error)]
@@ -116,18 +533,30 @@ pub enum EasyDRMError {
     IOError(#[from] std::io::Error),
     #[error("Monitor setup error: {0}")]
     MonitorSetup(#[from] MonitorSetupError),
+    #[error("no DRM card or EGL device found")]
+    NoDevice,
 }
 
 pub struct EasyDRM<T> {
-    card: Card,
-    gbm_device: GbmDevice<std::fs::File>,
+    /// `None` when running headless, without a DRM card
+    device: Option<Arc<GpuDevice>>,
+    #[cfg(feature = "gbm")]
+    headless: Option<HeadlessDevice>,
     monitors: HashMap<connector::Handle, Monitor<T>>,
+    /// Set when monitor contexts are shared
+    share_group: Option<Arc<ShareGroup>>,
+    /// Set once a monitor renders on its own thread, wakes `poll_events()` for its frames
+    #[cfg(feature = "gbm")]
+    frame_waker: Option<Arc<FrameWaker>>,
     refresh_rate_groups: HashMap<u32, Vec<connector::Handle>>, // refresh_rate -> connector handles
     fastest_group_refresh: Option<u32>,
     fastest_group_pending: HashSet<connector::Handle>,
     should_update_flag: bool,
     context_constructor: Box<dyn for<'a> Fn(&MonitorContextCreationRequest<'a>) -> T + 'static>,
+    options: EasyDRMOptions,
+    kernel_mode_hints: KernelModeHints,
     uevent_socket: Option<hotplug::UEventSocket>,
+    next_virtual_id: u32,
 }
 
 impl<T> EasyDRM<T> {
@@ -135,7 +564,8 @@ impl<T> EasyDRM<T> {
     ///
     /// The context_constructor receives a [`MonitorContextCreationRequest`] with access to GL
     /// bindings, framebuffer size, and a `get_proc_address` helper so you can load extra
-    /// OpenGL functions during initialization. Use this to set up per-monitor resources
+    /// OpenGL functions during initialization (both `None` for monitors rendering without
+    /// OpenGL ES). Use this to set up per-monitor resources
     /// (Skia surfaces, Cairo contexts, etc.)
     ///
     /// # Example
@@ -147,52 +577,113 @@ impl<T> EasyDRM<T> {
     ///
     /// let easydrm = EasyDRM::init(|req| {
     ///     MyContext {
-    ///         skia_surface: create_skia_surface(req.gl),
+    ///         skia_surface: create_skia_surface(req.gl.expect("OpenGL ES backend")),
     ///     }
     /// })?;
     /// ```
     ///
     /// Note: EasyDRM will successfully initialize even with zero monitors connected as long as you have a GPU.
     /// Monitors can be hot-plugged later and will be automatically discovered via `poll_events()`.
+    /// Without any DRM card, it renders headless instead (see [`EasyDRMOptions::headless`]).
+    /// If there is a card that can't be opened, this returns the open error.
     pub fn init<F>(context_constructor: F) -> Result<Self, EasyDRMError>
     where
         F: for<'a> Fn(&MonitorContextCreationRequest<'a>) -> T + 'static,
     {
-        // Open DRM card
-        let card = Card::open_default_card();
-
-        // Enable required capabilities
-        card.set_client_capability(drm::ClientCapability::UniversalPlanes, true)
-            .expect("Unable to request UniversalPlanes capability");
-        card.set_client_capability(drm::ClientCapability::Atomic, true)
-            .expect("Unable to request Atomic capability");
-
-        // Create GBM device (needs ownership, so we clone the file descriptor)
-        let fd = card.as_raw_fd();
-        let gbm_device = unsafe {
-            use std::os::unix::io::FromRawFd;
-            GbmDevice::new(std::fs::File::from_raw_fd(libc::dup(fd)))
-                .expect("Failed to create GBM device")
+        Self::init_with_options(EasyDRMOptions::default(), context_constructor)
+    }
+
+    /// Initialize EasyDRM with custom [`EasyDRMOptions`] and a context constructor
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let config = DisplayConfig::load("/etc/kiosk/displays.toml")?;
+    /// let easydrm = EasyDRM::init_with_options(
+    ///     EasyDRMOptions::new().display_config(config),
+    ///     |_| (),
+    /// )?;
+    /// ```
+    pub fn init_with_options<F>(
+        options: EasyDRMOptions,
+        context_constructor: F,
+    ) -> Result<Self, EasyDRMError>
+    where
+        F: for<'a> Fn(&MonitorContextCreationRequest<'a>) -> T + 'static,
+    {
+        let device = match options.headless {
+            true => None,
+            false => GpuDevice::open_default(options.render_backend.needs_gbm())?.map(Arc::new),
         };
+        // Without a DRM card, virtual monitors render on an EGL device (e.g. llvmpipe).
+        // Other backends allocate their buffers on the card.
+        #[cfg(feature = "gbm")]
+        let headless = match (&device, &options.render_backend) {
+            (Some(_), _) => None,
+            (None, RenderBackendKind::GbmEgl) => {
+                Some(HeadlessDevice::open().ok_or(EasyDRMError::NoDevice)?)
+            }
+            (None, _) => return Err(EasyDRMError::NoDevice),
+        };
+        #[cfg(not(feature = "gbm"))]
+        if device.is_none() {
+            return Err(EasyDRMError::NoDevice);
+        }
 
+        #[cfg(feature = "gbm")]
+        let share_group = match (&device, &headless) {
+            _ if !options.shared_gl_contexts || !options.render_backend.needs_egl() => None,
+            (Some(device), _) => Some(ShareGroup::new(device.gbm()?, &options.default_gl_config())),
+            (None, Some(headless)) => Some(ShareGroup::headless(
+                headless.egl_device(),
+                &options.default_gl_config(),
+            )),
+            (None, None) => None,
+        }
+        .transpose()
+        .map_err(MonitorSetupError::from)?
+        .map(Arc::new);
+        #[cfg(not(feature = "gbm"))]
+        let share_group = None;
+
+        // Read `video=` overrides; a missing or unreadable cmdline just means no hints
+        let kernel_mode_hints = options
+            .kernel_cmdline_path
+            .as_ref()
+            .and_then(|path| KernelModeHints::load(path).ok())
+            .unwrap_or_default();
+
+        let virtual_monitors = options.virtual_monitors.clone();
         let mut easydrm = EasyDRM {
-            card,
-            gbm_device,
+            uevent_socket: device
+                .as_ref()
+                .and_then(|_| hotplug::UEventSocket::open().ok()),
+            device,
+            #[cfg(feature = "gbm")]
+            headless,
             monitors: HashMap::new(),
+            share_group,
+            #[cfg(feature = "gbm")]
+            frame_waker: None,
             refresh_rate_groups: HashMap::new(),
             fastest_group_refresh: None,
             fastest_group_pending: HashSet::new(),
             should_update_flag: false,
             context_constructor: Box::new(context_constructor),
-            uevent_socket: hotplug::UEventSocket::open().ok(),
+            options,
+            kernel_mode_hints,
+            next_virtual_id: 0,
         };
-        if easydrm.uevent_socket.is_none() {
+        if easydrm.device.is_some() && easydrm.uevent_socket.is_none() {
             eprintln!(
                 "[WARNING] Failed to open uevent socket, monitors plugged in afterwards won't be able to be detected"
             );
         }
         // Discover and initialize all connected monitors
         easydrm.discover_monitors()?;
+        for (width, height, refresh) in virtual_monitors {
+            easydrm.add_virtual_monitor(width, height, refresh)?;
+        }
 
         // It's OK to have zero monitors - they might be connected later via hotplug
 
@@ -201,10 +692,13 @@ impl<T> EasyDRM<T> {
 
     /// Discover all connected monitors and initialize them
     fn discover_monitors(&mut self) -> Result<(), EasyDRMError> {
-        let res = self.card.resource_handles()?;
+        let Some(device) = self.device.clone() else {
+            return Ok(());
+        };
+        let res = device.card.resource_handles()?;
 
-        // Get all connector handles
-        let connector_handles: Vec<connector::Handle> = res.connectors().to_vec();
+        // Get all connector handles, leaving out writeback connectors
+        let connector_handles = Self::display_connectors(&device, &res);
         let (mut used_crtcs, mut used_primary_planes, mut used_cursor_planes) =
             self.current_resource_usage();
 
@@ -215,6 +709,7 @@ impl<T> EasyDRM<T> {
             }
 
             let allocation = match self.allocate_monitor_resources(
+                &device,
                 connector_id,
                 &used_crtcs,
                 &used_primary_planes,
@@ -231,20 +726,28 @@ impl<T> EasyDRM<T> {
             };
 
             match Monitor::setup(
-                &self.card,
-                &self.gbm_device,
+                &device,
                 connector_id,
                 allocation,
+                &self.options,
+                self.share_group.as_ref(),
+                &self.kernel_mode_hints,
                 |request| (self.context_constructor)(request),
             ) {
                 Ok(monitor) => {
-                    used_crtcs.insert(monitor.crtc().handle());
-                    used_primary_planes.insert(monitor.primary_plane());
+                    used_crtcs.extend(monitor.try_crtc().map(|crtc| crtc.handle()));
+                    used_primary_planes.extend(monitor.try_primary_plane());
                     if let Some(cursor) = monitor.cursor_plane() {
                         used_cursor_planes.insert(cursor);
                     }
                     self.monitors.insert(connector_id, monitor);
                 }
+                Err(
+                    e @ (MonitorSetupError::DisabledByConfig
+                    | MonitorSetupError::DisabledByKernelCmdline),
+                ) => {
+                    println!("[INFO] Monitor {:?}: {}.", connector_id, e);
+                }
                 Err(e) => {
                     eprintln!("Warning: Failed to setup monitor {:?}: {}", connector_id, e);
                 }
@@ -294,17 +797,23 @@ impl<T> EasyDRM<T> {
 
     /// Handle hotplug events - add/remove monitors as needed
     fn handle_hotplug(&mut self) -> Result<(), EasyDRMError> {
-        let res = self.card.resource_handles()?;
+        let Some(device) = self.device.clone() else {
+            return Ok(());
+        };
+        let res = device.card.resource_handles()?;
 
         // Get current connected monitors from DRM
-        let drm_connected = res
-            .connectors()
+        let drm_connected = Self::display_connectors(&device, &res)
             .into_iter()
-            .copied()
             .collect::<HashSet<_>>();
 
-        // Get current monitor connector IDs
-        let current_monitors: HashSet<connector::Handle> = self.monitors.keys().copied().collect();
+        // Get current monitor connector IDs (virtual monitors aren't affected by hotplug)
+        let current_monitors: HashSet<connector::Handle> = self
+            .monitors
+            .iter()
+            .filter(|(_, monitor)| !monitor.is_virtual())
+            .map(|(&connector_id, _)| connector_id)
+            .collect();
 
         // Find monitors that were disconnected
         let disconnected: Vec<connector::Handle> = current_monitors
@@ -332,6 +841,7 @@ impl<T> EasyDRM<T> {
         // Add newly connected monitors
         for connector_id in newly_connected {
             let allocation = match self.allocate_monitor_resources(
+                &device,
                 connector_id,
                 &used_crtcs,
                 &used_primary_planes,
@@ -348,20 +858,28 @@ impl<T> EasyDRM<T> {
             };
 
             match Monitor::setup(
-                &self.card,
-                &self.gbm_device,
+                &device,
                 connector_id,
                 allocation,
+                &self.options,
+                self.share_group.as_ref(),
+                &self.kernel_mode_hints,
                 |request| (self.context_constructor)(request),
             ) {
                 Ok(monitor) => {
-                    used_crtcs.insert(monitor.crtc().handle());
-                    used_primary_planes.insert(monitor.primary_plane());
+                    used_crtcs.extend(monitor.try_crtc().map(|crtc| crtc.handle()));
+                    used_primary_planes.extend(monitor.try_primary_plane());
                     if let Some(cursor) = monitor.cursor_plane() {
                         used_cursor_planes.insert(cursor);
                     }
                     self.monitors.insert(connector_id, monitor);
                 }
+                Err(
+                    e @ (MonitorSetupError::DisabledByConfig
+                    | MonitorSetupError::DisabledByKernelCmdline),
+                ) => {
+                    println!("[INFO] Monitor {:?}: {}.", connector_id, e);
+                }
                 Err(e) => {
                     eprintln!("Warning: Failed to setup monitor {:?}: {}", connector_id, e);
                 }
@@ -388,8 +906,8 @@ impl<T> EasyDRM<T> {
         let mut used_cursor_planes = HashSet::new();
 
         for monitor in self.monitors.values() {
-            used_crtcs.insert(monitor.crtc().handle());
-            used_primary_planes.insert(monitor.primary_plane());
+            used_crtcs.extend(monitor.try_crtc().map(|crtc| crtc.handle()));
+            used_primary_planes.extend(monitor.try_primary_plane());
             if let Some(cursor) = monitor.cursor_plane() {
                 used_cursor_planes.insert(cursor);
             }
@@ -400,31 +918,38 @@ impl<T> EasyDRM<T> {
 
     fn allocate_monitor_resources(
         &self,
+        device: &GpuDevice,
         connector_id: connector::Handle,
         used_crtcs: &HashSet<crtc::Handle>,
         used_primary_planes: &HashSet<plane::Handle>,
         used_cursor_planes: &HashSet<plane::Handle>,
     ) -> Result<MonitorResourceAllocation, MonitorSetupError> {
-        let connector = self.card.get_connector(connector_id, true)?;
-        if connector.state() != connector::State::Connected {
+        let connector = device.card.get_connector(connector_id, true)?;
+        // `video=<connector>:e` (or `D`) sets up the output even without a display
+        let forced_on = self
+            .kernel_mode_hints
+            .for_connector(&output::connector_name(&connector))
+            .is_some_and(VideoModeHint::forces_on);
+        if connector.state() != connector::State::Connected && !forced_on {
             return Err(MonitorSetupError::NotConnected);
         }
 
-        let res = self.card.resource_handles()?;
-        let crtc_candidates = self.crtc_candidates_for_connector(&connector, &res, used_crtcs)?;
-        let planes = self.card.plane_handles()?;
+        let res = device.card.resource_handles()?;
+        let crtc_candidates =
+            self.crtc_candidates_for_connector(device, &connector, &res, used_crtcs)?;
+        let planes = device.card.plane_handles()?;
         let plane_handles: Vec<plane::Handle> = planes.iter().copied().collect();
 
         let mut crtc_info = None;
         for handle in crtc_candidates {
-            if let Ok(info) = self.card.get_crtc(handle) {
+            if let Ok(info) = device.card.get_crtc(handle) {
                 crtc_info = Some(info);
                 break;
             }
         }
         let crtc_info = crtc_info.ok_or(MonitorSetupError::NoCRTCFound)?;
 
-        let primary_plane = self
+        let primary_plane = device
             .find_plane_for_crtc(
                 &plane_handles,
                 &res,
@@ -434,7 +959,7 @@ impl<T> EasyDRM<T> {
             )?
             .ok_or(MonitorSetupError::NoPrimaryPlaneFound)?;
 
-        let cursor_plane = self.find_plane_for_crtc(
+        let mut cursor_plane = device.find_plane_for_crtc(
             &plane_handles,
             &res,
             crtc_info.handle(),
@@ -442,15 +967,340 @@ impl<T> EasyDRM<T> {
             used_cursor_planes,
         )?;
 
+        // Without a cursor plane, show the cursor on a free overlay that can scan out ARGB
+        let mut cursor_on_overlay = false;
+        if cursor_plane.is_none() {
+            let argb_planes = device.planes_with_format(&plane_handles, DrmFourcc::Argb8888);
+            let mut used_overlays = device.claimed_planes();
+            used_overlays.extend(used_cursor_planes);
+            cursor_plane = device.find_plane_for_crtc(
+                &argb_planes,
+                &res,
+                crtc_info.handle(),
+                PlaneType::Overlay,
+                &used_overlays,
+            )?;
+            cursor_on_overlay = cursor_plane.is_some();
+        }
+
         Ok(MonitorResourceAllocation {
             crtc_info,
             primary_plane,
             cursor_plane,
+            cursor_on_overlay,
         })
     }
 
+    /// Returns the connectors that drive displays, leaving out writeback connectors.
+    fn display_connectors(
+        device: &GpuDevice,
+        res: &drm::control::ResourceHandles,
+    ) -> Vec<connector::Handle> {
+        res.connectors()
+            .iter()
+            .copied()
+            .filter(|&connector| !writeback::is_writeback(&device.card, connector))
+            .collect()
+    }
+
+    /// Returns the writeback connectors of the card.
+    ///
+    /// Writeback connectors capture the composed output of a CRTC into a buffer instead
+    /// of driving a display (vkms and many ARM display controllers have them). They are
+    /// not set up as monitors; use them with [`request_writeback`](Self::request_writeback).
+    pub fn writeback_connectors(&self) -> Vec<connector::Handle> {
+        let Some(device) = &self.device else {
+            return Vec::new();
+        };
+        device
+            .card
+            .resource_handles()
+            .map(|res| {
+                res.connectors()
+                    .iter()
+                    .copied()
+                    .filter(|&connector| writeback::is_writeback(&device.card, connector))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Captures what `monitor` shows, as composed by the display controller (primary
+    /// plane, overlays and cursor), through a free writeback connector.
+    ///
+    /// The capture is attached to the monitor's next commit; get the frame with
+    /// [`wait_writeback`](Self::wait_writeback) or `Monitor::take_writeback()` afterwards.
+    /// This works without a GPU on vkms, which makes pixel-level integration tests
+    /// possible.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// easydrm.request_writeback(connector)?;
+    /// // ... draw the frame to check
+    /// easydrm.swap_buffers()?;
+    /// let frame = easydrm.wait_writeback(connector, Duration::from_secs(1))?;
+    /// assert_eq!(frame.pixel(0, 0), Some([0, 0, 0, 255]));
+    /// ```
+    #[cfg(feature = "gbm")]
+    pub fn request_writeback(&mut self, monitor: connector::Handle) -> Result<(), WritebackError> {
+        let busy: HashSet<connector::Handle> = self
+            .monitors
+            .values()
+            .filter_map(|monitor| monitor.writeback_connector())
+            .collect();
+        let candidates = self.writeback_connectors();
+        let device = self
+            .device
+            .clone()
+            .ok_or(WritebackError::NoWritebackConnector)?;
+        let monitor = self
+            .monitors
+            .get_mut(&monitor)
+            .ok_or(WritebackError::NoSuchMonitor)?;
+        if monitor.writeback_connector().is_some() {
+            return Err(WritebackError::AlreadyPending);
+        }
+
+        let crtc = monitor
+            .try_crtc()
+            .ok_or(WritebackError::NoWritebackConnector)?
+            .handle();
+        let connector = candidates
+            .into_iter()
+            .filter(|connector| !busy.contains(connector))
+            .find(|&connector| {
+                writeback::possible_crtcs(&device.card, connector)
+                    .is_ok_and(|crtcs| crtcs.contains(&crtc))
+            })
+            .ok_or(WritebackError::NoWritebackConnector)?;
+        let (width, height) = monitor.active_mode().size();
+        let job = WritebackJob::new(device, connector, width as u32, height as u32)?;
+        monitor.set_writeback(job);
+        Ok(())
+    }
+
+    /// Waits up to `timeout` for the frame captured by
+    /// [`request_writeback`](Self::request_writeback), handling DRM events meanwhile.
+    ///
+    /// The capture is done when its out fence signals or, on writeback connectors
+    /// without `WRITEBACK_OUT_FENCE_PTR`, when the commit's page flip completes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WritebackError::Timeout`] if the capture isn't done after `timeout`; it
+    /// stays pending. Returns [`WritebackError::NotRequested`] if no capture is pending.
+    #[cfg(feature = "gbm")]
+    pub fn wait_writeback(
+        &mut self,
+        monitor: connector::Handle,
+        timeout: Duration,
+    ) -> Result<CapturedFrame, WritebackError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let monitor_ref = self
+                .monitors
+                .get_mut(&monitor)
+                .ok_or(WritebackError::NoSuchMonitor)?;
+            if let Some(frame) = monitor_ref.take_writeback()? {
+                return Ok(frame);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let Some(device) = self.device.clone().filter(|_| !remaining.is_zero()) else {
+                return Err(WritebackError::Timeout);
+            };
+
+            let mut fds = vec![PollFd::new(device.card.as_fd(), PollFlags::POLLIN)];
+            fds.extend(
+                monitor_ref
+                    .writeback_out_fence()
+                    .map(|fence| PollFd::new(fence, PollFlags::POLLIN)),
+            );
+            let timeout = PollTimeout::try_from(remaining).unwrap_or(PollTimeout::MAX);
+            poll(&mut fds, timeout).map_err(std::io::Error::from)?;
+            let drm_ready = fds[0]
+                .revents()
+                .is_some_and(|revents| revents.contains(PollFlags::POLLIN));
+            drop(fds);
+            if drm_ready {
+                self.handle_drm_events()?;
+            }
+        }
+    }
+
+    /// Adds a monitor that renders offscreen, with no display behind it.
+    ///
+    /// Virtual monitors get a connector handle of their own and are named `VIRTUAL-1`,
+    /// `VIRTUAL-2`, etc. Their page flips complete on a timer running at `refresh` Hz,
+    /// so they show up in [`monitors`](Self::monitors), [`should_update`](Self::should_update)
+    /// and the refresh rate groups like any real output. Read what they presented with
+    /// `Monitor::capture_frame()` or `Monitor::set_frame_export()`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let connector = easydrm.add_virtual_monitor(1280, 720, 60.0)?.connector_id();
+    /// ```
+    pub fn add_virtual_monitor(
+        &mut self,
+        width: u16,
+        height: u16,
+        refresh: f64,
+    ) -> Result<&mut Monitor<T>, EasyDRMError> {
+        let mode = ModeBuilder::new(width, height, refresh)
+            .build()
+            .map_err(MonitorSetupError::from)?;
+        // CVT rounds the pixel clock, which skews the refresh rate of tiny modes
+        let mut mode_info = drm_sys::drm_mode_modeinfo::from(mode);
+        mode_info.vrefresh = refresh.round() as u32;
+        let mode = drm::control::Mode::from(mode_info);
+        self.next_virtual_id += 1;
+        let connector_id: connector::Handle =
+            drm::control::from_u32(VIRTUAL_CONNECTOR_BASE + self.next_virtual_id)
+                .expect("virtual connector ids are non-zero");
+        let connector_name = format!("VIRTUAL-{}", self.next_virtual_id);
+        #[cfg_attr(not(feature = "gbm"), allow(unused_mut))]
+        let mut backend: Box<dyn RenderBackend> = match &self.device {
+            Some(device) => match &self.options.render_backend {
+                #[cfg(feature = "gbm")]
+                RenderBackendKind::GbmEgl => Box::new(GbmEglBackend::offscreen(
+                    GlesContext::new(
+                        device.gbm()?,
+                        width,
+                        height,
+                        &self.options.default_gl_config(),
+                        self.share_group.as_deref(),
+                    )
+                    .map_err(MonitorSetupError::from)?,
+                )),
+                RenderBackendKind::DumbBuffers => {
+                    Box::new(DumbBuffers::new(Arc::clone(device), width, height)?)
+                }
+                #[cfg(feature = "vulkan")]
+                RenderBackendKind::Vulkan(vulkan) => Box::new(
+                    VulkanBackend::new(Arc::clone(device), vulkan, width, height, None)
+                        .map_err(MonitorSetupError::from)?,
+                ),
+                RenderBackendKind::Custom(factory) => factory(&RenderBackendRequest {
+                    drm_fd: device.card.as_fd(),
+                    connector: connector_id,
+                    width,
+                    height,
+                    plane_formats: None,
+                })
+                .map_err(MonitorSetupError::from)?,
+            },
+            #[cfg(feature = "gbm")]
+            None => {
+                let headless = self
+                    .headless
+                    .as_ref()
+                    .expect("EasyDRM always has a DRM or a headless device");
+                Box::new(GbmEglBackend::offscreen(
+                    GlesContext::offscreen(
+                        headless.egl_device(),
+                        width,
+                        height,
+                        &self.options.default_gl_config(),
+                        self.share_group.as_deref(),
+                    )
+                    .map_err(MonitorSetupError::from)?,
+                ))
+            }
+            #[cfg(not(feature = "gbm"))]
+            None => unreachable!("EasyDRM always has a DRM device without the `gbm` feature"),
+        };
+        #[cfg(feature = "gbm")]
+        enable_gl_debug_output(
+            backend.as_mut(),
+            self.options
+                .gl_debug_output_for(connector_id, &connector_name),
+        )
+        .map_err(MonitorSetupError::from)?;
+        let monitor = Monitor::setup_virtual(
+            self.device.as_ref(),
+            backend,
+            connector_id,
+            connector_name,
+            mode,
+            refresh,
+            |request| (self.context_constructor)(request),
+        )?;
+        println!(
+            "[INFO] Added virtual monitor {} ({}x{}@{})",
+            monitor.connector_name(),
+            width,
+            height,
+            refresh
+        );
+        self.monitors.insert(connector_id, monitor);
+        self.update_refresh_rate_groups();
+        Ok(self
+            .monitors
+            .get_mut(&connector_id)
+            .expect("virtual monitor was just inserted"))
+    }
+
+    /// Removes a monitor added with [`add_virtual_monitor`](Self::add_virtual_monitor).
+    ///
+    /// Returns false if `connector` isn't a virtual monitor.
+    pub fn remove_virtual_monitor(&mut self, connector: connector::Handle) -> bool {
+        if !self
+            .monitors
+            .get(&connector)
+            .is_some_and(|monitor| monitor.is_virtual())
+        {
+            return false;
+        }
+        self.monitors.remove(&connector);
+        self.update_refresh_rate_groups();
+        true
+    }
+
+    /// Creates an offscreen context sharing objects with every monitor's context, for
+    /// uploading assets on a background thread. See [`ResourceContext`].
+    ///
+    /// Fails with `GlesContextError::NotShared` unless
+    /// [`EasyDRMOptions::shared_gl_contexts`] is enabled.
+    #[cfg(feature = "gbm")]
+    pub fn create_resource_context(&self) -> Result<ResourceContext, GlesContextError> {
+        self.share_group
+            .as_ref()
+            .ok_or(GlesContextError::NotShared)?
+            .resource_context()
+    }
+
+    /// Moves a monitor's OpenGL ES context to a [`RenderThread`], to render it from
+    /// another thread. The monitor then presents the frames the thread finishes with
+    /// the next [`swap_buffers`](Self::swap_buffers), and [`poll_events`](Self::poll_events)
+    /// returns when one is ready.
+    ///
+    /// Only monitors rendering with the default OpenGL ES backend can be moved. Monitors
+    /// connected later (hotplug, `add_virtual_monitor`) render on the main thread until
+    /// their thread is taken too. Once the monitor is removed, the thread's
+    /// `wait_for_flip()` fails with [`RenderThreadError::Disconnected`].
+    #[cfg(feature = "gbm")]
+    pub fn take_render_thread(
+        &mut self,
+        connector: connector::Handle,
+    ) -> Result<RenderThread, RenderThreadError> {
+        let monitor = self
+            .monitors
+            .get_mut(&connector)
+            .ok_or(RenderThreadError::NoSuchMonitor)?;
+        let waker = match &self.frame_waker {
+            Some(waker) => waker.clone(),
+            None => self
+                .frame_waker
+                .insert(Arc::new(FrameWaker::new()?))
+                .clone(),
+        };
+        monitor.detach_render_thread(waker)
+    }
+
     fn crtc_candidates_for_connector(
         &self,
+        device: &GpuDevice,
         connector: &connector::Info,
         res: &drm::control::ResourceHandles,
         used_crtcs: &HashSet<crtc::Handle>,
@@ -459,7 +1309,7 @@ impl<T> EasyDRM<T> {
         let mut candidates = Vec::new();
 
         for encoder_handle in connector.encoders() {
-            let Ok(encoder) = self.card.get_encoder(*encoder_handle) else {
+            let Ok(encoder) = device.card.get_encoder(*encoder_handle) else {
                 continue;
             };
 
@@ -476,58 +1326,6 @@ impl<T> EasyDRM<T> {
         Ok(candidates)
     }
 
-    fn find_plane_for_crtc(
-        &self,
-        plane_handles: &[plane::Handle],
-        res: &drm::control::ResourceHandles,
-        crtc_handle: crtc::Handle,
-        plane_type: PlaneType,
-        used_planes: &HashSet<plane::Handle>,
-    ) -> Result<Option<plane::Handle>, MonitorSetupError> {
-        for plane_handle in plane_handles {
-            if used_planes.contains(plane_handle) {
-                continue;
-            }
-
-            let Ok(plane_info) = self.card.get_plane(*plane_handle) else {
-                continue;
-            };
-            let compatible_crtcs = res.filter_crtcs(plane_info.possible_crtcs());
-            if !compatible_crtcs.contains(&crtc_handle) {
-                continue;
-            }
-
-            if self.plane_is_type(*plane_handle, plane_type)? {
-                return Ok(Some(*plane_handle));
-            }
-        }
-
-        Ok(None)
-    }
-
-    fn plane_is_type(
-        &self,
-        plane_handle: plane::Handle,
-        plane_type: PlaneType,
-    ) -> Result<bool, MonitorSetupError> {
-        let properties = self.card.get_properties(plane_handle)?;
-        for (&id, &value) in properties.iter() {
-            let Ok(info) = self.card.get_property(id) else {
-                continue;
-            };
-
-            if info
-                .name()
-                .to_str()
-                .map(|name| name == "type")
-                .unwrap_or(false)
-            {
-                return Ok(value == (plane_type as u32).into());
-            }
-        }
-
-        Ok(false)
-    }
     /// Poll for events (page flip, hotplug, etc.)
     /// This blocks until an event is received
     pub fn poll_events(&mut self) -> Result<(), EasyDRMError> {
@@ -538,11 +1336,14 @@ impl<T> EasyDRM<T> {
         &mut self,
         extra_fds: impl IntoIterator<Item = RawFd>,
     ) -> Result<(), EasyDRMError> {
-        let drm_fd = self.card.as_fd();
+        let device = self.device.clone();
         let uevents_socket = self.uevent_socket.as_ref();
 
         // preparar descritores para poll
-        let mut fds = vec![PollFd::new(drm_fd, PollFlags::POLLIN)];
+        let mut fds = Vec::new();
+        if let Some(device) = &device {
+            fds.push(PollFd::new(device.card.as_fd(), PollFlags::POLLIN));
+        }
         fds.extend(
             extra_fds
                 .into_iter()
@@ -551,26 +1352,80 @@ impl<T> EasyDRM<T> {
         if let Some(uevents_socket) = uevents_socket {
             fds.push(PollFd::new(uevents_socket.fd.as_fd(), PollFlags::POLLIN));
         }
+        // Frames finished by render threads
+        #[cfg(feature = "gbm")]
+        let frame_waker = self.frame_waker.clone();
+        #[cfg(feature = "gbm")]
+        if let Some(frame_waker) = &frame_waker {
+            fds.push(PollFd::new(frame_waker.fd(), PollFlags::POLLIN));
+        }
+        // Simulated vblanks of virtual monitors
+        let virtual_timers: Vec<(connector::Handle, RawFd)> = self
+            .monitors
+            .iter()
+            .filter_map(|(&connector_id, monitor)| {
+                let timer = monitor.virtual_timer_fd()?;
+                Some((connector_id, timer.as_raw_fd()))
+            })
+            .collect();
+        fds.extend(
+            virtual_timers.iter().map(|&(_, fd)| {
+                PollFd::new(unsafe { BorrowedFd::borrow_raw(fd) }, PollFlags::POLLIN)
+            }),
+        );
 
         // bloquear até haver evento
         poll(&mut fds, PollTimeout::NONE).ok();
 
-        let drm_ready = fds[0]
-            .revents()
-            .unwrap_or(PollFlags::empty())
-            .contains(PollFlags::POLLIN);
-
-        if let Some(uevents_socket) = uevents_socket {
-            let hotplug_ready = fds
-                .iter()
+        let drm_ready = device.is_some()
+            && fds[0]
+                .revents()
+                .unwrap_or(PollFlags::empty())
+                .contains(PollFlags::POLLIN);
+        let hotplug_ready = uevents_socket.is_some_and(|uevents_socket| {
+            fds.iter()
                 .find(|p| p.as_fd().as_raw_fd() == uevents_socket.fd.as_raw_fd())
                 .and_then(|p| p.revents())
                 .unwrap_or(PollFlags::empty())
-                .contains(PollFlags::POLLIN);
+                .contains(PollFlags::POLLIN)
+        });
+        #[cfg(feature = "gbm")]
+        let frame_ready = frame_waker.as_ref().is_some_and(|frame_waker| {
+            fds.iter()
+                .find(|p| p.as_fd().as_raw_fd() == frame_waker.fd().as_raw_fd())
+                .and_then(|p| p.revents())
+                .unwrap_or(PollFlags::empty())
+                .contains(PollFlags::POLLIN)
+        });
+        let fired_timers: Vec<connector::Handle> = fds[fds.len() - virtual_timers.len()..]
+            .iter()
+            .zip(&virtual_timers)
+            .filter(|(fd, _)| {
+                fd.revents()
+                    .unwrap_or(PollFlags::empty())
+                    .contains(PollFlags::POLLIN)
+            })
+            .map(|(_, &(connector_id, _))| connector_id)
+            .collect();
+        drop(fds);
+
+        // The frames themselves are picked up by `swap_buffers()`
+        #[cfg(feature = "gbm")]
+        if let Some(frame_waker) = frame_waker.filter(|_| frame_ready) {
+            frame_waker.drain();
+        }
+
+        if let Some(uevents_socket) = uevents_socket
+            && hotplug_ready
+            && uevents_socket.drain_hotplug_events().unwrap_or(false)
+        {
+            println!("[INFO] Hotplug detected, refreshing monitors.");
+            self.handle_hotplug()?;
+        }
 
-            if hotplug_ready && uevents_socket.drain_hotplug_events().unwrap_or(false) {
-                println!("[INFO] Hotplug detected, refreshing monitors.");
-                self.handle_hotplug()?;
+        for connector_id in fired_timers {
+            if let Some(monitor) = self.monitors.get_mut(&connector_id) {
+                monitor.virtual_vblank();
             }
         }
 
@@ -600,36 +1455,133 @@ impl<T> EasyDRM<T> {
         self.monitors.get(&connector_id)
     }
 
+    /// Get a specific monitor by its stable [`MonitorId`]
+    ///
+    /// An exact match (same EDID identity and connector name) is preferred. If the
+    /// connector name changed (e.g. the monitor moved from `DP-2` to `DP-3` after an MST
+    /// rebuild), the monitor with the same EDID identity is returned, unless several
+    /// connected monitors share it (identical panels without serials), in which case
+    /// the lookup is ambiguous and returns `None`.
+    pub fn get_monitor_by_id(&self, id: &MonitorId) -> Option<&Monitor<T>> {
+        let connector_id = self.find_monitor_by_id(id)?;
+        self.monitors.get(&connector_id)
+    }
+
+    /// Get a specific monitor by its stable [`MonitorId`]
+    ///
+    /// See [`get_monitor_by_id`](Self::get_monitor_by_id) for the matching rules.
+    pub fn get_monitor_by_id_mut(&mut self, id: &MonitorId) -> Option<&mut Monitor<T>> {
+        let connector_id = self.find_monitor_by_id(id)?;
+        self.monitors.get_mut(&connector_id)
+    }
+
+    fn find_monitor_by_id(&self, id: &MonitorId) -> Option<connector::Handle> {
+        id.find_in(
+            self.monitors
+                .iter()
+                .map(|(&connector_id, m)| (connector_id, m.id())),
+        )
+    }
+
     /// Swap buffers for all monitors that were drawn to.
     ///
     /// Each monitor that set `was_drawn = true` during this frame gets its own
     /// `Monitor::swap_buffers()` call, which issues the atomic commit and
-    /// fence hand-off for that monitor.
+    /// fence hand-off for that monitor. Monitors where only overlay [`Layer`]s were
+    /// drawn or changed commit just those layers.
     pub fn swap_buffers(&mut self) -> Result<(), EasyDRMError> {
         let mut atomic_req = AtomicModeReq::new();
         // Determine commit flags
         let flags = AtomicCommitFlags::PAGE_FLIP_EVENT | AtomicCommitFlags::ALLOW_MODESET;
         // Rebuild the set with swapped monitors
         let mut committed = Vec::new();
+        let mut has_kms_changes = false;
         for (&connector_id, monitor) in self.monitors.iter_mut() {
             if monitor.was_drawn() {
-                monitor.swap_buffers(&self.card, &mut atomic_req)?;
+                monitor.swap_buffers(&mut atomic_req)?;
                 monitor.reset_drawn_flag();
+                has_kms_changes |= !monitor.is_virtual();
+                committed.push(connector_id);
+                continue;
+            }
+            #[cfg(feature = "gbm")]
+            if monitor.has_pending_layer_update()
+                && monitor.can_render()
+                && !monitor.needs_mode_set()
+            {
+                // Only overlays changed, keep the primary plane's frame
+                monitor.swap_layers(&mut atomic_req)?;
+                has_kms_changes = true;
                 committed.push(connector_id);
             }
         }
-        for connector_id in committed {
+        for &connector_id in &committed {
             self.mark_fast_group_commit(connector_id);
         }
+        // Virtual monitors only present offscreen
+        let Some(device) = self.device.as_ref().filter(|_| has_kms_changes) else {
+            return Ok(());
+        };
 
         // Submit atomic commit (queues the page flip, doesn't wait)
-        self.card
-            .atomic_commit(flags, atomic_req)
-            .map_err(|e| MonitorSetupError::DrmError(format!("Failed to commit: {}", e)))?;
+        let result = device.card.atomic_commit(flags, atomic_req);
+        for connector_id in committed {
+            if let Some(monitor) = self.monitors.get_mut(&connector_id) {
+                match result {
+                    Ok(()) => monitor.commit_succeeded(),
+                    Err(_) => monitor.commit_failed(),
+                }
+            }
+        }
+        result.map_err(|e| MonitorSetupError::DrmError(format!("Failed to commit: {}", e)))?;
 
         Ok(())
     }
 
+    /// Commit cursor changes of monitors that aren't being redrawn.
+    ///
+    /// Cursor changes are normally folded into the next [`swap_buffers`](Self::swap_buffers)
+    /// of their monitor. Call this after `Monitor::move_cursor()` & co. to show them right
+    /// away without drawing a new frame, e.g. when the pointer moves over a static screen.
+    ///
+    /// Monitors with a page flip in flight are skipped; their cursor is updated with the
+    /// next commit. Like a page flip, a cursor commit makes the monitor unavailable for
+    /// rendering until the next vblank.
+    pub fn commit_cursors(&mut self) -> Result<(), EasyDRMError> {
+        let mut atomic_req = AtomicModeReq::new();
+        let mut committed = Vec::new();
+        for (&connector_id, monitor) in self.monitors.iter_mut() {
+            if monitor.has_pending_cursor_update()
+                && monitor.can_render()
+                && !monitor.needs_mode_set()
+            {
+                monitor.add_cursor_to_request(&mut atomic_req);
+                committed.push(connector_id);
+            }
+        }
+        let Some(device) = self.device.as_ref().filter(|_| !committed.is_empty()) else {
+            return Ok(());
+        };
+
+        let result = device.card.atomic_commit(
+            AtomicCommitFlags::NONBLOCK | AtomicCommitFlags::PAGE_FLIP_EVENT,
+            atomic_req,
+        );
+        for connector_id in committed {
+            if let Some(monitor) = self.monitors.get_mut(&connector_id) {
+                if result.is_ok() {
+                    monitor.commit_succeeded();
+                    monitor.set_can_render(false); // Wait for the flip event
+                } else {
+                    monitor.commit_failed();
+                }
+            }
+        }
+        result
+            .map_err(|e| MonitorSetupError::DrmError(format!("Failed to commit cursors: {}", e)))?;
+        Ok(())
+    }
+
     /// Get the number of connected monitors
     pub fn monitor_count(&self) -> usize {
         self.monitors.len()
@@ -670,17 +1622,23 @@ impl<T> EasyDRM<T> {
     }
 
     fn handle_drm_events(&mut self) -> std::io::Result<()> {
+        let Some(device) = self.device.clone() else {
+            return Ok(());
+        };
         // Wait for events from DRM
-        for event in self.card.receive_events()? {
+        for event in device.card.receive_events()? {
             match event {
                 Event::PageFlip(page_flip_event) => {
                     // Find the monitor that completed the page flip
-                    // Set can_render = true for that monitor
+                    // Set can_render = true for that monitor and release replaced buffers
                     let crtc_handle = page_flip_event.crtc;
 
                     for monitor in self.monitors.values_mut() {
-                        if monitor.crtc().handle() == crtc_handle {
-                            monitor.set_can_render(true);
+                        if monitor
+                            .try_crtc()
+                            .is_some_and(|crtc| crtc.handle() == crtc_handle)
+                        {
+                            monitor.page_flip_complete();
                         }
                     }
                 }
@@ -0,0 +1,490 @@
+//! Generation of display modes the monitor doesn't advertise.
+//!
+//! [`ModeBuilder`] computes timings with the VESA Coordinated Video Timings (CVT 1.2,
+//! including reduced blanking v1 and v2) and Generalized Timing Formula (GTF) standards,
+//! and [`parse_modeline`] reads X11-style `Modeline` strings. The resulting
+//! `control::Mode` can be passed to [`Monitor::set_mode`](crate::Monitor::set_mode).
+//!
+//! ```ignore
+//! let mode = ModeBuilder::new(2560, 1080, 75.0)
+//!     .timing(ModeTiming::CvtReducedBlanking)
+//!     .build()?;
+//! monitor.set_mode(Some(mode));
+//!
+//! let mode = parse_modeline(
+//!     r#"Modeline "1920x1080_60.00" 173.00 1920 2048 2248 2576 1080 1083 1088 1120 -hsync +vsync"#,
+//! )?;
+//! ```
+
+use drm::control::{self, ModeFlags, ModeTypeFlags};
+use thiserror::Error;
+
+/// Errors that can occur while generating or parsing a mode
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ModeGenError {
+    #[error("invalid mode parameters: {0}")]
+    InvalidParameters(String),
+    #[error("invalid modeline: {0}")]
+    InvalidModeline(String),
+}
+
+/// Timing standard used by [`ModeBuilder`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ModeTiming {
+    /// VESA CVT with regular blanking, suitable for CRTs and most displays.
+    #[default]
+    Cvt,
+    /// VESA CVT reduced blanking v1, for digital displays that don't need CRT-style
+    /// blanking intervals. Lowers the pixel clock considerably.
+    CvtReducedBlanking,
+    /// VESA CVT reduced blanking v2, with an even shorter horizontal blanking interval.
+    CvtReducedBlankingV2,
+    /// VESA GTF, the predecessor of CVT, still required by some older displays.
+    Gtf,
+}
+
+/// Computes a `control::Mode` for an arbitrary resolution and refresh rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModeBuilder {
+    width: u16,
+    height: u16,
+    refresh: f64,
+    timing: ModeTiming,
+    interlaced: bool,
+}
+
+impl ModeBuilder {
+    /// Creates a builder for a progressive CVT mode.
+    pub fn new(width: u16, height: u16, refresh: f64) -> Self {
+        ModeBuilder {
+            width,
+            height,
+            refresh,
+            timing: ModeTiming::Cvt,
+            interlaced: false,
+        }
+    }
+
+    /// Selects the timing standard. Defaults to [`ModeTiming::Cvt`].
+    pub fn timing(mut self, timing: ModeTiming) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Generates an interlaced mode. Only supported by [`ModeTiming::Cvt`] and
+    /// [`ModeTiming::Gtf`].
+    ///
+    /// As with the kernel's `vrefresh`, the refresh rate is the field rate, so
+    /// `1920x1080` interlaced at 60 Hz draws 30 full frames per second.
+    pub fn interlaced(mut self, interlaced: bool) -> Self {
+        self.interlaced = interlaced;
+        self
+    }
+
+    /// Computes the mode timings.
+    pub fn build(&self) -> Result<control::Mode, ModeGenError> {
+        if self.width < 8 || self.height < 2 {
+            return Err(ModeGenError::InvalidParameters(format!(
+                "resolution {}x{} is too small",
+                self.width, self.height
+            )));
+        }
+        if !self.refresh.is_finite() || self.refresh <= 0.0 {
+            return Err(ModeGenError::InvalidParameters(format!(
+                "invalid refresh rate {}",
+                self.refresh
+            )));
+        }
+        if 1_000_000.0 / self.field().1 <= 550.0 {
+            return Err(ModeGenError::InvalidParameters(format!(
+                "refresh rate {} is too high",
+                self.refresh
+            )));
+        }
+        if self.interlaced
+            && matches!(
+                self.timing,
+                ModeTiming::CvtReducedBlanking | ModeTiming::CvtReducedBlankingV2
+            )
+        {
+            return Err(ModeGenError::InvalidParameters(
+                "reduced blanking timings can't be interlaced".to_string(),
+            ));
+        }
+
+        let timings = match self.timing {
+            ModeTiming::Cvt => self.cvt(),
+            ModeTiming::CvtReducedBlanking => self.cvt_reduced_blanking(),
+            ModeTiming::CvtReducedBlankingV2 => self.cvt_reduced_blanking_v2(),
+            ModeTiming::Gtf => self.gtf(),
+        };
+        let name = if self.interlaced {
+            format!("{}x{}i", self.width, self.height)
+        } else {
+            format!("{}x{}", self.width, self.height)
+        };
+        timings.into_mode(&name)
+    }
+
+    /// Lines per field and field rate
+    fn field(&self) -> (f64, f64) {
+        if self.interlaced {
+            ((self.height / 2) as f64, self.refresh)
+        } else {
+            (self.height as f64, self.refresh)
+        }
+    }
+
+    /// Half a line per field for interlaced modes
+    fn interlace(&self) -> f64 {
+        if self.interlaced { 0.5 } else { 0.0 }
+    }
+
+    /// Vertical porches and sync are doubled in frame lines for interlaced modes
+    fn field_scale(&self) -> u32 {
+        if self.interlaced { 2 } else { 1 }
+    }
+
+    /// CVT encodes the aspect ratio in the vertical sync width
+    fn cvt_vsync_width(hdisplay: u32, vdisplay: u32) -> u32 {
+        let is_aspect = |w: u32, h: u32| vdisplay.is_multiple_of(h) && vdisplay * w / h == hdisplay;
+        if is_aspect(4, 3) {
+            4
+        } else if is_aspect(16, 9) {
+            5
+        } else if is_aspect(16, 10) {
+            6
+        } else if is_aspect(5, 4) || is_aspect(15, 9) {
+            7
+        } else {
+            10
+        }
+    }
+
+    fn cvt(&self) -> Timings {
+        const MIN_VSYNC_BP: f64 = 550.0; // µs
+        const MIN_V_PORCH: u32 = 3;
+        const MIN_V_BPORCH: u32 = 6;
+        const C_PRIME: f64 = 30.0;
+        const M_PRIME: f64 = 300.0;
+        const H_SYNC_PERCENT: u32 = 8;
+        const CELL_GRAN: u32 = 8;
+        const CLOCK_STEP: u32 = 250; // kHz
+
+        let hdisplay = self.width as u32 / CELL_GRAN * CELL_GRAN;
+        let vdisplay = self.height as u32;
+        let (v_lines, field_rate) = self.field();
+        let vsync = Self::cvt_vsync_width(hdisplay, vdisplay);
+
+        let h_period = (1_000_000.0 / field_rate - MIN_VSYNC_BP)
+            / (v_lines + MIN_V_PORCH as f64 + self.interlace());
+        let vsync_bp = ((MIN_VSYNC_BP / h_period) as u32 + 1).max(vsync + MIN_V_BPORCH);
+        let field_total = v_lines + vsync_bp as f64 + self.interlace() + MIN_V_PORCH as f64;
+
+        let duty_cycle = (C_PRIME - M_PRIME * h_period / 1000.0).max(20.0);
+        let h_blank = (hdisplay as f64 * duty_cycle / (100.0 - duty_cycle)) as u32;
+        let h_blank = h_blank / (2 * CELL_GRAN) * (2 * CELL_GRAN);
+        let htotal = hdisplay + h_blank;
+        let hsync_width = htotal * H_SYNC_PERCENT / 100 / CELL_GRAN * CELL_GRAN;
+        let hsync_end = hdisplay + h_blank / 2;
+
+        let clock = (htotal as f64 * 1000.0 / h_period) as u32;
+        let scale = self.field_scale();
+        Timings {
+            clock: clock / CLOCK_STEP * CLOCK_STEP,
+            hdisplay,
+            hsync_start: hsync_end - hsync_width,
+            hsync_end,
+            htotal,
+            vdisplay,
+            vsync_start: vdisplay + MIN_V_PORCH * scale,
+            vsync_end: vdisplay + (MIN_V_PORCH + vsync) * scale,
+            vtotal: (field_total * scale as f64) as u32,
+            flags: ModeFlags::NHSYNC | ModeFlags::PVSYNC | self.interlace_flag(),
+        }
+    }
+
+    fn cvt_reduced_blanking(&self) -> Timings {
+        const MIN_V_BLANK: f64 = 460.0; // µs
+        const V_FPORCH: u32 = 3;
+        const MIN_V_BPORCH: u32 = 6;
+        const H_BLANK: u32 = 160;
+        const H_SYNC: u32 = 32;
+        const CELL_GRAN: u32 = 8;
+        const CLOCK_STEP: u32 = 250; // kHz
+
+        let hdisplay = self.width as u32 / CELL_GRAN * CELL_GRAN;
+        let vdisplay = self.height as u32;
+        let (v_lines, field_rate) = self.field();
+        let vsync = Self::cvt_vsync_width(hdisplay, vdisplay);
+
+        let h_period = (1_000_000.0 / field_rate - MIN_V_BLANK) / v_lines;
+        let vbi_lines = ((MIN_V_BLANK / h_period) as u32 + 1).max(V_FPORCH + vsync + MIN_V_BPORCH);
+        let vtotal = v_lines as u32 + vbi_lines;
+        let htotal = hdisplay + H_BLANK;
+        let hsync_end = hdisplay + H_BLANK / 2;
+
+        let clock = (field_rate * vtotal as f64 * htotal as f64 / 1000.0) as u32;
+        Timings {
+            clock: clock / CLOCK_STEP * CLOCK_STEP,
+            hdisplay,
+            hsync_start: hsync_end - H_SYNC,
+            hsync_end,
+            htotal,
+            vdisplay,
+            vsync_start: vdisplay + V_FPORCH,
+            vsync_end: vdisplay + V_FPORCH + vsync,
+            vtotal,
+            flags: ModeFlags::PHSYNC | ModeFlags::NVSYNC,
+        }
+    }
+
+    fn cvt_reduced_blanking_v2(&self) -> Timings {
+        const MIN_V_BLANK: f64 = 460.0; // µs
+        const MIN_V_FPORCH: u32 = 1;
+        const V_BPORCH: u32 = 6;
+        const V_SYNC: u32 = 8;
+        const H_BLANK: u32 = 80;
+        const H_SYNC: u32 = 32;
+        const H_FPORCH: u32 = 8;
+
+        let hdisplay = self.width as u32;
+        let vdisplay = self.height as u32;
+        let (v_lines, field_rate) = self.field();
+
+        let h_period = (1_000_000.0 / field_rate - MIN_V_BLANK) / v_lines;
+        let vbi_lines = ((MIN_V_BLANK / h_period) as u32 + 1).max(MIN_V_FPORCH + V_SYNC + V_BPORCH);
+        let vtotal = v_lines as u32 + vbi_lines;
+        let v_fporch = vbi_lines - V_SYNC - V_BPORCH;
+        let htotal = hdisplay + H_BLANK;
+
+        // The clock step is 1 kHz for reduced blanking v2
+        let clock = (field_rate * vtotal as f64 * htotal as f64 / 1000.0) as u32;
+        Timings {
+            clock,
+            hdisplay,
+            hsync_start: hdisplay + H_FPORCH,
+            hsync_end: hdisplay + H_FPORCH + H_SYNC,
+            htotal,
+            vdisplay,
+            vsync_start: vdisplay + v_fporch,
+            vsync_end: vdisplay + v_fporch + V_SYNC,
+            vtotal,
+            flags: ModeFlags::PHSYNC | ModeFlags::NVSYNC,
+        }
+    }
+
+    fn gtf(&self) -> Timings {
+        const MIN_VSYNC_BP: f64 = 550.0; // µs
+        const MIN_PORCH: u32 = 1;
+        const V_SYNC: u32 = 3;
+        const C_PRIME: f64 = 30.0;
+        const M_PRIME: f64 = 300.0;
+        const H_SYNC_PERCENT: f64 = 8.0;
+        const CELL_GRAN: f64 = 8.0;
+
+        let hdisplay = ((self.width as f64 / CELL_GRAN).round() * CELL_GRAN) as u32;
+        let vdisplay = self.height as u32;
+        let (v_lines, field_rate) = if self.interlaced {
+            ((self.height as f64 / 2.0).round(), self.refresh)
+        } else {
+            (self.height as f64, self.refresh)
+        };
+
+        let h_period_est = (1_000_000.0 / field_rate - MIN_VSYNC_BP)
+            / (v_lines + MIN_PORCH as f64 + self.interlace());
+        let vsync_bp = (MIN_VSYNC_BP / h_period_est).round();
+        let field_total = v_lines + vsync_bp + self.interlace() + MIN_PORCH as f64;
+        let field_rate_est = 1_000_000.0 / h_period_est / field_total;
+        let h_period = h_period_est / (field_rate / field_rate_est);
+
+        let duty_cycle = C_PRIME - M_PRIME * h_period / 1000.0;
+        let h_blank = (hdisplay as f64 * duty_cycle / (100.0 - duty_cycle) / (2.0 * CELL_GRAN))
+            .round()
+            * (2.0 * CELL_GRAN);
+        let htotal = hdisplay + h_blank as u32;
+        let hsync_width =
+            ((H_SYNC_PERCENT / 100.0 * htotal as f64 / CELL_GRAN).round() * CELL_GRAN) as u32;
+        let hsync_start = hdisplay + h_blank as u32 / 2 - hsync_width;
+
+        let scale = self.field_scale();
+        Timings {
+            clock: (htotal as f64 / h_period * 1000.0).round() as u32,
+            hdisplay,
+            hsync_start,
+            hsync_end: hsync_start + hsync_width,
+            htotal,
+            vdisplay,
+            vsync_start: vdisplay + MIN_PORCH * scale,
+            vsync_end: vdisplay + (MIN_PORCH + V_SYNC) * scale,
+            vtotal: (field_total * scale as f64) as u32,
+            flags: ModeFlags::NHSYNC | ModeFlags::PVSYNC | self.interlace_flag(),
+        }
+    }
+
+    fn interlace_flag(&self) -> ModeFlags {
+        if self.interlaced {
+            ModeFlags::INTERLACE
+        } else {
+            ModeFlags::empty()
+        }
+    }
+}
+
+/// Parses an X11-style modeline into a mode.
+///
+/// The leading `Modeline` keyword and the quoted name are optional:
+///
+/// ```text
+/// Modeline "1920x1080_60.00"  173.00  1920 2048 2248 2576  1080 1083 1088 1120 -hsync +vsync
+/// 148.50 1920 2008 2052 2200 1080 1084 1089 1125 +hsync +vsync
+/// ```
+///
+/// The pixel clock is in MHz. Supported flags are `+hsync`, `-hsync`, `+vsync`, `-vsync`,
+/// `+csync`, `-csync`, `composite`, `interlace` and `doublescan`.
+pub fn parse_modeline(modeline: &str) -> Result<control::Mode, ModeGenError> {
+    let error = |reason: &str| ModeGenError::InvalidModeline(format!("{reason} in {modeline:?}"));
+
+    let mut rest = modeline.trim();
+    if rest
+        .get(..8)
+        .is_some_and(|keyword| keyword.eq_ignore_ascii_case("modeline"))
+    {
+        rest = rest[8..].trim_start();
+    }
+    let mut name = None;
+    if let Some(quoted) = rest.strip_prefix('"') {
+        let (quoted_name, after) = quoted
+            .split_once('"')
+            .ok_or_else(|| error("unterminated name"))?;
+        name = Some(quoted_name.to_string());
+        rest = after;
+    }
+
+    let mut tokens = rest.split_whitespace();
+    let clock: f64 = tokens
+        .next()
+        .and_then(|clock| clock.parse().ok())
+        .filter(|clock: &f64| clock.is_finite() && *clock > 0.0)
+        .ok_or_else(|| error("missing or invalid pixel clock"))?;
+    let mut numbers = [0u32; 8];
+    for number in &mut numbers {
+        *number = tokens
+            .next()
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(|| error("expected 8 timing values"))?;
+    }
+    let [
+        hdisplay,
+        hsync_start,
+        hsync_end,
+        htotal,
+        vdisplay,
+        vsync_start,
+        vsync_end,
+        vtotal,
+    ] = numbers;
+
+    let mut flags = ModeFlags::empty();
+    for flag in tokens {
+        flags |= match flag.to_ascii_lowercase().as_str() {
+            "+hsync" => ModeFlags::PHSYNC,
+            "-hsync" => ModeFlags::NHSYNC,
+            "+vsync" => ModeFlags::PVSYNC,
+            "-vsync" => ModeFlags::NVSYNC,
+            "+csync" => ModeFlags::CSYNC | ModeFlags::PCSYNC,
+            "-csync" => ModeFlags::CSYNC | ModeFlags::NCSYNC,
+            "composite" => ModeFlags::CSYNC,
+            "interlace" => ModeFlags::INTERLACE,
+            "doublescan" => ModeFlags::DBLSCAN,
+            _ => return Err(error(&format!("unknown flag {flag:?}"))),
+        };
+    }
+
+    let increasing = |values: [u32; 4]| values[0] > 0 && values.is_sorted();
+    if !increasing([hdisplay, hsync_start, hsync_end, htotal])
+        || !increasing([vdisplay, vsync_start, vsync_end, vtotal])
+    {
+        return Err(error("timings are not in increasing order"));
+    }
+
+    let name = name.unwrap_or_else(|| format!("{hdisplay}x{vdisplay}"));
+    Timings {
+        clock: (clock * 1000.0).round() as u32,
+        hdisplay,
+        hsync_start,
+        hsync_end,
+        htotal,
+        vdisplay,
+        vsync_start,
+        vsync_end,
+        vtotal,
+        flags,
+    }
+    .into_mode(&name)
+    .map_err(|e| error(&e.to_string()))
+}
+
+/// Mode timings before conversion to the kernel's `drm_mode_modeinfo`
+struct Timings {
+    /// Pixel clock in kHz
+    clock: u32,
+    hdisplay: u32,
+    hsync_start: u32,
+    hsync_end: u32,
+    htotal: u32,
+    vdisplay: u32,
+    vsync_start: u32,
+    vsync_end: u32,
+    vtotal: u32,
+    flags: ModeFlags,
+}
+
+impl Timings {
+    fn into_mode(self, name: &str) -> Result<control::Mode, ModeGenError> {
+        let to_u16 = |value: u32| {
+            u16::try_from(value).map_err(|_| {
+                ModeGenError::InvalidParameters(format!("timing value {value} is out of range"))
+            })
+        };
+
+        // Same as the kernel's drm_mode_vrefresh()
+        let mut refresh_den = self.htotal as u64 * self.vtotal as u64;
+        let mut refresh_num = self.clock as u64 * 1000;
+        if self.flags.contains(ModeFlags::INTERLACE) {
+            refresh_num *= 2;
+        }
+        if self.flags.contains(ModeFlags::DBLSCAN) {
+            refresh_den *= 2;
+        }
+        if refresh_den == 0 {
+            return Err(ModeGenError::InvalidParameters(
+                "total size is zero".to_string(),
+            ));
+        }
+
+        let mut raw_name = [0 as std::ffi::c_char; 32];
+        for (dst, src) in raw_name.iter_mut().zip(name.bytes().take(31)) {
+            *dst = src as std::ffi::c_char;
+        }
+
+        Ok(drm_sys::drm_mode_modeinfo {
+            clock: self.clock,
+            hdisplay: to_u16(self.hdisplay)?,
+            hsync_start: to_u16(self.hsync_start)?,
+            hsync_end: to_u16(self.hsync_end)?,
+            htotal: to_u16(self.htotal)?,
+            hskew: 0,
+            vdisplay: to_u16(self.vdisplay)?,
+            vsync_start: to_u16(self.vsync_start)?,
+            vsync_end: to_u16(self.vsync_end)?,
+            vtotal: to_u16(self.vtotal)?,
+            vscan: 0,
+            vrefresh: ((refresh_num + refresh_den / 2) / refresh_den) as u32,
+            flags: self.flags.bits(),
+            type_: ModeTypeFlags::USERDEF.bits(),
+            name: raw_name,
+        }
+        .into())
+    }
+}
@@ -1,16 +1,126 @@
+use std::any::Any;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+use std::sync::Arc;
+#[cfg(feature = "gbm")]
+use std::time::Duration;
 use std::{collections::HashMap, hash::Hash};
 
-use drm::control::{self, atomic::AtomicModeReq, connector, crtc, plane, property};
+#[cfg(feature = "gbm")]
+use drm::buffer::{DrmFourcc, DrmModifier};
+use drm::control::{
+    self, Device as ControlDevice, atomic::AtomicModeReq, connector, crtc, plane, property,
+};
+#[cfg(feature = "gbm")]
+use drm::control::{AtomicCommitFlags, PlaneType};
 use thiserror::Error;
 
-use crate::MonitorContextCreationRequest;
+use crate::capture::{CaptureError, CapturedFrame};
+use crate::cmdline::{ForcedState, KernelModeHints, VideoModeHint};
+use crate::config::{ConfigIssue, Rotation};
+use crate::cursor::{Cursor, CursorBackend, CursorError};
+use crate::device::GpuDevice;
+#[cfg(feature = "gbm")]
+use crate::dmabuf::DmaBufDescriptor;
+use crate::dumb_buffer::{DumbBufferError, DumbBuffers, Pixels};
+use crate::edid::MonitorInfo;
+#[cfg(feature = "gbm")]
+use crate::export::{ExportedFrame, FrameExporter, Released};
+#[cfg(feature = "gbm")]
+use crate::formats::add_scanout_framebuffer;
+use crate::formats::{PlaneFormats, ScanoutFormat};
+#[cfg(feature = "gbm")]
+use crate::gl_config::GlConfig;
+#[cfg(feature = "gbm")]
+use crate::gl_debug::{GlDebugLogger, GlDebugOutput};
+#[cfg(feature = "gbm")]
 use crate::gles_context::{GlesContext, GlesContextError};
+#[cfg(feature = "gbm")]
+use crate::interop::{ExternalImage, ExternalSemaphore, InteropError, VulkanImageDescriptor};
+#[cfg(feature = "gbm")]
+use crate::layer::{Layer, LayerError};
+use crate::modegen::{ModeBuilder, ModeGenError};
+use crate::monitor_id::MonitorId;
+use crate::output::{
+    Subconnector, connector_name, is_internal_interface, read_connector_blob, read_subconnector,
+    select_digital_subconnector, supported_rotations,
+};
+#[cfg(feature = "gbm")]
+use crate::render_backend::GbmEglBackend;
+use crate::render_backend::{
+    BackendFrame, RenderBackend, RenderBackendError, RenderBackendKind, RenderBackendRequest,
+};
+#[cfg(feature = "gbm")]
+use crate::render_thread::{self, FrameWaker, RenderThread, RenderThreadError};
+use crate::share_group::ShareGroup;
+#[cfg(feature = "gbm")]
+use crate::texture::{Texture, TextureError};
+use crate::virtual_output::VirtualOutput;
+#[cfg(feature = "vulkan")]
+use crate::vulkan::VulkanBackend;
+#[cfg(feature = "gbm")]
+use crate::writeback::{WritebackError, WritebackJob};
+use crate::{EasyDRMOptions, MonitorContextCreationRequest};
 
 /// DRM resources dedicated to a monitor instance.
 pub(crate) struct MonitorResourceAllocation {
     pub crtc_info: crtc::Info,
     pub primary_plane: plane::Handle,
     pub cursor_plane: Option<plane::Handle>,
+    /// `cursor_plane` is an overlay plane standing in for a missing cursor plane
+    pub cursor_on_overlay: bool,
+}
+
+/// Returns the OpenGL ES context of `backend`, if it's the GBM+EGL backend.
+#[cfg(feature = "gbm")]
+fn gles_context_of(backend: &dyn RenderBackend) -> Option<&GlesContext> {
+    (backend as &dyn Any)
+        .downcast_ref::<GbmEglBackend>()
+        .map(GbmEglBackend::gles_context)
+}
+
+/// Forwards the GL debug messages of `backend`'s context to `output`, if it's the
+/// GBM+EGL backend and a debug logger is set.
+#[cfg(feature = "gbm")]
+pub(crate) fn enable_gl_debug_output(
+    backend: &mut dyn RenderBackend,
+    output: Option<GlDebugOutput>,
+) -> Result<(), GlesContextError> {
+    let Some(output) = output else {
+        return Ok(());
+    };
+    match (backend as &mut dyn Any).downcast_mut::<GbmEglBackend>() {
+        Some(backend) => backend.gles_context_mut().enable_debug_output(output),
+        None => Ok(()),
+    }
+}
+
+/// Calls the user's context constructor for a monitor rendering with `backend`.
+fn create_user_context<T, F>(
+    backend: &dyn RenderBackend,
+    width: u16,
+    height: u16,
+    constructor: F,
+) -> T
+where
+    F: for<'a> FnOnce(&MonitorContextCreationRequest<'a>) -> T,
+{
+    #[cfg(feature = "gbm")]
+    if let Some(gles_context) = gles_context_of(backend) {
+        return constructor(&MonitorContextCreationRequest {
+            gl: Some(gles_context.gl()),
+            width: width as _,
+            height: height as _,
+            get_proc_address: Some(&|symbol| gles_context.get_proc_address(symbol)),
+            render_backend: backend,
+        });
+    }
+    constructor(&MonitorContextCreationRequest {
+        gl: None,
+        width: width as _,
+        height: height as _,
+        get_proc_address: None,
+        render_backend: backend,
+    })
 }
 
 /// Represents a connected display monitor with its own OpenGL ES rendering context.
@@ -18,7 +128,8 @@ pub(crate) struct MonitorResourceAllocation {
 /// Each monitor manages:
 /// - DRM resources (connector, CRTC, planes)
 /// - Display mode configuration with 3-state tracking
-/// - Independent OpenGL ES context
+/// - A [`RenderBackend`]: an independent OpenGL ES context by default
+/// - Overlay [`Layer`]s on top of the primary plane
 /// - Render state tracking
 ///
 /// # Display Modes (3-State System)
@@ -45,20 +156,49 @@ pub(crate) struct MonitorResourceAllocation {
 /// // EasyDRM::swap_buffers() will orchestrate the monitor swaps
 /// ```
 pub struct Monitor<T> {
+    /// `None` for virtual monitors of a headless `EasyDRM`
+    device: Option<Arc<GpuDevice>>,
     connector_id: connector::Handle,
-    current_crtc: crtc::Info,
+    connector_name: String,
+    interface: connector::Interface,
+    subconnector: Option<Subconnector>,
+    current_crtc: Option<crtc::Info>,
     default_mode: control::Mode,
     requested_mode: Option<control::Mode>,
     current_mode: Option<control::Mode>,
-    primary_plane_id: plane::Handle,
+    primary_plane_id: Option<plane::Handle>,
+    plane_formats: Option<PlaneFormats>,
     cursor_plane_id: Option<plane::Handle>,
-    gles_context: GlesContext,
+    cursor: Cursor,
+    #[cfg(feature = "gbm")]
+    layers: Vec<Layer>,
+    /// Config and share group of the monitor's context, which its layers' contexts use too
+    #[cfg(feature = "gbm")]
+    gl_config: GlConfig,
+    #[cfg(feature = "gbm")]
+    share_group: Option<Arc<ShareGroup>>,
+    #[cfg(feature = "gbm")]
+    gl_debug_logger: Option<GlDebugLogger>,
+    info: Option<MonitorInfo>,
+    id: MonitorId,
+    rotation: Rotation,
+    position: Option<(i32, i32)>,
+    config_issues: Vec<ConfigIssue>,
+    backend: Box<dyn RenderBackend>,
     can_render: bool,
     was_drawn: bool,
     // DRM state tracking
+    #[cfg(feature = "gbm")]
     previous_bo: Option<gbm::BufferObject<()>>,
-    previous_fence_fd: Option<i32>,
-    previous_sync: Option<*mut std::ffi::c_void>,
+    /// Last frame of a headless monitor, read back from its pbuffer
+    offscreen_frame: Option<CapturedFrame>,
+    #[cfg(feature = "gbm")]
+    frame_exporter: FrameExporter,
+    #[cfg(feature = "gbm")]
+    writeback: Option<WritebackJob>,
+    virtual_output: Option<VirtualOutput>,
+    /// Fence of the last frame, kept open until the commit using it went through
+    previous_fence: Option<OwnedFd>,
     connector_properties: HashMap<String, property::Info>,
     crtc_properties: HashMap<String, property::Info>,
     plane_properties: HashMap<String, property::Info>,
@@ -94,48 +234,83 @@ pub enum MonitorSetupError {
     NoModesFound,
     #[error("no primary plane found for this monitor")]
     NoPrimaryPlaneFound,
+    #[cfg(feature = "gbm")]
     #[error("failed to create OpenGL ES context: {0}")]
     GlesContextError(#[from] GlesContextError),
     #[error("DRM error: {0}")]
     DrmError(String),
+    #[error("monitor is disabled by the display config")]
+    DisabledByConfig,
+    #[error("monitor is disabled by the kernel command line")]
+    DisabledByKernelCmdline,
+    #[error("invalid mode: {0}")]
+    InvalidMode(#[from] ModeGenError),
+    #[error("render backend error: {0}")]
+    RenderBackend(#[from] RenderBackendError),
 }
 
 impl<T> Monitor<T> {
+    #[cfg_attr(not(feature = "gbm"), allow(unused_variables))]
     pub(crate) fn setup<F>(
-        card: &impl control::Device,
-        gbm_device: &gbm::Device<std::fs::File>,
+        device: &Arc<GpuDevice>,
         connector_id: connector::Handle,
         allocation: MonitorResourceAllocation,
+        options: &EasyDRMOptions,
+        share_group: Option<&Arc<ShareGroup>>,
+        kernel_mode_hints: &KernelModeHints,
         context_constructor: F,
     ) -> Result<Self, MonitorSetupError>
     where
         F: for<'a> FnOnce(&MonitorContextCreationRequest<'a>) -> T,
     {
+        let card = &device.card;
         let connector = card.get_connector(connector_id, true)?;
         let MonitorResourceAllocation {
             crtc_info,
             primary_plane,
             cursor_plane,
+            cursor_on_overlay,
         } = allocation;
-        // Get the optimal/preferred mode (highest resolution + refresh rate)
-        let default_mode = connector
-            .modes()
-            .first()
-            .cloned()
-            .ok_or(MonitorSetupError::NoModesFound)?;
-
-        // Create the OpenGL ES context for this monitor
-        let gles_context = GlesContext::new(gbm_device, &default_mode)?;
-
-        // Initialize user context with access to GL bindings
-        let get_proc_address = |symbol: &str| gles_context.get_proc_address(symbol);
-        let request = MonitorContextCreationRequest {
-            gl: gles_context.gl(),
-            width: default_mode.size().0 as _,
-            height: default_mode.size().1 as _,
-            get_proc_address: &get_proc_address,
+        let info = read_connector_blob(card, connector_id, "EDID")
+            .and_then(|edid| MonitorInfo::from_edid(&edid).ok());
+        let connector_name = connector_name(&connector);
+
+        // Get the optimal/preferred mode (highest resolution + refresh rate), unless the
+        // kernel command line asks for a specific one (`video=HDMI-A-1:1280x720@60`)
+        let kernel_hint = kernel_mode_hints.for_connector(&connector_name);
+        let preferred_mode = match connector.modes().first() {
+            Some(&mode) => mode,
+            // A connector forced on with `e`/`D` may not report any modes, the kernel
+            // falls back to 1024x768 for those
+            None if kernel_hint.is_some_and(VideoModeHint::forces_on) => {
+                ModeBuilder::new(1024, 768, 60.0).build()?
+            }
+            None => return Err(MonitorSetupError::NoModesFound),
+        };
+        let default_mode = match kernel_hint.filter(|hint| hint.size.is_some()) {
+            Some(hint) => hint
+                .find_mode(connector.modes())
+                .or_else(|| hint.generate_mode())
+                .unwrap_or_else(|| {
+                    eprintln!(
+                        "Warning: Invalid mode on the kernel command line for {}, using the preferred mode",
+                        connector_name
+                    );
+                    preferred_mode
+                }),
+            None => preferred_mode,
         };
-        let user_context = context_constructor(&request);
+        let id = MonitorId::from_info(info.as_ref(), &connector_name);
+        if kernel_hint.is_some_and(|hint| hint.forced == Some(ForcedState::OnDigital))
+            && connector.interface() == connector::Interface::DVII
+            && !select_digital_subconnector(card, connector_id)
+        {
+            eprintln!(
+                "Warning: Failed to select the digital signal of {} from the kernel command line",
+                connector_name
+            );
+        }
+        let subconnector = read_subconnector(card, connector_id);
 
         // Cache DRM properties for atomic commits
         let connector_properties = card
@@ -159,25 +334,262 @@ impl<T> Monitor<T> {
                 MonitorSetupError::DrmError(format!("Failed to get plane properties: {}", e))
             })?;
 
-        Ok(Monitor {
+        // Apply the matching display config rule, if any
+        let rule = options
+            .display_config
+            .as_ref()
+            .and_then(|config| config.rule_for(&id, info.as_ref()));
+        let mut config_issues = Vec::new();
+        let mut requested_mode = None;
+        let mut rotation = Rotation::Normal;
+        // Many primary planes only flip by 180°, so the requested bit must be listed
+        let supported_rotations = supported_rotations(card, &plane_properties);
+        let can_rotate = |wanted: Rotation| {
+            wanted == Rotation::Normal
+                || supported_rotations.is_some_and(|bits| bits & wanted.drm_bits() != 0)
+        };
+        let mut position = None;
+        if let Some(hint) = kernel_hint {
+            if hint.forced == Some(ForcedState::Off)
+                && rule.is_none_or(|rule| rule.enabled != Some(true))
+            {
+                return Err(MonitorSetupError::DisabledByKernelCmdline);
+            }
+            match hint.rotation {
+                Some(wanted) if can_rotate(wanted) => rotation = wanted,
+                Some(wanted) => eprintln!(
+                    "Warning: Rotation of {}° from the kernel command line is not supported by {}",
+                    wanted.degrees(),
+                    connector_name
+                ),
+                None => {}
+            }
+        }
+        if let Some(rule) = rule {
+            if rule.enabled == Some(false) {
+                return Err(MonitorSetupError::DisabledByConfig);
+            }
+            if let Some(spec) = rule.mode_spec(&default_mode) {
+                requested_mode = spec.find_in(connector.modes());
+                if requested_mode.is_none() {
+                    config_issues.push(ConfigIssue::ModeNotAvailable(spec));
+                }
+            }
+            // An explicit `rotation = 0` also overrides a kernel `rotate=`
+            match rule.rotation {
+                Some(wanted) if can_rotate(wanted) => rotation = wanted,
+                Some(wanted) => config_issues.push(ConfigIssue::RotationNotSupported(wanted)),
+                None => {}
+            }
+            position = rule.position;
+        }
+
+        // Create the buffers for this monitor, sized for the mode we're going to set (with
+        // swapped dimensions if the plane rotates the framebuffer by 90°/270°)
+        let (mode_width, mode_height) = requested_mode.unwrap_or(default_mode).size();
+        let (width, height) = if rotation.is_transposed() {
+            (mode_height, mode_width)
+        } else {
+            (mode_width, mode_height)
+        };
+        let plane_formats = PlaneFormats::read(card, primary_plane, &plane_properties)
+            .ok()
+            .flatten();
+        #[cfg(feature = "gbm")]
+        let gl_config = options.gl_config_for(&id, info.as_ref());
+        #[cfg_attr(not(feature = "gbm"), allow(unused_mut))]
+        let mut backend: Box<dyn RenderBackend> = match &options.render_backend {
+            #[cfg(feature = "gbm")]
+            RenderBackendKind::GbmEgl => {
+                let test = ScanoutTest {
+                    connector_id,
+                    connector_name: &connector_name,
+                    connector_properties: &connector_properties,
+                    crtc: crtc_info.handle(),
+                    crtc_properties: &crtc_properties,
+                    plane: primary_plane,
+                    plane_properties: &plane_properties,
+                    mode: requested_mode.unwrap_or(default_mode),
+                    rotation,
+                };
+                let (gles_context, scanout_format) = test.negotiate(
+                    card,
+                    device.gbm()?,
+                    plane_formats.as_ref(),
+                    &gl_config,
+                    share_group.map(Arc::as_ref),
+                    (width, height),
+                )?;
+                Box::new(GbmEglBackend::new(
+                    Arc::clone(device),
+                    gles_context,
+                    scanout_format,
+                ))
+            }
+            RenderBackendKind::DumbBuffers => {
+                Box::new(DumbBuffers::new(Arc::clone(device), width, height)?)
+            }
+            #[cfg(feature = "vulkan")]
+            RenderBackendKind::Vulkan(vulkan) => Box::new(VulkanBackend::new(
+                Arc::clone(device),
+                vulkan,
+                width,
+                height,
+                plane_formats.as_ref(),
+            )?),
+            RenderBackendKind::Custom(factory) => factory(&RenderBackendRequest {
+                drm_fd: card.as_fd(),
+                connector: connector_id,
+                width,
+                height,
+                plane_formats: plane_formats.as_ref(),
+            })?,
+        };
+
+        #[cfg(feature = "gbm")]
+        enable_gl_debug_output(
+            backend.as_mut(),
+            options.gl_debug_output_for(connector_id, &connector_name),
+        )?;
+
+        let cursor = Cursor::new(device, cursor_plane, cursor_on_overlay);
+
+        // Initialize user context with access to GL bindings
+        let user_context =
+            create_user_context(backend.as_ref(), width, height, context_constructor);
+
+        // Overlays are shared with layers, so other monitors must not claim this one
+        if let (CursorBackend::OverlayPlane, Some(plane)) = (cursor.backend(), cursor_plane) {
+            device.claim_plane(plane);
+        }
+
+        // Only touch the CRTC's gamma once nothing can fail anymore, so a monitor that
+        // failed to set up doesn't keep the modified ramps
+        if let Some(color) = rule.and_then(|rule| rule.color.as_ref()) {
+            let [red, green, blue] = color.gamma_ramps(crtc_info.gamma_length() as usize);
+            if let Err(e) = card.set_gamma(crtc_info.handle(), &red, &green, &blue) {
+                config_issues.push(ConfigIssue::ColorNotApplied(e.to_string()));
+            }
+        }
+        for issue in &config_issues {
+            eprintln!("Warning: Display config for {}: {}", id, issue);
+        }
+
+        let mut monitor = Monitor {
+            device: Some(Arc::clone(device)),
             connector_id,
-            current_crtc: crtc_info,
+            connector_name,
+            interface: connector.interface(),
+            subconnector,
+            current_crtc: Some(crtc_info),
             default_mode,
-            requested_mode: None, // Use default mode initially
+            requested_mode: None, // `None` uses the default mode
             current_mode: None,   // No mode set in hardware yet
-            primary_plane_id: primary_plane,
+            primary_plane_id: Some(primary_plane),
+            plane_formats,
             cursor_plane_id: cursor_plane,
-            gles_context,
+            cursor,
+            #[cfg(feature = "gbm")]
+            layers: Vec::new(),
+            #[cfg(feature = "gbm")]
+            gl_config,
+            #[cfg(feature = "gbm")]
+            share_group: share_group.cloned(),
+            #[cfg(feature = "gbm")]
+            gl_debug_logger: options.gl_debug_logger.clone(),
+            info,
+            id,
+            rotation,
+            position,
+            config_issues,
+            backend,
             can_render: true, // Initially ready to render
             was_drawn: false,
+            #[cfg(feature = "gbm")]
             previous_bo: None,
-            previous_fence_fd: None,
-            previous_sync: None,
+            offscreen_frame: None,
+            #[cfg(feature = "gbm")]
+            frame_exporter: FrameExporter::new(),
+            #[cfg(feature = "gbm")]
+            writeback: None,
+            virtual_output: None,
+            previous_fence: None,
             connector_properties,
             crtc_properties,
             plane_properties,
             first_frame: true,
             user_context,
+        };
+        // The config rule's mode is requested like one chosen by the application
+        monitor.set_mode(requested_mode);
+        Ok(monitor)
+    }
+
+    /// Sets up a monitor that renders offscreen at `mode` with `backend`, with a
+    /// vblank simulated at `refresh` Hz.
+    ///
+    /// `device` is `None` for headless monitors, whose context renders to a pbuffer.
+    pub(crate) fn setup_virtual<F>(
+        device: Option<&Arc<GpuDevice>>,
+        backend: Box<dyn RenderBackend>,
+        connector_id: connector::Handle,
+        connector_name: String,
+        mode: control::Mode,
+        refresh: f64,
+        context_constructor: F,
+    ) -> Result<Self, MonitorSetupError>
+    where
+        F: for<'a> FnOnce(&MonitorContextCreationRequest<'a>) -> T,
+    {
+        let virtual_output = VirtualOutput::new(refresh)?;
+        let (width, height) = mode.size();
+        let user_context =
+            create_user_context(backend.as_ref(), width, height, context_constructor);
+
+        Ok(Monitor {
+            device: device.cloned(),
+            connector_id,
+            id: MonitorId::new(None, connector_name.clone()),
+            connector_name,
+            interface: connector::Interface::Virtual,
+            subconnector: None,
+            current_crtc: None,
+            default_mode: mode,
+            requested_mode: None,
+            current_mode: None,
+            primary_plane_id: None,
+            plane_formats: None,
+            cursor_plane_id: None,
+            cursor: Cursor::composited(),
+            #[cfg(feature = "gbm")]
+            layers: Vec::new(),
+            #[cfg(feature = "gbm")]
+            gl_config: GlConfig::default(),
+            #[cfg(feature = "gbm")]
+            share_group: None,
+            #[cfg(feature = "gbm")]
+            gl_debug_logger: None,
+            info: None,
+            rotation: Rotation::Normal,
+            position: None,
+            config_issues: Vec::new(),
+            backend,
+            can_render: true,
+            was_drawn: false,
+            #[cfg(feature = "gbm")]
+            previous_bo: None,
+            offscreen_frame: None,
+            #[cfg(feature = "gbm")]
+            frame_exporter: FrameExporter::new(),
+            #[cfg(feature = "gbm")]
+            writeback: None,
+            virtual_output: Some(virtual_output),
+            previous_fence: None,
+            connector_properties: HashMap::new(),
+            crtc_properties: HashMap::new(),
+            plane_properties: HashMap::new(),
+            first_frame: true,
+            user_context,
         })
     }
 
@@ -199,11 +611,12 @@ impl<T> Monitor<T> {
         self.can_render
     }
 
-    /// Internal flag indicating if this monitor was drawn to this frame.
+    /// Internal flag indicating if this monitor was drawn to this frame, or if its render
+    /// thread finished a frame.
     ///
     /// Used by `EasyDRM::swap_buffers()` to determine which monitors need presentation.
-    pub(crate) fn was_drawn(&self) -> bool {
-        self.was_drawn
+    pub(crate) fn was_drawn(&mut self) -> bool {
+        self.was_drawn || (self.can_render && self.backend.frame_ready())
     }
 
     /// Sets the can_render flag (used by poll_events).
@@ -211,6 +624,46 @@ impl<T> Monitor<T> {
         self.can_render = value;
     }
 
+    /// Called by poll_events when the last commit reached the screen: the monitor can
+    /// render again and the layers release the buffers it replaced.
+    pub(crate) fn page_flip_complete(&mut self) {
+        self.can_render = true;
+        self.backend.page_flip_complete();
+        self.cursor.page_flip_complete();
+        #[cfg(feature = "gbm")]
+        for layer in &mut self.layers {
+            layer.page_flip_complete();
+        }
+        #[cfg(feature = "gbm")]
+        if let Some(writeback) = &mut self.writeback {
+            writeback.page_flip_complete();
+        }
+    }
+
+    /// Moves the OpenGL ES context to a [`RenderThread`], the monitor then presents the
+    /// frames the thread sends (used by `EasyDRM::take_render_thread()`).
+    #[cfg(feature = "gbm")]
+    pub(crate) fn detach_render_thread(
+        &mut self,
+        waker: Arc<FrameWaker>,
+    ) -> Result<RenderThread, RenderThreadError> {
+        // Release the context before handing it over, the monitor has no GL afterwards
+        gles_context_of(self.backend.as_ref())
+            .ok_or(RenderThreadError::Unsupported)?
+            .release_current()?;
+        let (threaded, channels) =
+            render_thread::channel(self.backend.scanout_format(), self.can_render);
+        let backend = std::mem::replace(&mut self.backend, Box::new(threaded));
+        let backend = (backend as Box<dyn Any>)
+            .downcast::<GbmEglBackend>()
+            .expect("the backend was checked to be GbmEglBackend");
+        let thread =
+            RenderThread::new(self.connector_id, backend, channels, self.can_render, waker);
+        self.offscreen_frame = None;
+        self.was_drawn = false;
+        Ok(thread)
+    }
+
     /// Resets the was_drawn flag for the next frame (used by EasyDRM::swap_buffers).
     pub(crate) fn reset_drawn_flag(&mut self) {
         self.was_drawn = false;
@@ -223,7 +676,8 @@ impl<T> Monitor<T> {
     ///
     /// # Errors
     ///
-    /// Returns an error if the EGL context cannot be made current.
+    /// Returns an error if the EGL context cannot be made current, or
+    /// [`GlesContextError::NoContext`] if the monitor uses another render backend.
     ///
     /// # Example
     ///
@@ -234,12 +688,244 @@ impl<T> Monitor<T> {
     ///     gl::Clear(gl::COLOR_BUFFER_BIT);
     /// }
     /// ```
+    #[cfg(feature = "gbm")]
     pub fn make_current(&mut self) -> Result<(), GlesContextError> {
-        self.gles_context.make_current()?;
+        self.gles_context()?.make_current()?;
         self.was_drawn = true;
         Ok(())
     }
 
+    /// Maps the back buffer of a monitor in CPU rendering mode (see
+    /// `EasyDRMOptions::cpu_rendering()`) and marks the monitor as drawn.
+    ///
+    /// The buffer holds whatever was drawn two frames ago, so redraw all of it. It's
+    /// presented by the next `EasyDRM::swap_buffers()`. The cursor is only shown if it's
+    /// on a plane, composited cursors aren't drawn into dumb buffers.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DumbBufferError::NotCpuRendered`] if the monitor renders with OpenGL ES.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// if monitor.can_render() {
+    ///     let mut pixels = monitor.pixels_mut()?;
+    ///     for y in 0..pixels.height() {
+    ///         pixels.row_mut(y).unwrap().fill(0x0000_00ff); // blue
+    ///     }
+    /// }
+    /// easydrm.swap_buffers()?;
+    /// ```
+    pub fn pixels_mut(&mut self) -> Result<Pixels<'_>, DumbBufferError> {
+        let Some(dumb_buffers) =
+            (self.backend.as_mut() as &mut dyn Any).downcast_mut::<DumbBuffers>()
+        else {
+            return Err(DumbBufferError::NotCpuRendered);
+        };
+        let pixels = dumb_buffers.map_back()?;
+        self.was_drawn = true;
+        Ok(pixels)
+    }
+
+    /// Returns true if this monitor renders to dumb buffers with the CPU instead of
+    /// OpenGL ES.
+    pub fn is_cpu_rendered(&self) -> bool {
+        (self.backend.as_ref() as &dyn Any).is::<DumbBuffers>()
+    }
+
+    /// Returns the Vulkan backend of this monitor, to render its next frame with. `None`
+    /// unless the monitors render with [`RenderBackendKind::Vulkan`].
+    #[cfg(feature = "vulkan")]
+    pub fn vulkan(&self) -> Option<&VulkanBackend> {
+        (self.backend.as_ref() as &dyn Any).downcast_ref()
+    }
+
+    /// Returns the backend producing this monitor's frames, see
+    /// [`EasyDRMOptions::render_backend`](crate::EasyDRMOptions::render_backend).
+    pub fn render_backend(&self) -> &dyn RenderBackend {
+        self.backend.as_ref()
+    }
+
+    /// Returns the backend producing this monitor's frames, e.g. to downcast a custom
+    /// backend to its type and render with it.
+    ///
+    /// ```ignore
+    /// let backend = (monitor.render_backend_mut() as &mut dyn Any)
+    ///     .downcast_mut::<MyVulkanBackend>()
+    ///     .unwrap();
+    /// backend.record_frame(&scene)?;
+    /// monitor.mark_drawn();
+    /// ```
+    pub fn render_backend_mut(&mut self) -> &mut dyn RenderBackend {
+        self.backend.as_mut()
+    }
+
+    /// Marks the monitor as drawn, so the next `EasyDRM::swap_buffers()` presents it.
+    ///
+    /// [`make_current`](Self::make_current) and [`pixels_mut`](Self::pixels_mut) do this
+    /// already, it's meant for custom render backends.
+    pub fn mark_drawn(&mut self) {
+        self.was_drawn = true;
+    }
+
+    #[cfg(feature = "gbm")]
+    fn gles_context(&self) -> Result<&GlesContext, GlesContextError> {
+        gles_context_of(self.backend.as_ref()).ok_or(GlesContextError::NoContext)
+    }
+
+    /// Starts exporting the frames this monitor shows as dma-bufs, e.g. for a screen
+    /// recorder or a remote viewer, keeping at most `capacity` of them reserved for the
+    /// consumer at once (up to [`MAX_EXPORTED_FRAMES`](crate::MAX_EXPORTED_FRAMES)).
+    /// `0` stops exporting; frames already exported stay reserved until released.
+    ///
+    /// While the ring is full, new frames are shown without being exported. Only frames
+    /// rendered to GBM buffers by the default backend are exported, virtual monitors of
+    /// a headless `EasyDRM` and other render backends never export frames.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// monitor.set_frame_export(2);
+    ///
+    /// // After `swap_buffers()`
+    /// for frame in monitor.take_exported_frames() {
+    ///     recorder.send(frame)?;
+    /// }
+    /// for sequence in recorder.finished() {
+    ///     monitor.release_exported_frame(sequence);
+    /// }
+    /// ```
+    #[cfg(feature = "gbm")]
+    pub fn set_frame_export(&mut self, capacity: usize) {
+        self.frame_exporter.set_capacity(capacity);
+    }
+
+    /// Returns how many frames can be exported at once, `0` if export is off.
+    #[cfg(feature = "gbm")]
+    pub fn frame_export_capacity(&self) -> usize {
+        self.frame_exporter.capacity()
+    }
+
+    /// Returns the frames exported since the last call, oldest first.
+    #[cfg(feature = "gbm")]
+    pub fn take_exported_frames(&mut self) -> Vec<ExportedFrame> {
+        self.frame_exporter.take_ready()
+    }
+
+    /// Gives an exported frame's buffer back to the monitor once the consumer is done
+    /// reading it.
+    ///
+    /// Returns false if no frame with this sequence is reserved.
+    #[cfg(feature = "gbm")]
+    pub fn release_exported_frame(&mut self, sequence: u64) -> bool {
+        match self.frame_exporter.release(sequence) {
+            Released::NotHeld => false,
+            Released::Freed => true,
+            Released::OnScreen(bo) => {
+                self.previous_bo = Some(bo);
+                true
+            }
+        }
+    }
+
+    /// Reads back the last frame presented on this monitor, e.g. for bug reports or
+    /// visual tests.
+    ///
+    /// The scanout buffer is mapped through GBM (or directly for dumb buffers), so tiled
+    /// or compressed buffers are converted by the driver. The image is in the orientation
+    /// it was rendered in, before the monitor's rotation is applied.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// easydrm.swap_buffers()?;
+    /// let frame = monitor.capture_frame()?;
+    /// assert_eq!(frame.pixel(0, 0), Some([255, 0, 0, 255]));
+    /// frame.write_png("frame.png")?;
+    /// ```
+    pub fn capture_frame(&self) -> Result<CapturedFrame, CaptureError> {
+        if let Some(frame) = &self.offscreen_frame {
+            return Ok(frame.clone());
+        }
+        #[cfg(feature = "gbm")]
+        if let Some(bo) = self
+            .previous_bo
+            .as_ref()
+            .or_else(|| self.frame_exporter.front_buffer())
+        {
+            let (width, height, format) = (bo.width(), bo.height(), bo.format());
+            return bo.map(0, 0, width, height, |mapped| {
+                CapturedFrame::from_pixels(width, height, format, mapped.buffer(), mapped.stride())
+            })?;
+        }
+        self.backend.read_frame()
+    }
+
+    /// Imports the memory of a Vulkan image into this monitor's context, so a Vulkan
+    /// renderer can produce frames that are copied to the screen with
+    /// [`blit_external_image`](Self::blit_external_image).
+    ///
+    /// Synchronize with the Vulkan side through semaphores from
+    /// [`import_vulkan_semaphore`](Self::import_vulkan_semaphore). The monitor's context
+    /// is made current.
+    #[cfg(feature = "gbm")]
+    pub fn import_vulkan_image(
+        &self,
+        desc: VulkanImageDescriptor,
+    ) -> Result<ExternalImage, InteropError> {
+        ExternalImage::import(self.gles_context()?, desc)
+    }
+
+    /// Imports a Vulkan semaphore exported as an opaque fd into this monitor's context.
+    #[cfg(feature = "gbm")]
+    pub fn import_vulkan_semaphore(&self, fd: OwnedFd) -> Result<ExternalSemaphore, InteropError> {
+        ExternalSemaphore::import(self.gles_context()?, fd)
+    }
+
+    /// Copies an imported Vulkan image to the whole surface, scaling it if the sizes
+    /// differ, and marks the monitor as drawn.
+    ///
+    /// Wait on the semaphore Vulkan signals after rendering before calling this, and
+    /// signal one afterwards before Vulkan reuses the image (see [`ExternalSemaphore`]).
+    #[cfg(feature = "gbm")]
+    pub fn blit_external_image(&mut self, image: &ExternalImage) -> Result<(), InteropError> {
+        self.make_current()?;
+        image.blit(self.surface_size())
+    }
+
+    /// Returns the size of the GL surface, which is transposed for 90 and 270 degree
+    /// rotations.
+    #[cfg(feature = "gbm")]
+    fn surface_size(&self) -> (u32, u32) {
+        let (mode_width, mode_height) = self.active_mode().size();
+        if self.rotation.is_transposed() {
+            (mode_height as u32, mode_width as u32)
+        } else {
+            (mode_width as u32, mode_height as u32)
+        }
+    }
+
+    /// Imports a dma-buf as an OpenGL ES texture of this monitor's context, without
+    /// copying it, e.g. to composite a decoded video frame with the rest of the UI.
+    ///
+    /// The image is wrapped in an `EGLImage` with `EGL_EXT_image_dma_buf_import`.
+    /// Multi-plane YUV images (NV12, P010...) become `GL_TEXTURE_EXTERNAL_OES` textures
+    /// sampled as RGB. The monitor's context is made current; it's not marked as drawn.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let texture = monitor.import_dmabuf_texture(&frame.descriptor())?;
+    /// monitor.make_current()?;
+    /// gl.BindTexture(texture.target(), texture.id());
+    /// // ... draw a quad with a samplerExternalOES if texture.is_external()
+    /// ```
+    #[cfg(feature = "gbm")]
+    pub fn import_dmabuf_texture(&self, desc: &DmaBufDescriptor) -> Result<Texture, TextureError> {
+        Texture::import(self.gles_context()?, desc)
+    }
+
     /// Swaps buffers and submits an atomic commit to display the rendered content.
     ///
     /// This handles:
@@ -252,43 +938,69 @@ impl<T> Monitor<T> {
     /// cause synchronization issues.
     pub(crate) fn swap_buffers(
         &mut self,
-        card: &impl control::Device,
         atomic_req: &mut AtomicModeReq,
     ) -> Result<(), MonitorSetupError> {
         dbg!(self.connector_id);
-        self.gles_context.make_current()?;
-        // Flush GL and swap EGL buffers
-        // Note: GL functions need to be loaded first with gl::load_with()
-        // This will be called by the user before rendering
-
-        // Get the new buffer object from GBM
-        let bo = self
-            .gles_context
-            .swap_buffers()
-            .map_err(|e| MonitorSetupError::DrmError(format!("Failed to swap buffers: {}", e)))?;
+        #[cfg(feature = "gbm")]
+        if let Some(gles_context) = gles_context_of(self.backend.as_ref()) {
+            let surface_size = self.surface_size();
+            gles_context.make_current()?;
 
-        // Cleanup previous fence and sync object
-        if let Some(old_fence_fd) = self.previous_fence_fd.take() {
-            unsafe {
-                libc::close(old_fence_fd);
-            }
-        }
-        if let Some(old_sync) = self.previous_sync.take() {
-            self.destroy_egl_sync(old_sync);
+            // Without a cursor plane, the cursor is drawn on top of the finished frame
+            self.cursor.draw(gles_context, surface_size, self.rotation);
         }
 
-        // Create EGL fence for GPU->DRM synchronization
-        let (fence_fd, sync) = self
-            .create_egl_fence()
-            .map_err(|e| MonitorSetupError::DrmError(format!("Failed to create fence: {}", e)))?;
+        // Let the backend finish the frame (swap EGL buffers, flip dumb buffers...)
+        let mut frame = self.backend.present()?;
 
-        // Create DRM framebuffer from the buffer object
-        // NOTE: Framebuffer will drop automatically after atomic commit (RAII)
-        // We only need to keep the buffer object alive for proper double-buffering
-        let fb = card.add_framebuffer(&bo, 24, 32).map_err(|e| {
-            MonitorSetupError::DrmError(format!("Failed to add framebuffer: {}", e))
+        // Virtual monitors keep the frame in memory until the next one
+        if self.virtual_output.is_some() {
+            return self.swap_virtual(frame);
+        }
+        let fb = frame.framebuffer().ok_or_else(|| {
+            MonitorSetupError::DrmError("The render backend returned no framebuffer".to_string())
         })?;
 
+        // Store buffer object and fence for next frame
+        // Buffer must stay alive until after next lock_front_buffer (double-buffering)
+        // The fence is closed on the next swap_buffers or Drop
+        // An exported buffer is kept by the exporter until the consumer releases it
+        let fence = frame.take_fence();
+        #[cfg(feature = "gbm")]
+        {
+            self.previous_bo = match frame.bo.take() {
+                Some(bo) => self
+                    .frame_exporter
+                    .present(bo, fence.as_ref().map(AsFd::as_fd)),
+                None => None,
+            };
+        }
+        let fence_fd = fence.as_ref().map(AsRawFd::as_raw_fd);
+        self.previous_fence = fence;
+
+        self.present_framebuffer(atomic_req, fb, fence_fd)
+    }
+
+    /// Adds `fb` on the primary plane to the atomic request, with the mode set when one
+    /// is needed and the pending cursor, layer and writeback changes. `fence_fd` is
+    /// signaled once the GPU finished rendering to the framebuffer.
+    ///
+    /// Virtual monitors complete the flip at their next simulated vblank instead.
+    fn present_framebuffer(
+        &mut self,
+        atomic_req: &mut AtomicModeReq,
+        fb: control::framebuffer::Handle,
+        fence_fd: Option<i32>,
+    ) -> Result<(), MonitorSetupError> {
+        let (Some(crtc), Some(primary_plane), Some(device)) = (
+            self.crtc_handle(),
+            self.primary_plane_id,
+            self.device.clone(),
+        ) else {
+            return Err(MonitorSetupError::NoCRTCFound);
+        };
+        let card = &device.card;
+
         // Build atomic commit request
 
         // Determine which mode to use
@@ -299,74 +1011,84 @@ impl<T> Monitor<T> {
         atomic_req.add_property(
             self.connector_id,
             self.connector_properties["CRTC_ID"].handle(),
-            property::Value::CRTC(Some(self.current_crtc.handle())),
+            property::Value::CRTC(Some(crtc)),
         );
         atomic_req.add_property(
-            self.primary_plane_id,
+            primary_plane,
             self.plane_properties["CRTC_ID"].handle(),
-            property::Value::CRTC(Some(self.current_crtc.handle())),
+            property::Value::CRTC(Some(crtc)),
         );
 
         // Configure plane for full-screen scanout
         let (width, height) = target_mode.size();
+        let (src_width, src_height) = if self.rotation.is_transposed() {
+            (height, width)
+        } else {
+            (width, height)
+        };
 
         // Source rectangle (in 16.16 fixed point)
         atomic_req.add_property(
-            self.primary_plane_id,
+            primary_plane,
             self.plane_properties["SRC_X"].handle(),
             property::Value::UnsignedRange(0),
         );
         atomic_req.add_property(
-            self.primary_plane_id,
+            primary_plane,
             self.plane_properties["SRC_Y"].handle(),
             property::Value::UnsignedRange(0),
         );
         atomic_req.add_property(
-            self.primary_plane_id,
+            primary_plane,
             self.plane_properties["SRC_W"].handle(),
-            property::Value::UnsignedRange((width as u64) << 16),
+            property::Value::UnsignedRange((src_width as u64) << 16),
         );
         atomic_req.add_property(
-            self.primary_plane_id,
+            primary_plane,
             self.plane_properties["SRC_H"].handle(),
-            property::Value::UnsignedRange((height as u64) << 16),
+            property::Value::UnsignedRange((src_height as u64) << 16),
         );
 
         // Destination rectangle
         atomic_req.add_property(
-            self.primary_plane_id,
+            primary_plane,
             self.plane_properties["CRTC_X"].handle(),
             property::Value::SignedRange(0),
         );
         atomic_req.add_property(
-            self.primary_plane_id,
+            primary_plane,
             self.plane_properties["CRTC_Y"].handle(),
             property::Value::SignedRange(0),
         );
         atomic_req.add_property(
-            self.primary_plane_id,
+            primary_plane,
             self.plane_properties["CRTC_W"].handle(),
             property::Value::UnsignedRange(width as u64),
         );
         atomic_req.add_property(
-            self.primary_plane_id,
+            primary_plane,
             self.plane_properties["CRTC_H"].handle(),
             property::Value::UnsignedRange(height as u64),
-        ); // If mode set is needed (first frame or mode change)
+        );
+        if let Some(rotation_prop) = self.plane_properties.get("rotation") {
+            atomic_req.add_property(
+                primary_plane,
+                rotation_prop.handle(),
+                property::Value::Bitmask(self.rotation.drm_bits()),
+            );
+        }
+
+        // If mode set is needed (first frame or mode change)
         if needs_mode_set {
             // Create mode blob and set MODE_ID
             let mode_blob = card.create_property_blob(target_mode).map_err(|e| {
                 MonitorSetupError::DrmError(format!("Failed to create mode blob: {}", e))
             })?;
-            atomic_req.add_property(
-                self.current_crtc.handle(),
-                self.crtc_properties["MODE_ID"].handle(),
-                mode_blob,
-            );
+            atomic_req.add_property(crtc, self.crtc_properties["MODE_ID"].handle(), mode_blob);
 
             // Set CRTC active
             atomic_req.add_property(
-                self.current_crtc.handle(),
+                crtc,
                 self.crtc_properties["ACTIVE"].handle(),
                 property::Value::Boolean(true),
             );
@@ -374,32 +1096,35 @@ impl<T> Monitor<T> {
 
         // Set framebuffer on plane (always needed)
         atomic_req.add_property(
-            self.primary_plane_id,
+            primary_plane,
             self.plane_properties["FB_ID"].handle(),
             property::Value::Framebuffer(Some(fb)),
         );
 
         // Add fence for synchronization (prefer CRTC, fallback to plane)
-        if let Some(fence_prop) = self.crtc_properties.get("IN_FENCE_FD") {
-            atomic_req.add_property(
-                self.current_crtc.handle(),
-                fence_prop.handle(),
-                property::Value::SignedRange(fence_fd as i64),
-            );
-        } else if let Some(fence_prop) = self.plane_properties.get("IN_FENCE_FD") {
-            atomic_req.add_property(
-                self.primary_plane_id,
-                fence_prop.handle(),
-                property::Value::SignedRange(fence_fd as i64),
-            );
+        if let Some(fence_fd) = fence_fd {
+            if let Some(fence_prop) = self.crtc_properties.get("IN_FENCE_FD") {
+                atomic_req.add_property(
+                    crtc,
+                    fence_prop.handle(),
+                    property::Value::SignedRange(fence_fd as i64),
+                );
+            } else if let Some(fence_prop) = self.plane_properties.get("IN_FENCE_FD") {
+                atomic_req.add_property(
+                    primary_plane,
+                    fence_prop.handle(),
+                    property::Value::SignedRange(fence_fd as i64),
+                );
+            }
         }
 
-        // Store buffer object, fence, and sync for next frame
-        // Buffer must stay alive until after next lock_front_buffer (double-buffering)
-        // Fence/sync cleaned up on next swap_buffers or Drop
-        self.previous_bo = Some(bo);
-        self.previous_fence_fd = Some(fence_fd);
-        self.previous_sync = Some(sync);
+        // Fold pending cursor and overlay changes into this frame's commit
+        self.add_cursor_to_request(atomic_req);
+        #[cfg(feature = "gbm")]
+        {
+            self.add_layers_to_request(atomic_req)?;
+            self.add_writeback_to_request(atomic_req);
+        }
 
         // Update state
         self.first_frame = false;
@@ -413,55 +1138,222 @@ impl<T> Monitor<T> {
         Ok(())
     }
 
-    /// Creates an EGL fence for GPU->DRM synchronization
-    /// Returns (fence_fd, sync_object)
-    fn create_egl_fence(&self) -> Result<(i32, *mut std::ffi::c_void), String> {
-        unsafe {
-            // Get EGL bindings from glutin display
-            let egl = self.gles_context.display().egl();
+    /// Presents a frame of a virtual monitor: the buffer (or the pixels of a headless
+    /// monitor) is kept for captures and exports and the flip completes at the next
+    /// simulated vblank.
+    #[cfg_attr(not(feature = "gbm"), allow(unused_mut, unused_variables))]
+    fn swap_virtual(&mut self, mut frame: BackendFrame) -> Result<(), MonitorSetupError> {
+        if let Some(virtual_output) = &self.virtual_output {
+            virtual_output.queue_flip()?;
+        }
+        #[cfg(feature = "gbm")]
+        match frame.bo.take() {
+            Some(bo) => self.previous_bo = self.frame_exporter.present(bo, None),
+            // A pbuffer is drawn over by the next frame, so it's read back right away.
+            // Other backends read their front buffer when captured.
+            None if self.gles_context().is_ok_and(GlesContext::is_offscreen) => {
+                let frame = self.backend.read_frame().map_err(|e| {
+                    MonitorSetupError::DrmError(format!("Failed to read back frame: {}", e))
+                })?;
+                self.offscreen_frame = Some(frame);
+            }
+            None => {}
+        }
+        self.first_frame = false;
+        self.can_render = false; // Wait for the simulated vblank
+        if self.needs_mode_set() {
+            self.mark_mode_set();
+        }
+        Ok(())
+    }
 
-            const EGL_SYNC_FENCE_KHR: u32 = 0x3144;
+    /// Returns the timer of a virtual monitor's simulated vblank (internal).
+    pub(crate) fn virtual_timer_fd(&self) -> Option<BorrowedFd<'_>> {
+        self.virtual_output.as_ref().map(VirtualOutput::timer_fd)
+    }
 
-            // Create EGL sync fence
-            let sync = egl.CreateSyncKHR(
-                egl.GetCurrentDisplay(),
-                EGL_SYNC_FENCE_KHR,
-                std::ptr::null(),
-            );
+    /// Completes the flip of a virtual monitor once its vblank timer fired (internal).
+    pub(crate) fn virtual_vblank(&mut self) {
+        if self
+            .virtual_output
+            .as_ref()
+            .is_some_and(VirtualOutput::take_flip)
+        {
+            self.page_flip_complete();
+        }
+    }
 
-            if sync.is_null() {
-                return Err("Failed to create EGL sync".to_string());
-            }
+    fn crtc_handle(&self) -> Option<crtc::Handle> {
+        self.current_crtc.as_ref().map(|crtc| crtc.handle())
+    }
+
+    /// Returns true if a plane cursor changed since it was last committed (internal).
+    pub(crate) fn has_pending_cursor_update(&mut self) -> bool {
+        self.cursor.has_pending_plane_update()
+    }
 
-            // Duplicate as a native fence FD for DRM
-            let fence_fd = egl.DupNativeFenceFDANDROID(egl.GetCurrentDisplay(), sync);
+    /// Adds pending cursor changes to an atomic request (internal).
+    ///
+    /// Used by `swap_buffers` and by `EasyDRM::commit_cursors()` for cursor-only commits.
+    pub(crate) fn add_cursor_to_request(&mut self, atomic_req: &mut AtomicModeReq) {
+        let Some(crtc) = self.crtc_handle() else {
+            return;
+        };
+        self.cursor.add_to_request(atomic_req, crtc);
+    }
 
-            if fence_fd < 0 {
-                return Err(format!("Failed to duplicate fence FD: fence_fd={fence_fd}"));
+    /// Called by `EasyDRM` once the atomic request this monitor was added to has been
+    /// committed, so state that only counts once it reached the kernel (the cursor, layers
+    /// and writeback) is updated.
+    pub(crate) fn commit_succeeded(&mut self) {
+        self.cursor.committed();
+        #[cfg(feature = "gbm")]
+        {
+            for layer in &mut self.layers {
+                layer.commit_succeeded();
             }
+            if let Some(writeback) = &mut self.writeback {
+                writeback.commit_succeeded();
+            }
+        }
+    }
 
-            Ok((fence_fd, sync as *mut std::ffi::c_void))
+    /// Called by `EasyDRM` when the atomic request this monitor was added to was
+    /// rejected, so pending layer changes and a writeback go into the next commit instead.
+    pub(crate) fn commit_failed(&mut self) {
+        #[cfg(feature = "gbm")]
+        {
+            for layer in &mut self.layers {
+                layer.commit_failed();
+            }
+            if let Some(writeback) = &mut self.writeback {
+                writeback.commit_failed();
+            }
         }
     }
 
-    /// Destroys an EGL sync object
-    fn destroy_egl_sync(&self, sync: *mut std::ffi::c_void) {
-        unsafe {
-            // Get EGL bindings from glutin display
-            let egl = self.gles_context.display().egl();
-            let egl_display = egl.GetCurrentDisplay();
+    /// Returns true if an overlay layer was redrawn or changed since it was last
+    /// committed (internal).
+    #[cfg(feature = "gbm")]
+    pub(crate) fn has_pending_layer_update(&self) -> bool {
+        self.layers.iter().any(Layer::needs_commit)
+    }
+
+    /// Commits overlay layers (and the cursor) without a new frame on the primary plane
+    /// (internal).
+    ///
+    /// Used by `EasyDRM::swap_buffers()` when only layers were drawn, e.g. a video
+    /// playing over a static UI.
+    #[cfg(feature = "gbm")]
+    pub(crate) fn swap_layers(
+        &mut self,
+        atomic_req: &mut AtomicModeReq,
+    ) -> Result<(), MonitorSetupError> {
+        self.add_cursor_to_request(atomic_req);
+        self.add_layers_to_request(atomic_req)?;
+        self.add_writeback_to_request(atomic_req);
+        self.can_render = false; // Wait for page flip event
+        Ok(())
+    }
+
+    #[cfg(feature = "gbm")]
+    fn add_writeback_to_request(&mut self, atomic_req: &mut AtomicModeReq) {
+        let Some(crtc) = self.crtc_handle() else {
+            return;
+        };
+        if let Some(writeback) = &mut self.writeback {
+            writeback.add_to_request(atomic_req, crtc);
+        }
+    }
+
+    /// Returns the writeback connector capturing this monitor, if a capture is pending
+    /// (internal).
+    #[cfg(feature = "gbm")]
+    pub(crate) fn writeback_connector(&self) -> Option<connector::Handle> {
+        self.writeback.as_ref().map(WritebackJob::connector)
+    }
+
+    /// Returns the out fence of the committed writeback capture, if it has one
+    /// (internal).
+    #[cfg(feature = "gbm")]
+    pub(crate) fn writeback_out_fence(&self) -> Option<BorrowedFd<'_>> {
+        self.writeback.as_ref()?.out_fence()
+    }
+
+    /// Queues a writeback capture for the next commit (internal, see
+    /// `EasyDRM::request_writeback()`).
+    #[cfg(feature = "gbm")]
+    pub(crate) fn set_writeback(&mut self, job: WritebackJob) {
+        self.writeback = Some(job);
+    }
+
+    /// Returns the frame captured by `EasyDRM::request_writeback()` if the display
+    /// controller finished writing it, or `None` if it's still pending.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WritebackError::NotRequested`] if no capture is pending.
+    #[cfg(feature = "gbm")]
+    pub fn take_writeback(&mut self) -> Result<Option<CapturedFrame>, WritebackError> {
+        self.poll_writeback(Duration::ZERO)
+    }
 
-            // Destroy the sync object
-            egl.DestroySyncKHR(egl_display, sync);
+    /// Waits up to `timeout` for the frame captured by `EasyDRM::request_writeback()`.
+    ///
+    /// The capture is attached to the monitor's next commit, so call
+    /// `EasyDRM::swap_buffers()` first. Writeback connectors without an out fence finish
+    /// with the commit's page flip, which this can't wait for; use
+    /// `EasyDRM::wait_writeback()` there, or call it after `EasyDRM::poll_events()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WritebackError::Timeout`] if the capture isn't done after `timeout`; it
+    /// stays pending. Returns [`WritebackError::NotRequested`] if no capture is pending.
+    #[cfg(feature = "gbm")]
+    pub fn wait_writeback(&mut self, timeout: Duration) -> Result<CapturedFrame, WritebackError> {
+        self.poll_writeback(timeout)?.ok_or(WritebackError::Timeout)
+    }
+
+    #[cfg(feature = "gbm")]
+    fn poll_writeback(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<CapturedFrame>, WritebackError> {
+        let job = self
+            .writeback
+            .as_ref()
+            .ok_or(WritebackError::NotRequested)?;
+        if job.is_queued() || !job.wait(timeout)? {
+            return Ok(None);
+        }
+        let frame = job.read();
+        self.writeback = None;
+        frame.map(Some)
+    }
+
+    #[cfg(feature = "gbm")]
+    fn add_layers_to_request(
+        &mut self,
+        atomic_req: &mut AtomicModeReq,
+    ) -> Result<(), MonitorSetupError> {
+        let Some(crtc) = self.crtc_handle() else {
+            return Ok(());
+        };
+        for layer in &mut self.layers {
+            layer.add_to_request(atomic_req, crtc).map_err(|e| {
+                MonitorSetupError::DrmError(format!("Failed to update overlay layer: {}", e))
+            })?;
         }
+        Ok(())
     }
 
     /// Checks if a mode set is needed (internal).
     ///
-    /// Returns `true` if `requested_mode` differs from `current_mode`,
-    /// indicating that a mode set should be included in the next atomic commit.
+    /// Returns `true` on the first frame (or after the mode state was cleared) and when
+    /// `requested_mode` differs from `current_mode`, indicating that a mode set should be
+    /// included in the next atomic commit.
     pub(crate) fn needs_mode_set(&self) -> bool {
-        self.requested_mode != self.current_mode
+        self.first_frame || self.requested_mode != self.current_mode
     }
 
     /// Marks the mode as successfully set (internal).
@@ -499,8 +1391,15 @@ impl<T> Monitor<T> {
     ///     gl.Clear(gl::COLOR_BUFFER_BIT);
     /// }
     /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the monitor uses another render backend, which has no OpenGL ES context.
+    #[cfg(feature = "gbm")]
     pub fn gl(&self) -> &crate::gl::Gles2 {
-        self.gles_context.gl()
+        self.gles_context()
+            .expect("the monitor's render backend has no OpenGL ES context")
+            .gl()
     }
 
     /// Gets a function pointer for loading OpenGL functions.
@@ -514,8 +1413,14 @@ impl<T> Monitor<T> {
     /// // For use with external libraries
     /// external_lib::load_with(|symbol| monitor.get_proc_address(symbol));
     /// ```
+    ///
+    /// Returns a null pointer if the monitor uses another render backend.
+    #[cfg(feature = "gbm")]
     pub fn get_proc_address(&self, symbol: &str) -> *const std::ffi::c_void {
-        self.gles_context.get_proc_address(symbol)
+        match self.gles_context() {
+            Ok(gles_context) => gles_context.get_proc_address(symbol),
+            Err(_) => std::ptr::null(),
+        }
     }
 
     /// Returns the DRM connector handle for this monitor.
@@ -523,15 +1428,93 @@ impl<T> Monitor<T> {
         self.connector_id
     }
 
+    /// Returns the kernel name of this monitor's connector, e.g. `HDMI-A-1`, `eDP-1` or `DP-3`.
+    ///
+    /// This is the same name used by `modetest`, sysfs, the `video=` kernel parameter and
+    /// compositors, so it can be used in logs and configuration files.
+    pub fn connector_name(&self) -> &str {
+        &self.connector_name
+    }
+
+    /// Returns the type of connector this monitor is plugged into.
+    pub fn interface(&self) -> connector::Interface {
+        self.interface
+    }
+
+    /// Returns the physical connector behind a multi-signal port, if the driver reports it.
+    ///
+    /// For example, a DP++ port with a passive HDMI adapter reports [`Subconnector::HDMI`],
+    /// and a DVI-I port driving a digital display reports [`Subconnector::DVID`].
+    pub fn subconnector(&self) -> Option<&Subconnector> {
+        self.subconnector.as_ref()
+    }
+
+    /// Returns true if this monitor is a built-in panel (eDP, LVDS, DSI or DPI).
+    pub fn is_internal(&self) -> bool {
+        is_internal_interface(self.interface)
+    }
+
+    /// Returns the rotation applied to this monitor's primary plane.
+    ///
+    /// For 90° and 270° the GL surface has swapped dimensions (e.g. 1080x1920 for a
+    /// 1920x1080 mode), so render in portrait and the display controller rotates it.
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    /// Returns the position of this monitor in the application's layout, if the display
+    /// config assigns one.
+    pub fn position(&self) -> Option<(i32, i32)> {
+        self.position
+    }
+
+    /// Returns the display config settings that couldn't be applied to this monitor.
+    pub fn config_issues(&self) -> &[ConfigIssue] {
+        &self.config_issues
+    }
+
+    /// Returns the information decoded from this monitor's EDID.
+    ///
+    /// Returns `None` if the connector doesn't expose an EDID or it couldn't be parsed
+    /// (common for virtual GPUs and some embedded panels).
+    pub fn info(&self) -> Option<&MonitorInfo> {
+        self.info.as_ref()
+    }
+
+    /// Returns the stable identifier of this monitor.
+    ///
+    /// Unlike [`connector_id`](Self::connector_id), this survives hotplug, MST topology
+    /// rebuilds and reboots, so it can be persisted in configuration files.
+    pub fn id(&self) -> &MonitorId {
+        &self.id
+    }
+
     /// Returns the CRTC information for this monitor.
+    ///
+    /// # Panics
+    ///
+    /// Panics on virtual monitors, which have no CRTC; use [`try_crtc`](Self::try_crtc)
+    /// if the monitor may be virtual.
     pub fn crtc(&self) -> &crtc::Info {
-        &self.current_crtc
+        self.try_crtc().expect("virtual monitors have no CRTC")
+    }
+
+    /// Returns the CRTC information for this monitor, or `None` for virtual monitors.
+    pub fn try_crtc(&self) -> Option<&crtc::Info> {
+        self.current_crtc.as_ref()
+    }
+
+    /// Returns true for monitors added with `EasyDRM::add_virtual_monitor()`, which
+    /// render offscreen.
+    pub fn is_virtual(&self) -> bool {
+        self.virtual_output.is_some()
     }
 
     /// Returns the optimal display mode for this monitor.
     ///
     /// This is the preferred mode reported by the monitor (typically the highest
-    /// resolution and refresh rate supported, e.g., 4K@120Hz).
+    /// resolution and refresh rate supported, e.g., 4K@120Hz), or the mode given for this
+    /// connector by a `video=` parameter on the kernel command line.
     pub fn default_mode(&self) -> &control::Mode {
         &self.default_mode
     }
@@ -600,17 +1583,209 @@ impl<T> Monitor<T> {
     }
 
     /// Returns the handle to the primary plane used for scanout.
+    ///
+    /// # Panics
+    ///
+    /// Panics on virtual monitors, which have no primary plane; use
+    /// [`try_primary_plane`](Self::try_primary_plane) if the monitor may be virtual.
     pub fn primary_plane(&self) -> plane::Handle {
+        self.try_primary_plane()
+            .expect("virtual monitors have no primary plane")
+    }
+
+    /// Returns the handle to the primary plane used for scanout, or `None` for virtual
+    /// monitors.
+    pub fn try_primary_plane(&self) -> Option<plane::Handle> {
         self.primary_plane_id
     }
 
-    /// Returns the handle to the cursor plane, if available.
+    /// Returns the formats and modifiers the primary plane can scan out, if the driver
+    /// exposes them (`IN_FORMATS`).
+    pub fn plane_formats(&self) -> Option<&PlaneFormats> {
+        self.plane_formats.as_ref()
+    }
+
+    /// Returns the pixel format and memory layout of the buffers this monitor renders to.
+    ///
+    /// When the primary plane lists its modifiers, the layout is negotiated with the
+    /// renderer, so tiled or compressed layouts (AFBC, CCS, DCC) are used when the display
+    /// controller supports them. If a test commit rejects the result, EasyDRM falls back
+    /// to a linear layout.
+    pub fn scanout_format(&self) -> ScanoutFormat {
+        self.backend.scanout_format()
+    }
+
+    /// Returns the handle to the plane showing the cursor, if available.
     ///
-    /// Some GPUs (especially virtual ones like virtio-gpu) don't expose a separate cursor plane.
+    /// Some GPUs (especially virtual ones like virtio-gpu) don't expose a separate cursor
+    /// plane. A free overlay plane is used instead when there is one, see
+    /// [`cursor_backend`](Self::cursor_backend).
     pub fn cursor_plane(&self) -> Option<plane::Handle> {
         self.cursor_plane_id
     }
 
+    /// Sets the cursor image and shows the cursor.
+    ///
+    /// `argb` holds `width * height` pixels in `0xAARRGGBB` format with premultiplied
+    /// alpha, row by row. The image may be smaller than [`cursor_size`](Self::cursor_size),
+    /// the rest of the cursor is transparent. `hotspot` is the pixel of the image that
+    /// points at the cursor position.
+    ///
+    /// The change is shown with the next `EasyDRM::swap_buffers()` or
+    /// `EasyDRM::commit_cursors()`. For a [composited](CursorBackend::Composited) cursor,
+    /// only a redrawn frame shows it, see [`cursor_needs_redraw`](Self::cursor_needs_redraw).
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// // 16x16 white square with the hotspot at its center
+    /// monitor.set_cursor_image(&[0xFFFFFFFF; 16 * 16], 16, 16, (8, 8))?;
+    /// monitor.move_cursor(640, 360);
+    /// ```
+    pub fn set_cursor_image(
+        &mut self,
+        argb: &[u32],
+        width: u32,
+        height: u32,
+        hotspot: (i32, i32),
+    ) -> Result<(), CursorError> {
+        self.cursor.set_image(argb, width, height, hotspot)
+    }
+
+    /// Moves the cursor hotspot to (x, y), in pixels from the top-left corner of the mode.
+    pub fn move_cursor(&mut self, x: i32, y: i32) {
+        self.cursor.move_to(x, y);
+    }
+
+    /// Hides the cursor until the next [`set_cursor_image`](Self::set_cursor_image).
+    pub fn hide_cursor(&mut self) {
+        self.cursor.hide();
+    }
+
+    /// Returns the maximum cursor image size.
+    pub fn cursor_size(&self) -> (u32, u32) {
+        self.cursor.size()
+    }
+
+    /// Returns the cursor position, or `None` if the cursor is hidden.
+    pub fn cursor_position(&self) -> Option<(i32, i32)> {
+        self.cursor.is_visible().then(|| self.cursor.position())
+    }
+
+    /// Returns how the cursor is displayed on this monitor.
+    pub fn cursor_backend(&self) -> CursorBackend {
+        self.cursor.backend()
+    }
+
+    /// Returns true if the cursor is [composited](CursorBackend::Composited) and changed
+    /// since the last frame, so the monitor has to be redrawn to show the change.
+    ///
+    /// Always false for cursors on a plane, which are updated without redrawing.
+    pub fn cursor_needs_redraw(&self) -> bool {
+        self.cursor.needs_redraw()
+    }
+
+    /// Allocates a free overlay plane for this monitor and returns it as a [`Layer`]
+    /// with its own `width`x`height` OpenGL ES surface.
+    ///
+    /// The layer is committed atomically with the primary plane, so e.g. a video can be
+    /// shown on the overlay and the UI on the primary plane. Use [`Layer::plane`] to find
+    /// it again with [`layer_mut`](Self::layer_mut).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LayerError::NoOverlayAvailable`] if every overlay plane that can be used
+    /// with this monitor's CRTC is already in use, by this or another monitor.
+    #[cfg(feature = "gbm")]
+    pub fn request_overlay(&mut self, width: u16, height: u16) -> Result<&mut Layer, LayerError> {
+        let (device, plane) = self.find_free_overlay(DrmFourcc::Argb8888)?;
+        let debug_output = self
+            .gl_debug_logger
+            .as_ref()
+            .map(|logger| GlDebugOutput::new(self.connector_id, &self.connector_name, logger));
+        let layer = Layer::new(
+            device,
+            plane,
+            (width, height),
+            &self.gl_config,
+            self.share_group.as_deref(),
+            debug_output,
+        )?;
+        self.layers.push(layer);
+        let index = self.layers.len() - 1;
+        Ok(&mut self.layers[index])
+    }
+
+    /// Allocates a free overlay plane that can scan out `format` and returns it as a
+    /// [`Layer`] without an OpenGL surface, to show dma-bufs with
+    /// [`Layer::present_dmabuf`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LayerError::NoOverlayAvailable`] if no free overlay plane of this
+    /// monitor's CRTC supports `format`.
+    #[cfg(feature = "gbm")]
+    pub fn request_dmabuf_overlay(&mut self, format: DrmFourcc) -> Result<&mut Layer, LayerError> {
+        let (device, plane) = self.find_free_overlay(format)?;
+        let layer = Layer::for_dmabuf(device, plane)?;
+        self.layers.push(layer);
+        let index = self.layers.len() - 1;
+        Ok(&mut self.layers[index])
+    }
+
+    #[cfg(feature = "gbm")]
+    fn find_free_overlay(
+        &self,
+        format: DrmFourcc,
+    ) -> Result<(Arc<GpuDevice>, plane::Handle), LayerError> {
+        let (Some(crtc), Some(device)) = (self.crtc_handle(), &self.device) else {
+            return Err(LayerError::NoOverlayAvailable);
+        };
+        let card = &device.card;
+        let res = card.resource_handles()?;
+        let plane_handles = card.plane_handles()?;
+        let format_planes = device.planes_with_format(&plane_handles, format);
+        let plane = device
+            .find_plane_for_crtc(
+                &format_planes,
+                &res,
+                crtc,
+                PlaneType::Overlay,
+                &device.claimed_planes(),
+            )
+            .map_err(|e| LayerError::DrmError(e.to_string()))?
+            .ok_or(LayerError::NoOverlayAvailable)?;
+        Ok((Arc::clone(device), plane))
+    }
+
+    /// Returns the overlay layers of this monitor.
+    #[cfg(feature = "gbm")]
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// Returns the overlay layers of this monitor.
+    #[cfg(feature = "gbm")]
+    pub fn layers_mut(&mut self) -> &mut [Layer] {
+        &mut self.layers
+    }
+
+    /// Returns the overlay layer on `plane`, if this monitor has one.
+    #[cfg(feature = "gbm")]
+    pub fn layer_mut(&mut self, plane: plane::Handle) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|layer| layer.plane() == plane)
+    }
+
+    /// Turns off the overlay layer on `plane` and makes the plane available again.
+    ///
+    /// Returns false if this monitor has no layer on `plane`.
+    #[cfg(feature = "gbm")]
+    pub fn release_overlay(&mut self, plane: plane::Handle) -> bool {
+        let count = self.layers.len();
+        self.layers.retain(|layer| layer.plane() != plane);
+        self.layers.len() != count
+    }
+
     /// Returns the current resolution as (width, height).
     ///
     /// Uses the requested mode if one has been set, otherwise returns
@@ -622,14 +1797,183 @@ impl<T> Monitor<T> {
 
 impl<T> Drop for Monitor<T> {
     fn drop(&mut self) {
-        // Clean up fence resources to prevent FD leaks
-        if let Some(fence_fd) = self.previous_fence_fd.take() {
-            unsafe {
-                libc::close(fence_fd);
+        // The composited cursor's GL objects are deleted in the monitor's context
+        #[cfg(feature = "gbm")]
+        if let Some(gles_context) = gles_context_of(self.backend.as_ref())
+            && gles_context.make_current().is_ok()
+        {
+            self.cursor.release_gl();
+        }
+        if let (CursorBackend::OverlayPlane, Some(plane), Some(device)) =
+            (self.cursor.backend(), self.cursor_plane_id, &self.device)
+        {
+            device.release_plane(plane);
+        }
+    }
+}
+
+/// A TEST_ONLY commit of a monitor's first frame, to check that the display controller can
+/// scan out the buffer layout GBM picked.
+#[cfg(feature = "gbm")]
+struct ScanoutTest<'a> {
+    connector_id: connector::Handle,
+    connector_name: &'a str,
+    connector_properties: &'a HashMap<String, property::Info>,
+    crtc: crtc::Handle,
+    crtc_properties: &'a HashMap<String, property::Info>,
+    plane: plane::Handle,
+    plane_properties: &'a HashMap<String, property::Info>,
+    mode: control::Mode,
+    rotation: Rotation,
+}
+
+#[cfg(feature = "gbm")]
+impl ScanoutTest<'_> {
+    /// Creates the OpenGL ES context of the monitor, negotiating the buffer layout with
+    /// the primary plane so the GPU renders tiled or compressed buffers when the display
+    /// controller can scan them out.
+    fn negotiate(
+        &self,
+        card: &impl control::Device,
+        gbm: &gbm::Device<std::fs::File>,
+        plane_formats: Option<&PlaneFormats>,
+        gl_config: &GlConfig,
+        share_group: Option<&ShareGroup>,
+        (width, height): (u16, u16),
+    ) -> Result<(GlesContext, ScanoutFormat), MonitorSetupError> {
+        let format = gl_config.scanout_format();
+        let mut gles_context = match plane_formats {
+            Some(formats) => GlesContext::with_modifiers(
+                gbm,
+                width,
+                height,
+                format,
+                formats.modifiers(format),
+                gl_config,
+                share_group,
+            )?,
+            None => GlesContext::new(gbm, width, height, gl_config, share_group)?,
+        };
+        let mut scanout_modifier = None;
+        if let Some(formats) = plane_formats.filter(|_| gles_context.has_explicit_modifier()) {
+            match self.run(card, &mut gles_context) {
+                Ok(modifier) => scanout_modifier = Some(modifier),
+                Err(e) => {
+                    eprintln!(
+                        "Warning: {} rejected the negotiated buffer layout, falling back to linear: {}",
+                        self.connector_name, e
+                    );
+                    let linear: &[DrmModifier] = if formats.supports(format, DrmModifier::Linear) {
+                        &[DrmModifier::Linear]
+                    } else {
+                        &[]
+                    };
+                    gles_context = GlesContext::with_modifiers(
+                        gbm,
+                        width,
+                        height,
+                        format,
+                        linear,
+                        gl_config,
+                        share_group,
+                    )?;
+                }
             }
         }
-        if let Some(sync) = self.previous_sync.take() {
-            self.destroy_egl_sync(sync);
+        let scanout_format = ScanoutFormat {
+            format: gles_context.format(),
+            modifier: scanout_modifier.filter(|&modifier| modifier != DrmModifier::Invalid),
+            explicit_modifier: gles_context.has_explicit_modifier(),
+        };
+        Ok((gles_context, scanout_format))
+    }
+
+    /// Renders a black frame and test-commits it. Returns the modifier of the buffer.
+    fn run(
+        &self,
+        card: &impl control::Device,
+        gles_context: &mut GlesContext,
+    ) -> Result<DrmModifier, MonitorSetupError> {
+        gles_context.make_current()?;
+        let gl = gles_context.gl();
+        unsafe {
+            gl.ClearColor(0.0, 0.0, 0.0, 1.0);
+            gl.Clear(crate::gl::COLOR_BUFFER_BIT);
+        }
+        let bo = gles_context
+            .swap_buffers()
+            .map_err(|e| MonitorSetupError::DrmError(format!("Failed to swap buffers: {}", e)))?;
+        let fb = add_scanout_framebuffer(card, &bo, 24, true)?;
+        let mode_blob = card.create_property_blob(&self.mode)?;
+
+        let (width, height) = self.mode.size();
+        let (src_width, src_height) = if self.rotation.is_transposed() {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        let mut atomic_req = AtomicModeReq::new();
+        let mut set = |object: control::RawResourceHandle,
+                       properties: &HashMap<String, property::Info>,
+                       name: &str,
+                       value: property::Value| {
+            if let Some(prop) = properties.get(name) {
+                atomic_req.add_raw_property(object, prop.handle(), value.into());
+            }
+        };
+        let (connector, crtc, plane) = (
+            self.connector_id.into(),
+            self.crtc.into(),
+            self.plane.into(),
+        );
+        set(
+            connector,
+            self.connector_properties,
+            "CRTC_ID",
+            property::Value::CRTC(Some(self.crtc)),
+        );
+        set(crtc, self.crtc_properties, "MODE_ID", mode_blob);
+        set(
+            crtc,
+            self.crtc_properties,
+            "ACTIVE",
+            property::Value::Boolean(true),
+        );
+        let plane_values = [
+            ("CRTC_ID", property::Value::CRTC(Some(self.crtc))),
+            ("FB_ID", property::Value::Framebuffer(Some(fb))),
+            ("SRC_X", property::Value::UnsignedRange(0)),
+            ("SRC_Y", property::Value::UnsignedRange(0)),
+            (
+                "SRC_W",
+                property::Value::UnsignedRange((src_width as u64) << 16),
+            ),
+            (
+                "SRC_H",
+                property::Value::UnsignedRange((src_height as u64) << 16),
+            ),
+            ("CRTC_X", property::Value::SignedRange(0)),
+            ("CRTC_Y", property::Value::SignedRange(0)),
+            ("CRTC_W", property::Value::UnsignedRange(width as u64)),
+            ("CRTC_H", property::Value::UnsignedRange(height as u64)),
+            (
+                "rotation",
+                property::Value::Bitmask(self.rotation.drm_bits()),
+            ),
+        ];
+        for (name, value) in plane_values {
+            set(plane, self.plane_properties, name, value);
+        }
+
+        let result = card.atomic_commit(
+            AtomicCommitFlags::TEST_ONLY | AtomicCommitFlags::ALLOW_MODESET,
+            atomic_req,
+        );
+        card.destroy_framebuffer(fb).ok();
+        if let property::Value::Blob(blob) = mode_blob {
+            card.destroy_property_blob(blob).ok();
         }
+        result?;
+        Ok(bo.modifier())
     }
 }
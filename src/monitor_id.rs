@@ -0,0 +1,159 @@
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::edid::MonitorInfo;
+
+/// Identity of the physical display, taken from its EDID.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EdidIdentity {
+    /// Three letter PNP manufacturer id.
+    pub manufacturer: String,
+    /// Manufacturer assigned product code.
+    pub product_code: u16,
+    /// Serial number string, or the numeric serial if there's no string.
+    ///
+    /// Empty if the display reports neither (common for cheap panels).
+    pub serial: String,
+}
+
+impl EdidIdentity {
+    pub(crate) fn from_info(info: &MonitorInfo) -> Self {
+        let serial = info
+            .serial_string
+            .clone()
+            .or_else(|| info.serial_number.map(|serial| serial.to_string()))
+            .unwrap_or_default();
+        EdidIdentity {
+            manufacturer: info.manufacturer.clone(),
+            product_code: info.product_code,
+            serial,
+        }
+    }
+}
+
+/// Stable identifier of a monitor.
+///
+/// Unlike `connector::Handle`, which changes when DP MST topologies are rebuilt or the
+/// GPU enumeration order shifts, a `MonitorId` is derived from the display's EDID
+/// (manufacturer, product code, serial) plus the kernel connector name (e.g. `HDMI-A-1`),
+/// so it stays the same across hotplug and reboots.
+///
+/// It round-trips through its string form, `DEL:A0F3:CFV9N01A1B2L@DP-2` (or just `DP-2`
+/// for monitors without an EDID), so it can be stored in configuration files.
+///
+/// See [`EasyDRM::get_monitor_by_id`](crate::EasyDRM::get_monitor_by_id) for how ids are
+/// matched when the connector name has changed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MonitorId {
+    edid: Option<EdidIdentity>,
+    connector_name: String,
+}
+
+impl MonitorId {
+    /// Creates an id from an EDID identity and a connector name.
+    pub fn new(edid: Option<EdidIdentity>, connector_name: impl Into<String>) -> Self {
+        MonitorId {
+            edid,
+            connector_name: connector_name.into(),
+        }
+    }
+
+    pub(crate) fn from_info(info: Option<&MonitorInfo>, connector_name: &str) -> Self {
+        Self::new(info.map(EdidIdentity::from_info), connector_name)
+    }
+
+    /// Returns the EDID part of this id, if the monitor has an EDID.
+    pub fn edid(&self) -> Option<&EdidIdentity> {
+        self.edid.as_ref()
+    }
+
+    /// Returns the connector name part of this id (e.g. `HDMI-A-1`).
+    pub fn connector_name(&self) -> &str {
+        &self.connector_name
+    }
+
+    /// Returns true if both ids describe the same physical display, ignoring the
+    /// connector it's plugged into.
+    ///
+    /// Always false for monitors without an EDID.
+    pub fn same_display(&self, other: &MonitorId) -> bool {
+        self.edid.is_some() && self.edid == other.edid
+    }
+
+    /// Finds the candidate this id refers to and returns its key.
+    ///
+    /// An exact match is preferred. Otherwise the single candidate showing the same
+    /// display is returned; if several do (identical panels without serials), the id is
+    /// ambiguous and `None` is returned.
+    pub fn find_in<'a, K>(
+        &self,
+        candidates: impl IntoIterator<Item = (K, &'a MonitorId)>,
+    ) -> Option<K> {
+        let mut same_display = None;
+        let mut ambiguous = false;
+        for (key, candidate) in candidates {
+            if candidate == self {
+                return Some(key);
+            }
+            if candidate.same_display(self) {
+                ambiguous |= same_display.replace(key).is_some();
+            }
+        }
+        same_display.filter(|_| !ambiguous)
+    }
+}
+
+impl fmt::Display for MonitorId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(edid) = &self.edid {
+            write!(
+                f,
+                "{}:{:04X}:{}@",
+                edid.manufacturer, edid.product_code, edid.serial
+            )?;
+        }
+        f.write_str(&self.connector_name)
+    }
+}
+
+/// Error returned when parsing a [`MonitorId`] from a string fails
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("invalid monitor id: {0:?}")]
+pub struct ParseMonitorIdError(String);
+
+impl FromStr for MonitorId {
+    type Err = ParseMonitorIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseMonitorIdError(s.to_string());
+
+        let Some((edid, connector_name)) = s.rsplit_once('@') else {
+            if s.is_empty() {
+                return Err(error());
+            }
+            return Ok(MonitorId::new(None, s));
+        };
+        if connector_name.is_empty() {
+            return Err(error());
+        }
+
+        let mut parts = edid.splitn(3, ':');
+        let (Some(manufacturer), Some(product_code), Some(serial)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(error());
+        };
+        let product_code = u16::from_str_radix(product_code, 16).map_err(|_| error())?;
+
+        Ok(MonitorId::new(
+            Some(EdidIdentity {
+                manufacturer: manufacturer.to_string(),
+                product_code,
+                serial: serial.to_string(),
+            }),
+            connector_name,
+        ))
+    }
+}
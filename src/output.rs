@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use drm::control::{self, connector, property};
+
+/// Kernel name of a connector, e.g. `HDMI-A-1`, `eDP-1` or `DP-3`.
+///
+/// This matches the names used by the kernel (sysfs, `video=` command line),
+/// `modetest` and compositors: the connector type name followed by the
+/// per-type connector index.
+pub(crate) fn connector_name(connector: &connector::Info) -> String {
+    format!(
+        "{}-{}",
+        connector.interface().as_str(),
+        connector.interface_id()
+    )
+}
+
+/// Returns true for interfaces that drive built-in panels (laptops, tablets, SBC displays).
+pub(crate) fn is_internal_interface(interface: connector::Interface) -> bool {
+    matches!(
+        interface,
+        connector::Interface::EmbeddedDisplayPort
+            | connector::Interface::LVDS
+            | connector::Interface::DSI
+            | connector::Interface::DPI
+    )
+}
+
+/// The physical connector behind a connector that can carry several signal types,
+/// such as a DP++ port with a passive HDMI adapter or a DVI-I port.
+///
+/// Read from the connector `subconnector` property.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Subconnector {
+    Unknown,
+    /// Native signal of the connector (e.g. DisplayPort on a DP port)
+    Native,
+    VGA,
+    DVID,
+    DVIA,
+    HDMI,
+    DisplayPort,
+    Wireless,
+    Composite,
+    SVideo,
+    Component,
+    SCART,
+    /// A value not known to EasyDRM, as reported by the kernel
+    Other(String),
+}
+
+impl Subconnector {
+    fn from_kernel_name(name: &str) -> Self {
+        match name {
+            "Unknown" => Subconnector::Unknown,
+            "Native" => Subconnector::Native,
+            "VGA" => Subconnector::VGA,
+            "DVI-D" => Subconnector::DVID,
+            "DVI-A" => Subconnector::DVIA,
+            "HDMI" => Subconnector::HDMI,
+            "DP" => Subconnector::DisplayPort,
+            "Wireless" => Subconnector::Wireless,
+            "Composite" => Subconnector::Composite,
+            "SVIDEO" => Subconnector::SVideo,
+            "Component" => Subconnector::Component,
+            "SCART" => Subconnector::SCART,
+            other => Subconnector::Other(other.to_string()),
+        }
+    }
+}
+
+/// Finds a connector property by name, returning its info and current raw value.
+fn find_connector_property(
+    card: &impl control::Device,
+    connector_id: connector::Handle,
+    name: &str,
+) -> Option<(property::Info, property::RawValue)> {
+    let properties = card.get_properties(connector_id).ok()?;
+    let (ids, values) = properties.as_props_and_values();
+    for (&id, &value) in ids.iter().zip(values) {
+        let Ok(info) = card.get_property(id) else {
+            continue;
+        };
+        if info.name().to_str() == Ok(name) {
+            return Some((info, value));
+        }
+    }
+    None
+}
+
+/// Reads the contents of a blob property (e.g. `EDID`) of a connector.
+///
+/// Returns `None` if the property doesn't exist or is unset.
+pub(crate) fn read_connector_blob(
+    card: &impl control::Device,
+    connector_id: connector::Handle,
+    name: &str,
+) -> Option<Vec<u8>> {
+    match find_connector_property(card, connector_id, name)? {
+        (_, 0) => None,
+        (_, blob) => card.get_property_blob(blob).ok(),
+    }
+}
+
+/// Returns the `DRM_MODE_ROTATE_*`/`DRM_MODE_REFLECT_*` bits a plane's `rotation`
+/// property accepts, or `None` if the plane can't rotate.
+///
+/// drm-rs doesn't keep the values of bitmask properties, so they're read again through
+/// the raw ioctl.
+pub(crate) fn supported_rotations(
+    card: &impl control::Device,
+    plane_properties: &HashMap<String, property::Info>,
+) -> Option<u64> {
+    let info = plane_properties.get("rotation")?;
+    let mut bits = Vec::new();
+    drm_ffi::mode::get_property(card.as_fd(), info.handle().into(), Some(&mut bits), None).ok()?;
+    Some(
+        bits.iter()
+            .filter(|&&bit| bit < 64)
+            .fold(0, |mask, bit| mask | 1 << bit),
+    )
+}
+
+/// Reads the `subconnector` property of a connector, if it has one.
+pub(crate) fn read_subconnector(
+    card: &impl control::Device,
+    connector_id: connector::Handle,
+) -> Option<Subconnector> {
+    let (info, value) = find_connector_property(card, connector_id, "subconnector")?;
+    let property::ValueType::Enum(values) = info.value_type() else {
+        return None;
+    };
+    let name = values
+        .get_value_from_raw_value(value)?
+        .name()
+        .to_str()
+        .ok()?;
+    Some(Subconnector::from_kernel_name(name))
+}
+
+/// Makes a DVI-I connector drive its digital signal through the `select subconnector`
+/// property, like the kernel's `D` force flag.
+///
+/// Returns false if the connector has no such property or setting it failed.
+pub(crate) fn select_digital_subconnector(
+    card: &impl control::Device,
+    connector_id: connector::Handle,
+) -> bool {
+    let Some((info, _)) = find_connector_property(card, connector_id, "select subconnector") else {
+        return false;
+    };
+    let property::ValueType::Enum(values) = info.value_type() else {
+        return false;
+    };
+    let (_, enums) = values.values();
+    let Some(digital) = enums.iter().find(|e| e.name().to_str() == Ok("DVI-D")) else {
+        return false;
+    };
+    card.set_property(connector_id, info.handle(), digital.value())
+        .is_ok()
+}
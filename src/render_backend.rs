@@ -0,0 +1,306 @@
+use std::any::Any;
+#[cfg(feature = "gbm")]
+use std::os::fd::FromRawFd;
+use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
+use std::sync::Arc;
+
+#[cfg(feature = "gbm")]
+use drm::buffer::DrmModifier;
+use drm::control::{connector, framebuffer};
+use thiserror::Error;
+
+use crate::capture::{CaptureError, CapturedFrame};
+#[cfg(feature = "gbm")]
+use crate::device::GpuDevice;
+#[cfg(feature = "gbm")]
+use crate::formats::add_scanout_framebuffer;
+use crate::formats::{PlaneFormats, ScanoutFormat};
+#[cfg(feature = "gbm")]
+use crate::gles_context::{GlesContext, GlesContextError};
+
+/// Errors that can occur while a render backend produces a frame
+#[derive(Debug, Error)]
+pub enum RenderBackendError {
+    #[error("IO Error: {0}")]
+    IOError(#[from] std::io::Error),
+    #[cfg(feature = "gbm")]
+    #[error("OpenGL ES error: {0}")]
+    GlesContextError(#[from] GlesContextError),
+    #[error("failed to create fence: {0}")]
+    Fence(String),
+    #[cfg(feature = "vulkan")]
+    #[error("Vulkan error: {0}")]
+    Vulkan(#[from] ash::vk::Result),
+    #[error("{0}")]
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// A finished frame, handed by a [`RenderBackend`] to the display
+pub struct BackendFrame {
+    framebuffer: Option<framebuffer::Handle>,
+    fence: Option<OwnedFd>,
+    /// GBM buffer behind the framebuffer, kept for captures and frame export
+    #[cfg(feature = "gbm")]
+    pub(crate) bo: Option<gbm::BufferObject<()>>,
+}
+
+impl BackendFrame {
+    /// A frame showing `framebuffer` on the primary plane. The display controller waits
+    /// for `fence` (a sync_file signaled when rendering finished) before scanning it out.
+    pub fn new(framebuffer: framebuffer::Handle, fence: Option<OwnedFd>) -> Self {
+        BackendFrame {
+            framebuffer: Some(framebuffer),
+            fence,
+            #[cfg(feature = "gbm")]
+            bo: None,
+        }
+    }
+
+    /// A frame of a virtual monitor, which isn't scanned out.
+    #[cfg(feature = "gbm")]
+    pub(crate) fn offscreen(bo: Option<gbm::BufferObject<()>>) -> Self {
+        BackendFrame {
+            framebuffer: None,
+            fence: None,
+            bo,
+        }
+    }
+
+    /// Returns the framebuffer to scan out, `None` for frames of virtual monitors.
+    pub fn framebuffer(&self) -> Option<framebuffer::Handle> {
+        self.framebuffer
+    }
+
+    /// Returns the fence the display controller waits for, if any.
+    pub fn fence(&self) -> Option<BorrowedFd<'_>> {
+        self.fence.as_ref().map(AsFd::as_fd)
+    }
+
+    pub(crate) fn take_fence(&mut self) -> Option<OwnedFd> {
+        self.fence.take()
+    }
+}
+
+/// Produces the buffers a monitor scans out
+///
+/// The default backend renders with OpenGL ES to a GBM surface (with the `gbm` feature).
+/// Others are picked with
+/// [`EasyDRMOptions::render_backend`](crate::EasyDRMOptions::render_backend): dumb buffers
+/// drawn by the CPU, Vulkan (with the `vulkan` feature), or a custom backend (Vulkan, buffers from another process...).
+/// EasyDRM puts the frame on the primary plane in its atomic commit, together with the
+/// mode set, cursor and overlay layers.
+///
+/// Get a monitor's backend with `Monitor::render_backend_mut()` and downcast it to the
+/// custom type through [`Any`].
+pub trait RenderBackend: Any {
+    /// Returns the pixel format and memory layout of the buffers.
+    fn scanout_format(&self) -> ScanoutFormat;
+
+    /// Finishes the frame drawn since the last call and returns it.
+    ///
+    /// Called by `EasyDRM::swap_buffers()` for monitors that were drawn. The framebuffer
+    /// must stay valid until a later frame replaced it on screen, which is the case once
+    /// the monitor can render again.
+    fn present(&mut self) -> Result<BackendFrame, RenderBackendError>;
+
+    /// Reads back the last presented frame, for `Monitor::capture_frame()`.
+    fn read_frame(&self) -> Result<CapturedFrame, CaptureError> {
+        Err(CaptureError::NothingPresented)
+    }
+
+    /// Returns true if a frame was finished outside of the monitor, e.g. on a render
+    /// thread, and should be presented by the next `EasyDRM::swap_buffers()` even though
+    /// the monitor wasn't drawn.
+    fn frame_ready(&mut self) -> bool {
+        false
+    }
+
+    /// Called when the last commit of the monitor reached the screen.
+    fn page_flip_complete(&mut self) {}
+}
+
+/// Data given to a custom render backend factory for each monitor
+pub struct RenderBackendRequest<'a> {
+    /// The DRM card, to allocate buffers and add framebuffers on.
+    pub drm_fd: BorrowedFd<'a>,
+    /// Connector of the monitor.
+    pub connector: connector::Handle,
+    /// Width of the buffers in pixels.
+    pub width: u16,
+    /// Height of the buffers in pixels.
+    pub height: u16,
+    /// Formats and modifiers the primary plane can scan out, if the driver lists them.
+    /// `None` for virtual monitors.
+    pub plane_formats: Option<&'a PlaneFormats>,
+}
+
+/// Creates the render backend of a monitor, see [`RenderBackendKind::Custom`]
+pub type RenderBackendFactory = Arc<
+    dyn Fn(&RenderBackendRequest<'_>) -> Result<Box<dyn RenderBackend>, RenderBackendError>
+        + Send
+        + Sync,
+>;
+
+/// Which [`RenderBackend`] monitors use
+#[derive(Clone, Default)]
+pub enum RenderBackendKind {
+    /// OpenGL ES on a GBM surface, or on a pbuffer when headless. Needs the `gbm`
+    /// feature.
+    #[cfg(feature = "gbm")]
+    #[default]
+    GbmEgl,
+    /// Dumb buffers drawn by the CPU with `Monitor::pixels_mut()`, without EGL or GBM.
+    /// The default without the `gbm` feature.
+    #[cfg_attr(not(feature = "gbm"), default)]
+    DumbBuffers,
+    /// Vulkan rendering to GBM buffers imported as images, see
+    /// [`VulkanBackend`](crate::VulkanBackend). Needs a DRM card.
+    #[cfg(feature = "vulkan")]
+    Vulkan(Arc<crate::VulkanDevice>),
+    /// Backends created by a factory, e.g. a Vulkan renderer. Needs a DRM card.
+    Custom(RenderBackendFactory),
+}
+
+impl std::fmt::Debug for RenderBackendKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            #[cfg(feature = "gbm")]
+            RenderBackendKind::GbmEgl => f.write_str("GbmEgl"),
+            RenderBackendKind::DumbBuffers => f.write_str("DumbBuffers"),
+            #[cfg(feature = "vulkan")]
+            RenderBackendKind::Vulkan(device) => f.debug_tuple("Vulkan").field(device).finish(),
+            RenderBackendKind::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+impl RenderBackendKind {
+    /// Returns true if monitors render with OpenGL ES.
+    #[cfg(feature = "gbm")]
+    pub(crate) fn needs_egl(&self) -> bool {
+        matches!(self, RenderBackendKind::GbmEgl)
+    }
+
+    pub(crate) fn needs_gbm(&self) -> bool {
+        match self {
+            #[cfg(feature = "gbm")]
+            RenderBackendKind::GbmEgl => true,
+            #[cfg(feature = "vulkan")]
+            RenderBackendKind::Vulkan(_) => true,
+            RenderBackendKind::DumbBuffers | RenderBackendKind::Custom(_) => false,
+        }
+    }
+}
+
+/// The default backend: OpenGL ES rendering to a GBM surface
+///
+/// Without a device (virtual monitors), frames aren't given a framebuffer or a fence.
+#[cfg(feature = "gbm")]
+pub(crate) struct GbmEglBackend {
+    device: Option<Arc<GpuDevice>>,
+    gles_context: GlesContext,
+    scanout_format: ScanoutFormat,
+    previous_sync: Option<*mut std::ffi::c_void>,
+}
+
+#[cfg(feature = "gbm")]
+impl GbmEglBackend {
+    pub(crate) fn new(
+        device: Arc<GpuDevice>,
+        gles_context: GlesContext,
+        scanout_format: ScanoutFormat,
+    ) -> Self {
+        GbmEglBackend {
+            device: Some(device),
+            gles_context,
+            scanout_format,
+            previous_sync: None,
+        }
+    }
+
+    /// Wraps the context of a virtual monitor, rendering to a GBM surface or a pbuffer.
+    pub(crate) fn offscreen(gles_context: GlesContext) -> Self {
+        GbmEglBackend {
+            device: None,
+            scanout_format: ScanoutFormat {
+                format: gles_context.format(),
+                modifier: None,
+                explicit_modifier: false,
+            },
+            gles_context,
+            previous_sync: None,
+        }
+    }
+
+    pub(crate) fn gles_context(&self) -> &GlesContext {
+        &self.gles_context
+    }
+
+    pub(crate) fn gles_context_mut(&mut self) -> &mut GlesContext {
+        &mut self.gles_context
+    }
+}
+
+#[cfg(feature = "gbm")]
+impl RenderBackend for GbmEglBackend {
+    fn scanout_format(&self) -> ScanoutFormat {
+        self.scanout_format
+    }
+
+    fn present(&mut self) -> Result<BackendFrame, RenderBackendError> {
+        // Pbuffers have no buffers to swap, their frame is read back instead
+        if self.gles_context.is_offscreen() {
+            return Ok(BackendFrame::offscreen(None));
+        }
+
+        // Get the new buffer object from GBM
+        let bo = self.gles_context.swap_buffers()?;
+        let Some(device) = &self.device else {
+            return Ok(BackendFrame::offscreen(Some(bo)));
+        };
+
+        // Create EGL fence for GPU->DRM synchronization, the previous one was consumed
+        // by the last commit
+        if let Some(old_sync) = self.previous_sync.take() {
+            self.gles_context.destroy_sync(old_sync);
+        }
+        let (fence_fd, sync) = self
+            .gles_context
+            .create_native_fence()
+            .map_err(RenderBackendError::Fence)?;
+        self.previous_sync = Some(sync);
+        let fence = unsafe { OwnedFd::from_raw_fd(fence_fd) };
+
+        // Create DRM framebuffer from the buffer object
+        let fb = add_scanout_framebuffer(
+            &device.card,
+            &bo,
+            24,
+            self.gles_context.has_explicit_modifier(),
+        )?;
+        self.scanout_format.modifier = Some(bo.modifier()).filter(|&m| m != DrmModifier::Invalid);
+
+        Ok(BackendFrame {
+            framebuffer: Some(fb),
+            fence: Some(fence),
+            bo: Some(bo),
+        })
+    }
+
+    fn read_frame(&self) -> Result<CapturedFrame, CaptureError> {
+        // GBM buffers are mapped by the monitor, which keeps them
+        if !self.gles_context.is_offscreen() {
+            return Err(CaptureError::NothingPresented);
+        }
+        self.gles_context.read_frame()
+    }
+}
+
+#[cfg(feature = "gbm")]
+impl Drop for GbmEglBackend {
+    fn drop(&mut self) {
+        if let Some(sync) = self.previous_sync.take() {
+            self.gles_context.destroy_sync(sync);
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+
+use drm::control::connector;
+use thiserror::Error;
+
+use crate::capture::{CaptureError, CapturedFrame};
+use crate::formats::ScanoutFormat;
+use crate::gles_context::GlesContextError;
+use crate::render_backend::{BackendFrame, GbmEglBackend, RenderBackend, RenderBackendError};
+
+/// Errors that can occur while rendering a monitor from its own thread
+#[derive(Debug, Error)]
+pub enum RenderThreadError {
+    #[error("no monitor is connected to this connector")]
+    NoSuchMonitor,
+    #[error("only monitors rendering with the default OpenGL ES backend have a render thread")]
+    Unsupported,
+    #[error("the monitor was removed")]
+    Disconnected,
+    #[error("the previous frame hasn't reached the screen yet")]
+    NotReady,
+    #[error("OpenGL ES error: {0}")]
+    GlesContextError(#[from] GlesContextError),
+    #[error("render backend error: {0}")]
+    RenderBackend(#[from] RenderBackendError),
+    #[error("failed to read back the frame: {0}")]
+    Capture(#[from] CaptureError),
+    #[error("IO Error: {0}")]
+    IOError(#[from] std::io::Error),
+}
+
+/// A frame finished on a render thread, sent to the main thread
+pub(crate) struct RenderedFrame {
+    frame: BackendFrame,
+    scanout_format: ScanoutFormat,
+    /// Pixels of a headless monitor's pbuffer, which only the render thread can read
+    capture: Option<CapturedFrame>,
+}
+
+/// Wakes `EasyDRM::poll_events()` when a render thread finished a frame
+pub(crate) struct FrameWaker(OwnedFd);
+
+impl FrameWaker {
+    pub(crate) fn new() -> std::io::Result<Self> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(FrameWaker(unsafe { OwnedFd::from_raw_fd(fd) }))
+    }
+
+    fn wake(&self) {
+        let one = 1u64;
+        unsafe { libc::write(self.0.as_raw_fd(), (&one as *const u64).cast(), 8) };
+    }
+
+    /// Resets the eventfd after `poll()` reported it readable.
+    pub(crate) fn drain(&self) {
+        let mut count = 0u64;
+        unsafe { libc::read(self.0.as_raw_fd(), (&mut count as *mut u64).cast(), 8) };
+    }
+
+    pub(crate) fn fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+/// The main thread's end of a render thread: presents the frames it sends and forwards
+/// page flips to it
+pub(crate) struct ThreadedBackend {
+    scanout_format: ScanoutFormat,
+    frames: Receiver<RenderedFrame>,
+    flips: Sender<()>,
+    ready: Option<RenderedFrame>,
+    /// A frame of the render thread was committed and its flip not forwarded yet.
+    /// Cursor and layer commits flip too, they mustn't release the thread's buffers.
+    presented: bool,
+    last_capture: Option<CapturedFrame>,
+}
+
+impl RenderBackend for ThreadedBackend {
+    fn scanout_format(&self) -> ScanoutFormat {
+        self.scanout_format
+    }
+
+    fn present(&mut self) -> Result<BackendFrame, RenderBackendError> {
+        self.frame_ready();
+        let rendered = self.ready.take().ok_or_else(|| {
+            RenderBackendError::Other("the render thread hasn't finished a frame".into())
+        })?;
+        self.scanout_format = rendered.scanout_format;
+        if let Some(capture) = rendered.capture {
+            self.last_capture = Some(capture);
+        }
+        self.presented = true;
+        Ok(rendered.frame)
+    }
+
+    fn read_frame(&self) -> Result<CapturedFrame, CaptureError> {
+        self.last_capture
+            .clone()
+            .ok_or(CaptureError::NothingPresented)
+    }
+
+    /// Keeps the newest frame, older ones were never shown and are dropped.
+    fn frame_ready(&mut self) -> bool {
+        while let Ok(rendered) = self.frames.try_recv() {
+            self.ready = Some(rendered);
+        }
+        self.ready.is_some()
+    }
+
+    fn page_flip_complete(&mut self) {
+        if std::mem::take(&mut self.presented) {
+            // The render thread may be gone already, then there's nobody to tell
+            let _ = self.flips.send(());
+        }
+    }
+}
+
+/// The render thread's ends of the channels to a [`ThreadedBackend`]
+pub(crate) struct RenderThreadChannels {
+    frames: Sender<RenderedFrame>,
+    flips: Receiver<()>,
+}
+
+/// Creates the backend a monitor keeps on the main thread while its context is on a
+/// render thread. `can_render` is false while a frame of the main thread is in flight.
+pub(crate) fn channel(
+    scanout_format: ScanoutFormat,
+    can_render: bool,
+) -> (ThreadedBackend, RenderThreadChannels) {
+    let (frame_sender, frames) = std::sync::mpsc::channel();
+    let (flip_sender, flips) = std::sync::mpsc::channel();
+    let threaded = ThreadedBackend {
+        scanout_format,
+        frames,
+        flips: flip_sender,
+        ready: None,
+        // The flip of that frame unblocks the render thread
+        presented: !can_render,
+        last_capture: None,
+    };
+    let channels = RenderThreadChannels {
+        frames: frame_sender,
+        flips,
+    };
+    (threaded, channels)
+}
+
+/// A monitor's OpenGL ES context, moved out of the monitor to render from another thread
+///
+/// Get one with `EasyDRM::take_render_thread()` and move it to the thread. The context is
+/// only reachable through [`make_current`](Self::make_current), which binds it to the
+/// calling thread until the returned [`CurrentRenderThread`] is dropped; that guard
+/// can't leave the thread. Finished frames go back to the main thread, whose
+/// `EasyDRM::swap_buffers()` commits them together with the other monitors in one atomic
+/// commit, and page flips come back over a channel.
+///
+/// The main thread keeps calling `poll_events()` and `swap_buffers()`; `poll_events()`
+/// also returns when a render thread finished a frame. Monitors without a cursor plane
+/// don't get a software cursor in this mode, and only headless monitors can be captured.
+///
+/// # Example
+///
+/// ```ignore
+/// let render_thread = easydrm.take_render_thread(connector)?;
+/// std::thread::spawn(move || {
+///     let mut render_thread = render_thread;
+///     let mut current = render_thread.make_current().unwrap();
+///     // Returns an error once the monitor was unplugged
+///     while current.wait_for_flip().is_ok() {
+///         draw(current.gl());
+///         current.swap_buffers().unwrap();
+///     }
+/// });
+/// loop {
+///     easydrm.poll_events()?;
+///     easydrm.swap_buffers()?;
+/// }
+/// ```
+pub struct RenderThread {
+    connector: connector::Handle,
+    backend: Box<GbmEglBackend>,
+    frames: Sender<RenderedFrame>,
+    flips: Receiver<()>,
+    waker: Arc<FrameWaker>,
+    /// Presented buffers, released once a later frame replaced them on screen
+    in_flight: VecDeque<gbm::BufferObject<()>>,
+    can_render: bool,
+}
+
+// The context isn't current on any thread while a `RenderThread` is moved: the monitor
+// releases it before handing it over and `CurrentRenderThread` releases it on drop. The
+// guard is `!Send`, so GL calls stay on the thread that made the context current.
+unsafe impl Send for RenderThread {}
+
+impl RenderThread {
+    /// Wraps a monitor's backend, whose context must not be current on any thread.
+    pub(crate) fn new(
+        connector: connector::Handle,
+        backend: Box<GbmEglBackend>,
+        channels: RenderThreadChannels,
+        can_render: bool,
+        waker: Arc<FrameWaker>,
+    ) -> Self {
+        RenderThread {
+            connector,
+            backend,
+            frames: channels.frames,
+            flips: channels.flips,
+            waker,
+            in_flight: VecDeque::new(),
+            can_render,
+        }
+    }
+
+    /// Returns the connector of the monitor this thread renders.
+    pub fn connector_id(&self) -> connector::Handle {
+        self.connector
+    }
+
+    /// Makes the monitor's context current on the calling thread until the returned
+    /// guard is dropped.
+    pub fn make_current(&mut self) -> Result<CurrentRenderThread<'_>, RenderThreadError> {
+        self.backend.gles_context().make_current()?;
+        Ok(CurrentRenderThread {
+            thread: self,
+            _not_send: PhantomData,
+        })
+    }
+}
+
+/// A [`RenderThread`] whose context is current on this thread
+pub struct CurrentRenderThread<'a> {
+    thread: &'a mut RenderThread,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl CurrentRenderThread<'_> {
+    /// Gets a reference to the OpenGL ES bindings.
+    pub fn gl(&self) -> &crate::gl::Gles2 {
+        self.thread.backend.gles_context().gl()
+    }
+
+    /// Gets the address of an OpenGL function, for third-party loaders.
+    pub fn get_proc_address(&self, symbol: &str) -> *const std::ffi::c_void {
+        self.thread.backend.gles_context().get_proc_address(symbol)
+    }
+
+    /// Returns true if the last frame reached the screen and a new one can be rendered,
+    /// without blocking.
+    pub fn can_render(&mut self) -> Result<bool, RenderThreadError> {
+        while !self.thread.can_render {
+            match self.thread.flips.try_recv() {
+                Ok(()) => self.flip_complete(),
+                Err(TryRecvError::Empty) => return Ok(false),
+                Err(TryRecvError::Disconnected) => return Err(RenderThreadError::Disconnected),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Blocks until the last frame reached the screen. Fails with
+    /// [`RenderThreadError::Disconnected`] once the monitor was removed.
+    pub fn wait_for_flip(&mut self) -> Result<(), RenderThreadError> {
+        while !self.thread.can_render {
+            self.thread
+                .flips
+                .recv()
+                .map_err(|_| RenderThreadError::Disconnected)?;
+            self.flip_complete();
+        }
+        Ok(())
+    }
+
+    fn flip_complete(&mut self) {
+        // The newest buffer is on screen, the ones before it were replaced
+        while self.thread.in_flight.len() > 1 {
+            self.thread.in_flight.pop_front();
+        }
+        self.thread.can_render = true;
+    }
+
+    /// Finishes the frame and sends it to the main thread, which presents it with its
+    /// next `EasyDRM::swap_buffers()`.
+    ///
+    /// Fails with [`RenderThreadError::NotReady`] if the previous frame hasn't reached
+    /// the screen, see [`wait_for_flip`](Self::wait_for_flip).
+    pub fn swap_buffers(&mut self) -> Result<(), RenderThreadError> {
+        if !self.can_render()? {
+            return Err(RenderThreadError::NotReady);
+        }
+        let thread = &mut *self.thread;
+        let mut frame = thread.backend.present()?;
+        let capture = match thread.backend.gles_context().is_offscreen() {
+            true => Some(thread.backend.read_frame()?),
+            false => None,
+        };
+        thread.in_flight.extend(frame.bo.take());
+        thread
+            .frames
+            .send(RenderedFrame {
+                frame,
+                scanout_format: thread.backend.scanout_format(),
+                capture,
+            })
+            .map_err(|_| RenderThreadError::Disconnected)?;
+        thread.waker.wake();
+        thread.can_render = false;
+        Ok(())
+    }
+}
+
+impl Drop for CurrentRenderThread<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.thread.backend.gles_context().release_current() {
+            eprintln!(
+                "Warning: Failed to release the render thread's context: {}",
+                e
+            );
+        }
+    }
+}
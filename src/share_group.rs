@@ -0,0 +1,141 @@
+use std::ffi::CString;
+
+use gbm::Device as GbmDevice;
+use glutin::api::egl;
+use glutin::prelude::*;
+
+use crate::gl_config::GlConfig;
+use crate::gles_context::{GlesContextError, device_display, find_egl_config, gbm_display};
+
+/// The EGL display and context every shared monitor context is created against
+///
+/// The root context is never made current, it only keeps the share group alive while
+/// monitors come and go.
+pub(crate) struct ShareGroup {
+    display: egl::display::Display,
+    config: egl::config::Config,
+    gl_config: GlConfig,
+    root: egl::context::NotCurrentContext,
+}
+
+impl ShareGroup {
+    /// Creates the share group of monitors rendering to GBM surfaces.
+    pub(crate) fn new(
+        gbm_device: &GbmDevice<std::fs::File>,
+        gl_config: &GlConfig,
+    ) -> Result<Self, GlesContextError> {
+        let display = gbm_display(gbm_device)?;
+        let config = find_egl_config(&display, Some(gl_config.scanout_format()), gl_config)?;
+        Self::with_config(display, config, gl_config)
+    }
+
+    /// Creates the share group of headless monitors rendering to pbuffers.
+    pub(crate) fn headless(
+        egl_device: &egl::device::Device,
+        gl_config: &GlConfig,
+    ) -> Result<Self, GlesContextError> {
+        let display = device_display(egl_device)?;
+        let config = find_egl_config(&display, None, gl_config)?;
+        Self::with_config(display, config, gl_config)
+    }
+
+    fn with_config(
+        display: egl::display::Display,
+        config: egl::config::Config,
+        gl_config: &GlConfig,
+    ) -> Result<Self, GlesContextError> {
+        let root =
+            unsafe { display.create_context(&config, &gl_config.context_attributes(None, None)) }
+                .map_err(|e| GlesContextError::EglContextCreationFailed(e.to_string()))?;
+        Ok(ShareGroup {
+            display,
+            config,
+            gl_config: *gl_config,
+            root,
+        })
+    }
+
+    pub(crate) fn display(&self) -> &egl::display::Display {
+        &self.display
+    }
+
+    pub(crate) fn root(&self) -> &egl::context::NotCurrentContext {
+        &self.root
+    }
+
+    /// Creates a context without a surface in the group, for loading resources.
+    pub(crate) fn resource_context(&self) -> Result<ResourceContext, GlesContextError> {
+        let context = unsafe {
+            self.display.create_context(
+                &self.config,
+                &self.gl_config.context_attributes(None, Some(&self.root)),
+            )
+        }
+        .map_err(|e| GlesContextError::EglContextCreationFailed(e.to_string()))?
+        .treat_as_possibly_current();
+        let gl = crate::gl::Gles2::load_with(|symbol| {
+            let c_symbol = CString::new(symbol).unwrap();
+            self.display.get_proc_address(&c_symbol)
+        });
+        Ok(ResourceContext {
+            display: self.display.clone(),
+            context,
+            gl,
+        })
+    }
+}
+
+/// An offscreen OpenGL ES context sharing textures, buffers and shaders with every
+/// monitor, for uploading assets from a background thread
+///
+/// Get one with `EasyDRM::create_resource_context()` once
+/// [`shared_gl_contexts`](crate::EasyDRMOptions::shared_gl_contexts) is enabled, move
+/// it to the loading thread and make it current there. It has no surface, so render
+/// to framebuffer objects. Objects created here are visible to monitors once the upload
+/// is finished: call `glFinish()` (or wait on a fence) before handing them over.
+///
+/// # Example
+///
+/// ```ignore
+/// let loader = easydrm.create_resource_context()?;
+/// std::thread::spawn(move || {
+///     loader.make_current().unwrap();
+///     let texture = upload_texture(loader.gl(), &image);
+///     unsafe { loader.gl().Finish() };
+///     sender.send(texture).unwrap();
+/// });
+/// ```
+pub struct ResourceContext {
+    display: egl::display::Display,
+    context: egl::context::PossiblyCurrentContext,
+    gl: crate::gl::Gles2,
+}
+
+// The context is created without being current. EGL refuses to make it current on a
+// second thread while another one uses it, so moving it is sound.
+unsafe impl Send for ResourceContext {}
+
+impl ResourceContext {
+    /// Makes this context current on the calling thread, without a surface.
+    pub fn make_current(&self) -> Result<(), GlesContextError> {
+        self.context
+            .make_current_surfaceless()
+            .map_err(|_| GlesContextError::MakeCurrentFailed)
+    }
+
+    /// Returns true if this context is current on the calling thread.
+    pub fn is_current(&self) -> bool {
+        self.context.is_current()
+    }
+
+    /// Gets a reference to the OpenGL ES bindings.
+    pub fn gl(&self) -> &crate::gl::Gles2 {
+        &self.gl
+    }
+
+    /// Gets the address of an OpenGL function, for third-party loaders.
+    pub fn get_proc_address(&self, symbol: &str) -> *const std::ffi::c_void {
+        let c_symbol = CString::new(symbol).unwrap();
+        self.display.get_proc_address(&c_symbol)
+    }
+}
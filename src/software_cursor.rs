@@ -0,0 +1,506 @@
+#[cfg(feature = "gbm")]
+use std::ffi::CString;
+
+#[cfg(feature = "gbm")]
+use crate::config::Rotation;
+use crate::cursor::CursorError;
+#[cfg(feature = "gbm")]
+use crate::gl::{self, Gles2, types::*};
+#[cfg(feature = "gbm")]
+use crate::gles_context::{ContextToken, GlesContext};
+
+/// Largest image accepted by the composited cursor
+const MAX_SIZE: u32 = 256;
+
+/// Places the cursor in mode coordinates, then maps them to the unrotated surface
+#[cfg(feature = "gbm")]
+const VERTEX_SHADER: &str = "#version 100
+attribute vec2 a_corner;
+uniform vec4 u_rect;
+uniform mat3 u_mode_to_surface;
+uniform vec2 u_viewport;
+varying vec2 v_uv;
+void main() {
+    vec2 pos = (u_mode_to_surface * vec3(u_rect.xy + a_corner * u_rect.zw, 1.0)).xy;
+    v_uv = a_corner;
+    gl_Position = vec4(pos.x / u_viewport.x * 2.0 - 1.0, 1.0 - pos.y / u_viewport.y * 2.0, 0.0, 1.0);
+}
+";
+
+#[cfg(feature = "gbm")]
+const FRAGMENT_SHADER: &str = "#version 100
+precision mediump float;
+uniform sampler2D u_image;
+varying vec2 v_uv;
+void main() {
+    gl_FragColor = texture2D(u_image, v_uv);
+}
+";
+
+/// GL objects used to draw the cursor, created on first use in the monitor's context
+///
+/// They're deleted on drop if that context is current, and leaked otherwise.
+#[cfg(feature = "gbm")]
+struct CursorRenderer {
+    gl: Gles2,
+    context: ContextToken,
+    program: GLuint,
+    texture: GLuint,
+    vertex_buffer: GLuint,
+    rect_location: GLint,
+    transform_location: GLint,
+    viewport_location: GLint,
+    image_location: GLint,
+}
+
+/// Cursor composited into the frame with a small GL pass, for monitors without a
+/// usable cursor or overlay plane.
+///
+/// Since it's part of the frame, the cursor only moves when the monitor is redrawn.
+pub(crate) struct SoftwareCursor {
+    /// RGBA8 pixels, ready for `glTexImage2D`
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+    hotspot: (i32, i32),
+    position: (i32, i32),
+    visible: bool,
+    texture_stale: bool,
+    dirty: bool,
+    #[cfg(feature = "gbm")]
+    renderer: Option<Box<CursorRenderer>>,
+    #[cfg(feature = "gbm")]
+    renderer_failed: bool,
+}
+
+impl SoftwareCursor {
+    pub(crate) fn new() -> Self {
+        SoftwareCursor {
+            pixels: Vec::new(),
+            width: 0,
+            height: 0,
+            hotspot: (0, 0),
+            position: (0, 0),
+            visible: false,
+            texture_stale: false,
+            dirty: false,
+            #[cfg(feature = "gbm")]
+            renderer: None,
+            #[cfg(feature = "gbm")]
+            renderer_failed: false,
+        }
+    }
+
+    pub(crate) fn size(&self) -> (u32, u32) {
+        (MAX_SIZE, MAX_SIZE)
+    }
+
+    pub(crate) fn position(&self) -> (i32, i32) {
+        self.position
+    }
+
+    pub(crate) fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Returns true if the cursor changed since the last frame was drawn.
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub(crate) fn set_image(
+        &mut self,
+        argb: &[u32],
+        width: u32,
+        height: u32,
+        hotspot: (i32, i32),
+    ) -> Result<(), CursorError> {
+        if width > MAX_SIZE || height > MAX_SIZE {
+            return Err(CursorError::ImageTooLarge {
+                width,
+                height,
+                max_width: MAX_SIZE,
+                max_height: MAX_SIZE,
+            });
+        }
+        if argb.len() != width as usize * height as usize {
+            return Err(CursorError::InvalidImage {
+                len: argb.len(),
+                width,
+                height,
+            });
+        }
+
+        self.pixels = argb
+            .iter()
+            .flat_map(|pixel| {
+                let [a, r, g, b] = pixel.to_be_bytes();
+                [r, g, b, a]
+            })
+            .collect();
+        self.width = width;
+        self.height = height;
+        self.hotspot = hotspot;
+        self.visible = true;
+        self.texture_stale = true;
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub(crate) fn move_to(&mut self, x: i32, y: i32) {
+        if self.position != (x, y) {
+            self.position = (x, y);
+            self.dirty |= self.visible;
+        }
+    }
+
+    pub(crate) fn hide(&mut self) {
+        if self.visible {
+            self.visible = false;
+            self.dirty = true;
+        }
+    }
+
+    /// Draws the cursor on top of the current frame. Its position is in mode
+    /// coordinates, and it's mapped through `rotation` to the surface (which has swapped
+    /// dimensions for 90° and 270°) so it shows upright where a cursor plane would put it.
+    ///
+    /// Must be called with the monitor's context current, right before the buffer swap.
+    /// All GL state touched here is restored afterwards.
+    #[cfg(feature = "gbm")]
+    pub(crate) fn draw(
+        &mut self,
+        gles_context: &GlesContext,
+        surface_size: (u32, u32),
+        rotation: Rotation,
+    ) {
+        self.dirty = false;
+        if !self.visible || self.width == 0 || self.height == 0 || self.renderer_failed {
+            return;
+        }
+
+        let gl = gles_context.gl();
+        unsafe {
+            let saved = SavedState::save(gl);
+            if self.renderer.is_none() {
+                match CursorRenderer::new(gles_context) {
+                    Ok(renderer) => self.renderer = Some(Box::new(renderer)),
+                    Err(e) => {
+                        eprintln!("Warning: Failed to set up the software cursor: {}", e);
+                        self.renderer_failed = true;
+                        saved.restore(gl);
+                        return;
+                    }
+                }
+            }
+            let Some(renderer) = &self.renderer else {
+                return;
+            };
+
+            gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl.Viewport(0, 0, surface_size.0 as GLsizei, surface_size.1 as GLsizei);
+            gl.Disable(gl::SCISSOR_TEST);
+            gl.Disable(gl::DEPTH_TEST);
+            gl.Disable(gl::STENCIL_TEST);
+            gl.Disable(gl::CULL_FACE);
+            gl.Enable(gl::BLEND);
+            gl.BlendEquation(gl::FUNC_ADD);
+            gl.BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+
+            gl.UseProgram(renderer.program);
+            gl.ActiveTexture(gl::TEXTURE0);
+            gl.BindTexture(gl::TEXTURE_2D, renderer.texture);
+            if self.texture_stale {
+                gl.TexImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    gl::RGBA as GLint,
+                    self.width as GLsizei,
+                    self.height as GLsizei,
+                    0,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    self.pixels.as_ptr() as *const _,
+                );
+                self.texture_stale = false;
+            }
+
+            gl.Uniform4f(
+                renderer.rect_location,
+                (self.position.0 - self.hotspot.0) as f32,
+                (self.position.1 - self.hotspot.1) as f32,
+                self.width as f32,
+                self.height as f32,
+            );
+            gl.UniformMatrix3fv(
+                renderer.transform_location,
+                1,
+                gl::FALSE,
+                mode_to_surface(rotation, surface_size).as_ptr(),
+            );
+            gl.Uniform2f(
+                renderer.viewport_location,
+                surface_size.0 as f32,
+                surface_size.1 as f32,
+            );
+            gl.Uniform1i(renderer.image_location, 0);
+
+            gl.BindBuffer(gl::ARRAY_BUFFER, renderer.vertex_buffer);
+            gl.EnableVertexAttribArray(0);
+            gl.VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, 0, std::ptr::null());
+            gl.DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+
+            saved.restore(gl);
+        }
+    }
+
+    /// Deletes the GL objects of the cursor, with the monitor's context current.
+    #[cfg(feature = "gbm")]
+    pub(crate) fn release_renderer(&mut self) {
+        self.renderer = None;
+    }
+}
+
+/// Returns the column-major matrix mapping mode coordinates to the surface of a monitor
+/// whose primary plane applies `rotation` (counter-clockwise, like `DRM_MODE_ROTATE_*`).
+#[cfg(feature = "gbm")]
+fn mode_to_surface(rotation: Rotation, (surface_width, surface_height): (u32, u32)) -> [f32; 9] {
+    let (width, height) = (surface_width as f32, surface_height as f32);
+    match rotation {
+        Rotation::Normal => [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        // (x, y) -> (surface_width - y, x)
+        Rotation::Rotate90 => [0.0, 1.0, 0.0, -1.0, 0.0, 0.0, width, 0.0, 1.0],
+        // (x, y) -> (surface_width - x, surface_height - y)
+        Rotation::Rotate180 => [-1.0, 0.0, 0.0, 0.0, -1.0, 0.0, width, height, 1.0],
+        // (x, y) -> (y, surface_height - x)
+        Rotation::Rotate270 => [0.0, -1.0, 0.0, 1.0, 0.0, 0.0, 0.0, height, 1.0],
+    }
+}
+
+#[cfg(feature = "gbm")]
+impl CursorRenderer {
+    unsafe fn new(gles_context: &GlesContext) -> Result<Self, String> {
+        let gl = gles_context.gl();
+        unsafe {
+            let vertex_shader = compile_shader(gl, gl::VERTEX_SHADER, VERTEX_SHADER)?;
+            let fragment_shader = compile_shader(gl, gl::FRAGMENT_SHADER, FRAGMENT_SHADER)
+                .inspect_err(|_| gl.DeleteShader(vertex_shader))?;
+
+            let program = gl.CreateProgram();
+            gl.AttachShader(program, vertex_shader);
+            gl.AttachShader(program, fragment_shader);
+            gl.BindAttribLocation(program, 0, c"a_corner".as_ptr());
+            gl.LinkProgram(program);
+            gl.DeleteShader(vertex_shader);
+            gl.DeleteShader(fragment_shader);
+            let mut linked = 0;
+            gl.GetProgramiv(program, gl::LINK_STATUS, &mut linked);
+            if linked == 0 {
+                gl.DeleteProgram(program);
+                return Err("failed to link cursor shader".to_string());
+            }
+
+            let mut texture = 0;
+            gl.GenTextures(1, &mut texture);
+            gl.BindTexture(gl::TEXTURE_2D, texture);
+            for (param, value) in [
+                (gl::TEXTURE_MIN_FILTER, gl::NEAREST),
+                (gl::TEXTURE_MAG_FILTER, gl::NEAREST),
+                (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+                (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+            ] {
+                gl.TexParameteri(gl::TEXTURE_2D, param, value as GLint);
+            }
+
+            let corners: [f32; 8] = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+            let mut vertex_buffer = 0;
+            gl.GenBuffers(1, &mut vertex_buffer);
+            gl.BindBuffer(gl::ARRAY_BUFFER, vertex_buffer);
+            gl.BufferData(
+                gl::ARRAY_BUFFER,
+                std::mem::size_of_val(&corners) as GLsizeiptr,
+                corners.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+
+            Ok(CursorRenderer {
+                gl: gl.clone(),
+                context: gles_context.token(),
+                program,
+                texture,
+                vertex_buffer,
+                rect_location: gl.GetUniformLocation(program, c"u_rect".as_ptr()),
+                transform_location: gl.GetUniformLocation(program, c"u_mode_to_surface".as_ptr()),
+                viewport_location: gl.GetUniformLocation(program, c"u_viewport".as_ptr()),
+                image_location: gl.GetUniformLocation(program, c"u_image".as_ptr()),
+            })
+        }
+    }
+}
+
+#[cfg(feature = "gbm")]
+impl Drop for CursorRenderer {
+    fn drop(&mut self) {
+        if !self.context.is_current() {
+            eprintln!("Warning: Software cursor dropped without its context current, leaking it");
+            return;
+        }
+        unsafe {
+            self.gl.DeleteProgram(self.program);
+            self.gl.DeleteTextures(1, &self.texture);
+            self.gl.DeleteBuffers(1, &self.vertex_buffer);
+        }
+    }
+}
+
+#[cfg(feature = "gbm")]
+unsafe fn compile_shader(gl: &Gles2, kind: GLenum, source: &str) -> Result<GLuint, String> {
+    unsafe {
+        let shader = gl.CreateShader(kind);
+        let source = CString::new(source).expect("shader source contains a NUL byte");
+        gl.ShaderSource(shader, 1, &source.as_ptr(), std::ptr::null());
+        gl.CompileShader(shader);
+        let mut compiled = 0;
+        gl.GetShaderiv(shader, gl::COMPILE_STATUS, &mut compiled);
+        if compiled == 0 {
+            let mut log = vec![0u8; 1024];
+            let mut len = 0;
+            gl.GetShaderInfoLog(
+                shader,
+                log.len() as GLsizei,
+                &mut len,
+                log.as_mut_ptr() as *mut _,
+            );
+            gl.DeleteShader(shader);
+            log.truncate(len.max(0) as usize);
+            return Err(format!(
+                "failed to compile cursor shader: {}",
+                String::from_utf8_lossy(&log)
+            ));
+        }
+        Ok(shader)
+    }
+}
+
+/// GL state changed by the cursor pass, so the application's state is left untouched
+#[cfg(feature = "gbm")]
+struct SavedState {
+    program: GLint,
+    framebuffer: GLint,
+    array_buffer: GLint,
+    active_texture: GLint,
+    texture: GLint,
+    viewport: [GLint; 4],
+    capabilities: [(GLenum, bool); 5],
+    blend_func: [GLint; 4],
+    blend_equation: [GLint; 2],
+    attrib_enabled: GLint,
+    attrib_size: GLint,
+    attrib_type: GLint,
+    attrib_normalized: GLint,
+    attrib_stride: GLint,
+    attrib_buffer: GLint,
+    attrib_pointer: *mut std::ffi::c_void,
+}
+
+#[cfg(feature = "gbm")]
+impl SavedState {
+    unsafe fn save(gl: &Gles2) -> Self {
+        unsafe {
+            let get = |pname| {
+                let mut value = 0;
+                gl.GetIntegerv(pname, &mut value);
+                value
+            };
+            let get_attrib = |pname| {
+                let mut value = 0;
+                gl.GetVertexAttribiv(0, pname, &mut value);
+                value
+            };
+            let mut viewport = [0; 4];
+            gl.GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            let active_texture = get(gl::ACTIVE_TEXTURE);
+            gl.ActiveTexture(gl::TEXTURE0);
+            let mut attrib_pointer: *mut std::ffi::c_void = std::ptr::null_mut();
+            gl.GetVertexAttribPointerv(
+                0,
+                gl::VERTEX_ATTRIB_ARRAY_POINTER,
+                (&raw mut attrib_pointer).cast_const(),
+            );
+
+            SavedState {
+                program: get(gl::CURRENT_PROGRAM),
+                framebuffer: get(gl::FRAMEBUFFER_BINDING),
+                array_buffer: get(gl::ARRAY_BUFFER_BINDING),
+                active_texture,
+                texture: get(gl::TEXTURE_BINDING_2D),
+                viewport,
+                capabilities: [
+                    gl::BLEND,
+                    gl::SCISSOR_TEST,
+                    gl::DEPTH_TEST,
+                    gl::STENCIL_TEST,
+                    gl::CULL_FACE,
+                ]
+                .map(|cap| (cap, gl.IsEnabled(cap) == gl::TRUE)),
+                blend_func: [
+                    get(gl::BLEND_SRC_RGB),
+                    get(gl::BLEND_DST_RGB),
+                    get(gl::BLEND_SRC_ALPHA),
+                    get(gl::BLEND_DST_ALPHA),
+                ],
+                blend_equation: [get(gl::BLEND_EQUATION_RGB), get(gl::BLEND_EQUATION_ALPHA)],
+                attrib_enabled: get_attrib(gl::VERTEX_ATTRIB_ARRAY_ENABLED),
+                attrib_size: get_attrib(gl::VERTEX_ATTRIB_ARRAY_SIZE),
+                attrib_type: get_attrib(gl::VERTEX_ATTRIB_ARRAY_TYPE),
+                attrib_normalized: get_attrib(gl::VERTEX_ATTRIB_ARRAY_NORMALIZED),
+                attrib_stride: get_attrib(gl::VERTEX_ATTRIB_ARRAY_STRIDE),
+                attrib_buffer: get_attrib(gl::VERTEX_ATTRIB_ARRAY_BUFFER_BINDING),
+                attrib_pointer,
+            }
+        }
+    }
+
+    unsafe fn restore(&self, gl: &Gles2) {
+        unsafe {
+            gl.BindBuffer(gl::ARRAY_BUFFER, self.attrib_buffer as GLuint);
+            gl.VertexAttribPointer(
+                0,
+                self.attrib_size,
+                self.attrib_type as GLenum,
+                self.attrib_normalized as GLboolean,
+                self.attrib_stride,
+                self.attrib_pointer,
+            );
+            if self.attrib_enabled == 0 {
+                gl.DisableVertexAttribArray(0);
+            }
+            gl.BindBuffer(gl::ARRAY_BUFFER, self.array_buffer as GLuint);
+
+            gl.BindTexture(gl::TEXTURE_2D, self.texture as GLuint);
+            gl.ActiveTexture(self.active_texture as GLenum);
+            gl.UseProgram(self.program as GLuint);
+            gl.BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer as GLuint);
+            let [x, y, width, height] = self.viewport;
+            gl.Viewport(x, y, width, height);
+            for (cap, enabled) in self.capabilities {
+                if enabled {
+                    gl.Enable(cap);
+                } else {
+                    gl.Disable(cap);
+                }
+            }
+            let [src_rgb, dst_rgb, src_alpha, dst_alpha] = self.blend_func;
+            gl.BlendFuncSeparate(
+                src_rgb as GLenum,
+                dst_rgb as GLenum,
+                src_alpha as GLenum,
+                dst_alpha as GLenum,
+            );
+            gl.BlendEquationSeparate(
+                self.blend_equation[0] as GLenum,
+                self.blend_equation[1] as GLenum,
+            );
+        }
+    }
+}
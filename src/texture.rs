@@ -0,0 +1,234 @@
+use drm::buffer::{DrmFourcc, DrmModifier};
+use thiserror::Error;
+
+use crate::dmabuf::{DmaBufDescriptor, DmaBufError};
+use crate::egl;
+use crate::gl;
+use crate::gles_context::{ContextToken, GlesContext, GlesContextError};
+
+/// Errors that can occur while importing a dma-buf as a texture
+#[derive(Debug, Error)]
+pub enum TextureError {
+    #[error("invalid dma-buf: {0}")]
+    DmaBuf(#[from] DmaBufError),
+    #[error("failed to make the context current: {0}")]
+    GlesContextError(#[from] GlesContextError),
+    #[error("{0} is not supported by the driver")]
+    ExtensionMissing(&'static str),
+    #[error("eglCreateImageKHR failed with EGL error {0:#x}")]
+    ImageCreationFailed(i32),
+}
+
+/// An OpenGL ES texture sampling a dma-buf through an `EGLImage`, without copying it
+///
+/// Get one with `Monitor::import_dmabuf_texture()`. Multi-plane and YUV images are bound
+/// to `GL_TEXTURE_EXTERNAL_OES` and have to be sampled with a `samplerExternalOES` in
+/// shaders using `GL_OES_EGL_image_external`; the driver converts them to RGB. Other
+/// images are regular `GL_TEXTURE_2D` textures.
+///
+/// The producer must not modify the dma-buf while the texture is used. Dropping the
+/// texture deletes it and destroys the `EGLImage`; drop it while the monitor's context
+/// is current, otherwise the GL texture name is leaked.
+pub struct Texture {
+    gl: gl::Gles2,
+    egl: egl::Egl,
+    egl_display: egl::types::EGLDisplay,
+    context: ContextToken,
+    image: egl::types::EGLImageKHR,
+    id: gl::types::GLuint,
+    target: gl::types::GLenum,
+    size: (u32, u32),
+    format: DrmFourcc,
+}
+
+impl Texture {
+    /// Imports `desc` as a texture of `gles_context`, which is made current.
+    pub(crate) fn import(
+        gles_context: &GlesContext,
+        desc: &DmaBufDescriptor,
+    ) -> Result<Self, TextureError> {
+        desc.validate()?;
+        let egl = gles_context.egl_extensions();
+        let egl_display = gles_context
+            .raw_display()
+            .ok_or(TextureError::ExtensionMissing("EGL_KHR_image_base"))?;
+        if !egl.CreateImageKHR.is_loaded() || !egl.DestroyImageKHR.is_loaded() {
+            return Err(TextureError::ExtensionMissing("EGL_KHR_image_base"));
+        }
+        let gl = gles_context.gl();
+        if !gl.EGLImageTargetTexture2DOES.is_loaded() {
+            return Err(TextureError::ExtensionMissing("GL_OES_EGL_image"));
+        }
+
+        let attributes = image_attributes(desc);
+        gles_context.make_current()?;
+        let image = unsafe {
+            egl.CreateImageKHR(
+                egl_display,
+                egl::NO_CONTEXT,
+                egl::LINUX_DMA_BUF_EXT,
+                std::ptr::null(),
+                attributes.as_ptr(),
+            )
+        };
+        if image == egl::NO_IMAGE_KHR {
+            return Err(TextureError::ImageCreationFailed(unsafe { egl.GetError() }));
+        }
+
+        let target = if needs_external_target(desc) {
+            gl::TEXTURE_EXTERNAL_OES
+        } else {
+            gl::TEXTURE_2D
+        };
+        let mut id = 0;
+        unsafe {
+            gl.GenTextures(1, &mut id);
+            gl.BindTexture(target, id);
+            gl.TexParameteri(target, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl.TexParameteri(target, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl.TexParameteri(target, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl.TexParameteri(target, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl.EGLImageTargetTexture2DOES(target, image as gl::types::GLeglImageOES);
+            gl.BindTexture(target, 0);
+        }
+
+        Ok(Texture {
+            gl: gl.clone(),
+            context: gles_context.token(),
+            egl,
+            egl_display,
+            image,
+            id,
+            target,
+            size: (desc.width, desc.height),
+            format: desc.format,
+        })
+    }
+
+    /// Returns the OpenGL texture name, to pass to `glBindTexture`.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the texture target, `GL_TEXTURE_2D` or `GL_TEXTURE_EXTERNAL_OES`.
+    pub fn target(&self) -> u32 {
+        self.target
+    }
+
+    /// Returns true if the texture is bound to `GL_TEXTURE_EXTERNAL_OES`.
+    pub fn is_external(&self) -> bool {
+        self.target == gl::TEXTURE_EXTERNAL_OES
+    }
+
+    /// Returns the size of the image as (width, height).
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Returns the pixel format of the image.
+    pub fn format(&self) -> DrmFourcc {
+        self.format
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            if self.context.is_current() {
+                self.gl.DeleteTextures(1, &self.id);
+            } else {
+                eprintln!(
+                    "Warning: Texture {} dropped without its context current, leaking it",
+                    self.id
+                );
+            }
+            self.egl.DestroyImageKHR(self.egl_display, self.image);
+        }
+    }
+}
+
+/// Builds the `EGL_LINUX_DMA_BUF_EXT` attribute list of a dma-buf.
+fn image_attributes(desc: &DmaBufDescriptor) -> Vec<egl::types::EGLint> {
+    const PLANE_ATTRIBUTES: [[egl::types::EGLenum; 5]; 4] = [
+        [
+            egl::DMA_BUF_PLANE0_FD_EXT,
+            egl::DMA_BUF_PLANE0_OFFSET_EXT,
+            egl::DMA_BUF_PLANE0_PITCH_EXT,
+            egl::DMA_BUF_PLANE0_MODIFIER_LO_EXT,
+            egl::DMA_BUF_PLANE0_MODIFIER_HI_EXT,
+        ],
+        [
+            egl::DMA_BUF_PLANE1_FD_EXT,
+            egl::DMA_BUF_PLANE1_OFFSET_EXT,
+            egl::DMA_BUF_PLANE1_PITCH_EXT,
+            egl::DMA_BUF_PLANE1_MODIFIER_LO_EXT,
+            egl::DMA_BUF_PLANE1_MODIFIER_HI_EXT,
+        ],
+        [
+            egl::DMA_BUF_PLANE2_FD_EXT,
+            egl::DMA_BUF_PLANE2_OFFSET_EXT,
+            egl::DMA_BUF_PLANE2_PITCH_EXT,
+            egl::DMA_BUF_PLANE2_MODIFIER_LO_EXT,
+            egl::DMA_BUF_PLANE2_MODIFIER_HI_EXT,
+        ],
+        [
+            egl::DMA_BUF_PLANE3_FD_EXT,
+            egl::DMA_BUF_PLANE3_OFFSET_EXT,
+            egl::DMA_BUF_PLANE3_PITCH_EXT,
+            egl::DMA_BUF_PLANE3_MODIFIER_LO_EXT,
+            egl::DMA_BUF_PLANE3_MODIFIER_HI_EXT,
+        ],
+    ];
+
+    let mut attributes = vec![
+        egl::WIDTH as i32,
+        desc.width as i32,
+        egl::HEIGHT as i32,
+        desc.height as i32,
+        egl::LINUX_DRM_FOURCC_EXT as i32,
+        desc.format as i32,
+    ];
+    // Leaving the modifier out lets the driver use the buffer's implicit layout
+    let modifier = desc
+        .modifier
+        .filter(|&modifier| modifier != DrmModifier::Invalid)
+        .map(u64::from);
+    for (plane, [fd, offset, pitch, modifier_lo, modifier_hi]) in
+        desc.planes.iter().zip(PLANE_ATTRIBUTES)
+    {
+        attributes.extend([
+            fd as i32,
+            plane.fd,
+            offset as i32,
+            plane.offset as i32,
+            pitch as i32,
+            plane.stride as i32,
+        ]);
+        if let Some(modifier) = modifier {
+            attributes.extend([
+                modifier_lo as i32,
+                (modifier & 0xffff_ffff) as i32,
+                modifier_hi as i32,
+                (modifier >> 32) as i32,
+            ]);
+        }
+    }
+    attributes.push(egl::NONE as i32);
+    attributes
+}
+
+/// Returns true if the image can only be sampled through `GL_TEXTURE_EXTERNAL_OES`.
+fn needs_external_target(desc: &DmaBufDescriptor) -> bool {
+    desc.planes.len() > 1
+        || matches!(
+            desc.format,
+            DrmFourcc::Yuyv
+                | DrmFourcc::Yvyu
+                | DrmFourcc::Uyvy
+                | DrmFourcc::Vyuy
+                | DrmFourcc::Ayuv
+                | DrmFourcc::Xyuv8888
+                | DrmFourcc::Y210
+                | DrmFourcc::Y410
+        )
+}
@@ -0,0 +1,76 @@
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::time::{Duration, Instant};
+
+/// The vblank clock of a virtual monitor, which renders offscreen with no CRTC behind it
+///
+/// Virtual monitors don't get page flip events from the kernel, so a timerfd fires at the
+/// next vblank of a simulated display running at the monitor's refresh rate instead.
+pub(crate) struct VirtualOutput {
+    timer: OwnedFd,
+    period: Duration,
+    epoch: Instant,
+}
+
+impl VirtualOutput {
+    pub(crate) fn new(refresh: f64) -> std::io::Result<Self> {
+        let fd = unsafe {
+            libc::timerfd_create(
+                libc::CLOCK_MONOTONIC,
+                libc::TFD_NONBLOCK | libc::TFD_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(VirtualOutput {
+            timer: unsafe { OwnedFd::from_raw_fd(fd) },
+            period: Duration::from_secs_f64(1.0 / refresh),
+            epoch: Instant::now(),
+        })
+    }
+
+    /// Returns the timerfd, readable once the vblank after a frame was reached.
+    pub(crate) fn timer_fd(&self) -> BorrowedFd<'_> {
+        self.timer.as_fd()
+    }
+
+    /// Arms the timer for the next vblank, like a page flip queued on a real display.
+    pub(crate) fn queue_flip(&self) -> std::io::Result<()> {
+        let elapsed = self.epoch.elapsed();
+        let period = self.period.as_nanos().max(1);
+        let next = (elapsed.as_nanos() / period + 1) * period;
+        let delay = Duration::from_nanos((next - elapsed.as_nanos()) as u64);
+
+        let spec = libc::itimerspec {
+            it_interval: libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 0,
+            },
+            it_value: libc::timespec {
+                tv_sec: delay.as_secs() as libc::time_t,
+                // A zero value would disarm the timer
+                tv_nsec: delay.subsec_nanos().max(1) as libc::c_long,
+            },
+        };
+        let result = unsafe {
+            libc::timerfd_settime(self.timer.as_raw_fd(), 0, &spec, std::ptr::null_mut())
+        };
+        if result < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Consumes the timer expiration. Returns true if the vblank was reached.
+    pub(crate) fn take_flip(&self) -> bool {
+        let mut expirations = 0u64;
+        let read = unsafe {
+            libc::read(
+                self.timer.as_raw_fd(),
+                &mut expirations as *mut u64 as *mut libc::c_void,
+                std::mem::size_of::<u64>(),
+            )
+        };
+        read == std::mem::size_of::<u64>() as isize && expirations > 0
+    }
+}
@@ -0,0 +1,403 @@
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
+use std::sync::Arc;
+
+use ash::vk;
+use drm::buffer::{DrmFourcc, DrmModifier};
+use drm::control::{Device as ControlDevice, framebuffer};
+use gbm::BufferObjectFlags;
+
+use crate::capture::{CaptureError, CapturedFrame};
+use crate::device::GpuDevice;
+use crate::formats::{PlaneFormats, ScanoutFormat, add_scanout_framebuffer};
+use crate::render_backend::{BackendFrame, RenderBackend, RenderBackendError};
+
+/// Pixel format of the scanout buffers, `VK_FORMAT_B8G8R8A8_UNORM` on the Vulkan side
+const SCANOUT_FORMAT: DrmFourcc = DrmFourcc::Xrgb8888;
+const VULKAN_FORMAT: vk::Format = vk::Format::B8G8R8A8_UNORM;
+
+/// Number of images per monitor: one on screen, one being rendered
+const IMAGE_COUNT: usize = 2;
+
+/// A Vulkan device created by the application, which monitors render with
+///
+/// The device must have these extensions enabled: `VK_KHR_external_memory_fd`,
+/// `VK_EXT_external_memory_dma_buf`, `VK_EXT_image_drm_format_modifier`,
+/// `VK_EXT_queue_family_foreign` and `VK_KHR_external_semaphore_fd`. It must outlive
+/// the `EasyDRM` instance, and be idle before monitors are dropped (at exit or on
+/// unplug), since they destroy their images.
+#[derive(Clone)]
+pub struct VulkanDevice {
+    pub instance: ash::Instance,
+    pub physical_device: vk::PhysicalDevice,
+    pub device: ash::Device,
+}
+
+impl std::fmt::Debug for VulkanDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VulkanDevice")
+            .field("physical_device", &self.physical_device)
+            .field("device", &self.device.handle())
+            .finish()
+    }
+}
+
+struct ScanoutImage {
+    bo: gbm::BufferObject<()>,
+    framebuffer: framebuffer::Handle,
+    memory: vk::DeviceMemory,
+}
+
+/// Renders a monitor with Vulkan, to GBM buffers imported as `VkImage`s
+///
+/// Get it from the context constructor with `MonitorContextCreationRequest::vulkan()`
+/// to create image views and framebuffers for [`images`](Self::images), and in the
+/// render loop with `Monitor::vulkan()`. Each frame:
+///
+/// 1. Render to `images()[current_image()]`, then release it to
+///    `VK_QUEUE_FAMILY_FOREIGN_EXT` in the `GENERAL` layout.
+/// 2. Signal [`render_semaphore`](Self::render_semaphore) from the submission. It's
+///    exported as a sync_file the display controller waits on, so don't wait on it.
+/// 3. Call `Monitor::mark_drawn()`, `EasyDRM::swap_buffers()` presents the image.
+///
+/// # Example
+///
+/// ```ignore
+/// let vulkan = monitor.vulkan().unwrap();
+/// let image = vulkan.images()[vulkan.current_image()];
+/// record_frame(&command_buffer, image, vulkan.extent());
+/// let submit = vk::SubmitInfo::default()
+///     .command_buffers(&[command_buffer])
+///     .signal_semaphores(&[vulkan.render_semaphore()]);
+/// device.queue_submit(queue, &[submit], vk::Fence::null())?;
+/// monitor.mark_drawn();
+/// ```
+pub struct VulkanBackend {
+    device: Arc<GpuDevice>,
+    vulkan: Arc<VulkanDevice>,
+    external_memory_fd: ash::khr::external_memory_fd::Device,
+    external_semaphore_fd: ash::khr::external_semaphore_fd::Device,
+    buffers: Vec<ScanoutImage>,
+    images: Vec<vk::Image>,
+    extent: vk::Extent2D,
+    modifier: DrmModifier,
+    render_semaphore: vk::Semaphore,
+    /// Index of the image being rendered
+    back: usize,
+    /// Set once an image was presented
+    has_front: bool,
+}
+
+impl VulkanBackend {
+    pub(crate) fn new(
+        device: Arc<GpuDevice>,
+        vulkan: &Arc<VulkanDevice>,
+        width: u16,
+        height: u16,
+        plane_formats: Option<&PlaneFormats>,
+    ) -> Result<Self, RenderBackendError> {
+        let modifiers = negotiate_modifiers(vulkan, plane_formats);
+        if modifiers.is_empty() {
+            return Err(RenderBackendError::Other(
+                "Vulkan can't render to any modifier the plane scans out".into(),
+            ));
+        }
+
+        let external_memory_fd =
+            ash::khr::external_memory_fd::Device::new(&vulkan.instance, &vulkan.device);
+        let external_semaphore_fd =
+            ash::khr::external_semaphore_fd::Device::new(&vulkan.instance, &vulkan.device);
+
+        let mut export_info = vk::ExportSemaphoreCreateInfo::default()
+            .handle_types(vk::ExternalSemaphoreHandleTypeFlags::SYNC_FD);
+        let render_semaphore = unsafe {
+            vulkan.device.create_semaphore(
+                &vk::SemaphoreCreateInfo::default().push_next(&mut export_info),
+                None,
+            )?
+        };
+
+        // Filled in as images are created, so `Drop` cleans up after a failure
+        let mut backend = VulkanBackend {
+            device,
+            vulkan: Arc::clone(vulkan),
+            external_memory_fd,
+            external_semaphore_fd,
+            buffers: Vec::with_capacity(IMAGE_COUNT),
+            images: Vec::with_capacity(IMAGE_COUNT),
+            extent: vk::Extent2D {
+                width: width.into(),
+                height: height.into(),
+            },
+            modifier: DrmModifier::Invalid,
+            render_semaphore,
+            back: 0,
+            has_front: false,
+        };
+        for _ in 0..IMAGE_COUNT {
+            let bo = backend.device.gbm()?.create_buffer_object_with_modifiers2(
+                width.into(),
+                height.into(),
+                SCANOUT_FORMAT,
+                modifiers.iter().map(|&(modifier, _)| modifier),
+                BufferObjectFlags::SCANOUT | BufferObjectFlags::RENDERING,
+            )?;
+            let plane_count = modifiers
+                .iter()
+                .find(|&&(modifier, _)| modifier == bo.modifier())
+                .map_or(1, |&(_, plane_count)| plane_count);
+            backend.import(bo, plane_count)?;
+        }
+        Ok(backend)
+    }
+
+    /// Adds a framebuffer for `bo` and imports its memory as a Vulkan image.
+    fn import(
+        &mut self,
+        bo: gbm::BufferObject<()>,
+        plane_count: u32,
+    ) -> Result<(), RenderBackendError> {
+        let device = &self.vulkan.device;
+        let modifier = bo.modifier();
+        let plane_layouts: Vec<_> = (0..plane_count as i32)
+            .map(|plane| vk::SubresourceLayout {
+                offset: bo.offset(plane).into(),
+                row_pitch: bo.stride_for_plane(plane).into(),
+                ..Default::default()
+            })
+            .collect();
+        let fd = bo
+            .fd()
+            .map_err(|e| RenderBackendError::Other(Box::new(e)))?;
+
+        let mut external_info = vk::ExternalMemoryImageCreateInfo::default()
+            .handle_types(vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT);
+        let mut modifier_info = vk::ImageDrmFormatModifierExplicitCreateInfoEXT::default()
+            .drm_format_modifier(modifier.into())
+            .plane_layouts(&plane_layouts);
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(VULKAN_FORMAT)
+            .extent(self.extent.into())
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::DRM_FORMAT_MODIFIER_EXT)
+            .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .push_next(&mut external_info)
+            .push_next(&mut modifier_info);
+        let image = unsafe { device.create_image(&image_info, None)? };
+
+        let memory = match self.import_memory(image, &fd) {
+            Ok(memory) => memory,
+            Err(e) => {
+                unsafe { device.destroy_image(image, None) };
+                return Err(e);
+            }
+        };
+        // The image is bound to the memory from here on, destroying either one is enough
+        // to clean up
+        let framebuffer = match unsafe { device.bind_image_memory(image, memory, 0) }
+            .map_err(RenderBackendError::from)
+            .and_then(|()| Ok(add_scanout_framebuffer(&self.device.card, &bo, 24, true)?))
+        {
+            Ok(framebuffer) => framebuffer,
+            Err(e) => {
+                unsafe {
+                    device.destroy_image(image, None);
+                    device.free_memory(memory, None);
+                }
+                return Err(e);
+            }
+        };
+
+        self.modifier = modifier;
+        self.images.push(image);
+        self.buffers.push(ScanoutImage {
+            bo,
+            framebuffer,
+            memory,
+        });
+        Ok(())
+    }
+
+    /// Imports the dma-buf `fd` as dedicated memory for `image`.
+    fn import_memory(
+        &self,
+        image: vk::Image,
+        fd: &OwnedFd,
+    ) -> Result<vk::DeviceMemory, RenderBackendError> {
+        let device = &self.vulkan.device;
+        let handle_type = vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT;
+        let mut fd_properties = vk::MemoryFdPropertiesKHR::default();
+        unsafe {
+            self.external_memory_fd.get_memory_fd_properties(
+                handle_type,
+                fd.as_raw_fd(),
+                &mut fd_properties,
+            )?;
+        }
+        let requirements = unsafe { device.get_image_memory_requirements(image) };
+        let memory_types = requirements.memory_type_bits & fd_properties.memory_type_bits;
+        if memory_types == 0 {
+            return Err(RenderBackendError::Other(
+                "no Vulkan memory type can import the scanout buffer".into(),
+            ));
+        }
+
+        // Vulkan takes ownership of the fd only if the import succeeds
+        let import_fd = fd.try_clone()?;
+        let mut import_info = vk::ImportMemoryFdInfoKHR::default()
+            .handle_type(handle_type)
+            .fd(import_fd.as_raw_fd());
+        let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::default().image(image);
+        let allocate_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_types.trailing_zeros())
+            .push_next(&mut import_info)
+            .push_next(&mut dedicated_info);
+        let memory = unsafe { device.allocate_memory(&allocate_info, None)? };
+        let _ = import_fd.into_raw_fd();
+        Ok(memory)
+    }
+
+    /// Returns the Vulkan device the images belong to.
+    pub fn device(&self) -> &VulkanDevice {
+        &self.vulkan
+    }
+
+    /// Returns the images this monitor scans out, in the order they're rendered.
+    pub fn images(&self) -> &[vk::Image] {
+        &self.images
+    }
+
+    /// Returns the index in [`images`](Self::images) of the image to render next.
+    pub fn current_image(&self) -> usize {
+        self.back
+    }
+
+    /// Returns the format of the images, `VK_FORMAT_B8G8R8A8_UNORM`.
+    pub fn format(&self) -> vk::Format {
+        VULKAN_FORMAT
+    }
+
+    /// Returns the size of the images.
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    /// Returns the semaphore to signal once the current image is rendered.
+    pub fn render_semaphore(&self) -> vk::Semaphore {
+        self.render_semaphore
+    }
+}
+
+impl RenderBackend for VulkanBackend {
+    fn scanout_format(&self) -> ScanoutFormat {
+        ScanoutFormat {
+            format: SCANOUT_FORMAT,
+            modifier: Some(self.modifier),
+            explicit_modifier: true,
+        }
+    }
+
+    /// Exports the render semaphore as the frame's fence and moves on to the next image.
+    fn present(&mut self) -> Result<BackendFrame, RenderBackendError> {
+        let fd = unsafe {
+            self.external_semaphore_fd.get_semaphore_fd(
+                &vk::SemaphoreGetFdInfoKHR::default()
+                    .semaphore(self.render_semaphore)
+                    .handle_type(vk::ExternalSemaphoreHandleTypeFlags::SYNC_FD),
+            )?
+        };
+        // -1 means the rendering already finished
+        let fence = (fd >= 0).then(|| unsafe { OwnedFd::from_raw_fd(fd) });
+
+        let framebuffer = self.buffers[self.back].framebuffer;
+        self.back = (self.back + 1) % self.buffers.len();
+        self.has_front = true;
+        Ok(BackendFrame::new(framebuffer, fence))
+    }
+
+    fn read_frame(&self) -> Result<CapturedFrame, CaptureError> {
+        if !self.has_front {
+            return Err(CaptureError::NothingPresented);
+        }
+        let front = (self.back + self.buffers.len() - 1) % self.buffers.len();
+        let bo = &self.buffers[front].bo;
+        let (width, height, format) = (bo.width(), bo.height(), bo.format());
+        bo.map(0, 0, width, height, |mapped| {
+            CapturedFrame::from_pixels(width, height, format, mapped.buffer(), mapped.stride())
+        })?
+    }
+}
+
+impl Drop for VulkanBackend {
+    fn drop(&mut self) {
+        let device = &self.vulkan.device;
+        for (buffer, &image) in self.buffers.iter().zip(&self.images) {
+            if let Err(e) = self.device.card.destroy_framebuffer(buffer.framebuffer) {
+                eprintln!("Warning: Failed to destroy Vulkan framebuffer: {}", e);
+            }
+            unsafe {
+                device.destroy_image(image, None);
+                device.free_memory(buffer.memory, None);
+            }
+        }
+        unsafe { device.destroy_semaphore(self.render_semaphore, None) };
+    }
+}
+
+/// Returns the modifiers of `XRGB8888` Vulkan can render to and the plane can scan out,
+/// with their number of memory planes. Without a list from the plane, only linear
+/// buffers are assumed to be scanned out.
+fn negotiate_modifiers(
+    vulkan: &VulkanDevice,
+    plane_formats: Option<&PlaneFormats>,
+) -> Vec<(DrmModifier, u32)> {
+    let instance = &vulkan.instance;
+    let mut list = vk::DrmFormatModifierPropertiesListEXT::default();
+    unsafe {
+        let mut properties = vk::FormatProperties2::default().push_next(&mut list);
+        instance.get_physical_device_format_properties2(
+            vulkan.physical_device,
+            VULKAN_FORMAT,
+            &mut properties,
+        );
+    }
+    let mut modifier_properties = vec![
+        vk::DrmFormatModifierPropertiesEXT::default();
+        list.drm_format_modifier_count as usize
+    ];
+    let mut list = vk::DrmFormatModifierPropertiesListEXT::default()
+        .drm_format_modifier_properties(&mut modifier_properties);
+    unsafe {
+        let mut properties = vk::FormatProperties2::default().push_next(&mut list);
+        instance.get_physical_device_format_properties2(
+            vulkan.physical_device,
+            VULKAN_FORMAT,
+            &mut properties,
+        );
+    }
+    let count = list.drm_format_modifier_count as usize;
+
+    modifier_properties[..count]
+        .iter()
+        .filter(|properties| {
+            properties
+                .drm_format_modifier_tiling_features
+                .contains(vk::FormatFeatureFlags::COLOR_ATTACHMENT)
+        })
+        .map(|properties| {
+            (
+                DrmModifier::from(properties.drm_format_modifier),
+                properties.drm_format_modifier_plane_count,
+            )
+        })
+        .filter(|&(modifier, _)| match plane_formats {
+            Some(formats) => formats.supports(SCANOUT_FORMAT, modifier),
+            None => modifier == DrmModifier::Linear,
+        })
+        .collect()
+}
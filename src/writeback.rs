@@ -0,0 +1,295 @@
+#[cfg(feature = "gbm")]
+use std::collections::HashMap;
+#[cfg(feature = "gbm")]
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+#[cfg(feature = "gbm")]
+use std::sync::Arc;
+#[cfg(feature = "gbm")]
+use std::time::Duration;
+
+#[cfg(feature = "gbm")]
+use drm::buffer::DrmFourcc;
+use drm::control::{Device as ControlDevice, connector};
+#[cfg(feature = "gbm")]
+use drm::control::{FbCmd2Flags, atomic::AtomicModeReq, crtc, framebuffer, property};
+#[cfg(feature = "gbm")]
+use gbm::BufferObjectFlags;
+#[cfg(feature = "gbm")]
+use thiserror::Error;
+
+#[cfg(feature = "gbm")]
+use crate::capture::{CaptureError, CapturedFrame};
+#[cfg(feature = "gbm")]
+use crate::device::GpuDevice;
+
+/// Formats `CapturedFrame` can read, in order of preference
+#[cfg(feature = "gbm")]
+const CAPTURE_FORMATS: [DrmFourcc; 4] = [
+    DrmFourcc::Xrgb8888,
+    DrmFourcc::Argb8888,
+    DrmFourcc::Xbgr8888,
+    DrmFourcc::Abgr8888,
+];
+
+/// Errors that can occur while capturing a monitor through a writeback connector
+#[cfg(feature = "gbm")]
+#[derive(Debug, Error)]
+pub enum WritebackError {
+    #[error("IO Error: {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("no such monitor")]
+    NoSuchMonitor,
+    #[error("no free writeback connector can capture this monitor's CRTC")]
+    NoWritebackConnector,
+    #[error("the writeback connector doesn't support any format EasyDRM can read")]
+    NoSupportedFormat,
+    #[error("a writeback capture is already pending for this monitor")]
+    AlreadyPending,
+    #[error("no writeback capture was requested for this monitor")]
+    NotRequested,
+    #[error("failed to allocate the writeback buffer")]
+    BufferAllocationFailed,
+    #[error("the writeback didn't complete in time")]
+    Timeout,
+    #[error("failed to read the captured frame: {0}")]
+    Capture(#[from] CaptureError),
+}
+
+/// Returns true if `connector` is a writeback connector rather than a display.
+pub(crate) fn is_writeback(card: &impl ControlDevice, connector: connector::Handle) -> bool {
+    card.get_connector(connector, false)
+        .is_ok_and(|info| info.interface() == connector::Interface::Writeback)
+}
+
+/// Returns the CRTCs a connector can be attached to.
+#[cfg(feature = "gbm")]
+pub(crate) fn possible_crtcs(
+    card: &impl ControlDevice,
+    connector: connector::Handle,
+) -> std::io::Result<Vec<crtc::Handle>> {
+    let res = card.resource_handles()?;
+    let info = card.get_connector(connector, false)?;
+    let mut crtcs = Vec::new();
+    for &encoder in info.encoders() {
+        let Ok(encoder) = card.get_encoder(encoder) else {
+            continue;
+        };
+        for crtc in res.filter_crtcs(encoder.possible_crtcs()) {
+            if !crtcs.contains(&crtc) {
+                crtcs.push(crtc);
+            }
+        }
+    }
+    Ok(crtcs)
+}
+
+/// Where a writeback job is in the commit cycle
+#[cfg(feature = "gbm")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    /// Waiting for the monitor's next commit
+    Queued,
+    /// Added to an atomic request that hasn't been committed yet
+    Requested,
+    /// The commit was accepted, the display controller is writing the frame
+    Committed,
+    /// The commit's page flip completed
+    Flipped,
+}
+
+/// A capture of a CRTC's composed output, attached to its next commit
+#[cfg(feature = "gbm")]
+pub(crate) struct WritebackJob {
+    device: Arc<GpuDevice>,
+    connector: connector::Handle,
+    properties: HashMap<String, property::Info>,
+    bo: gbm::BufferObject<()>,
+    fb: framebuffer::Handle,
+    /// Written by the kernel on commit. Boxed so its address stays stable.
+    out_fence_slot: Box<i32>,
+    /// The fence from `out_fence_slot`, once the commit succeeded
+    out_fence: Option<OwnedFd>,
+    stage: Stage,
+}
+
+#[cfg(feature = "gbm")]
+impl WritebackJob {
+    /// Allocates a `width`x`height` buffer for the writeback connector.
+    pub(crate) fn new(
+        device: Arc<GpuDevice>,
+        connector: connector::Handle,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, WritebackError> {
+        let properties = device
+            .card
+            .get_properties(connector)?
+            .as_hashmap(&device.card)?;
+        let format = Self::pick_format(&device, connector, &properties)?;
+
+        let bo = device
+            .gbm()?
+            .create_buffer_object::<()>(
+                width,
+                height,
+                format,
+                BufferObjectFlags::SCANOUT | BufferObjectFlags::LINEAR,
+            )
+            .map_err(|_| WritebackError::BufferAllocationFailed)?;
+        let fb = device
+            .card
+            .add_planar_framebuffer(&bo, FbCmd2Flags::empty())?;
+
+        Ok(WritebackJob {
+            device,
+            connector,
+            properties,
+            bo,
+            fb,
+            out_fence_slot: Box::new(-1),
+            out_fence: None,
+            stage: Stage::Queued,
+        })
+    }
+
+    /// Picks a format from the connector's `WRITEBACK_PIXEL_FORMATS` blob.
+    fn pick_format(
+        device: &GpuDevice,
+        connector: connector::Handle,
+        properties: &HashMap<String, property::Info>,
+    ) -> Result<DrmFourcc, WritebackError> {
+        let prop = properties
+            .get("WRITEBACK_PIXEL_FORMATS")
+            .ok_or(WritebackError::NoSupportedFormat)?;
+        let values = device.card.get_properties(connector)?;
+        let (_, &blob_id) = values
+            .iter()
+            .find(|(id, _)| **id == prop.handle())
+            .ok_or(WritebackError::NoSupportedFormat)?;
+        let blob = device.card.get_property_blob(blob_id)?;
+        let formats: Vec<u32> = blob
+            .chunks_exact(4)
+            .map(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap()))
+            .collect();
+        CAPTURE_FORMATS
+            .into_iter()
+            .find(|&format| formats.contains(&(format as u32)))
+            .ok_or(WritebackError::NoSupportedFormat)
+    }
+
+    pub(crate) fn connector(&self) -> connector::Handle {
+        self.connector
+    }
+
+    /// Returns true until a commit carrying the job succeeded.
+    pub(crate) fn is_queued(&self) -> bool {
+        matches!(self.stage, Stage::Queued | Stage::Requested)
+    }
+
+    /// Attaches the buffer to the commit that updates `crtc`.
+    ///
+    /// The job only counts as committed once [`commit_succeeded`](Self::commit_succeeded)
+    /// is called, so it's added again if this request is rejected.
+    pub(crate) fn add_to_request(&mut self, atomic_req: &mut AtomicModeReq, crtc: crtc::Handle) {
+        if !self.is_queued() {
+            return;
+        }
+        atomic_req.add_property(
+            self.connector,
+            self.properties["CRTC_ID"].handle(),
+            property::Value::CRTC(Some(crtc)),
+        );
+        atomic_req.add_property(
+            self.connector,
+            self.properties["WRITEBACK_FB_ID"].handle(),
+            property::Value::Framebuffer(Some(self.fb)),
+        );
+        if let Some(fence_ptr) = self.properties.get("WRITEBACK_OUT_FENCE_PTR") {
+            *self.out_fence_slot = -1;
+            atomic_req.add_raw_property(
+                self.connector.into(),
+                fence_ptr.handle(),
+                &*self.out_fence_slot as *const i32 as u64,
+            );
+        }
+        self.stage = Stage::Requested;
+    }
+
+    /// Called once the request the job was added to has been committed.
+    pub(crate) fn commit_succeeded(&mut self) {
+        if self.stage == Stage::Requested {
+            self.stage = Stage::Committed;
+            // The kernel only writes a fence into the slot when the commit went through
+            let fd = std::mem::replace(&mut *self.out_fence_slot, -1);
+            self.out_fence = (fd >= 0).then(|| unsafe { OwnedFd::from_raw_fd(fd) });
+        }
+    }
+
+    /// Called when the request the job was added to was rejected, the job goes into the
+    /// monitor's next commit instead.
+    pub(crate) fn commit_failed(&mut self) {
+        if self.stage == Stage::Requested {
+            self.stage = Stage::Queued;
+        }
+    }
+
+    /// Called when the monitor's page flip completed. Without an out fence, this is when
+    /// the frame is known to be written.
+    pub(crate) fn page_flip_complete(&mut self) {
+        if self.stage == Stage::Committed {
+            self.stage = Stage::Flipped;
+        }
+    }
+
+    /// Returns the out fence to poll for completion, if the connector has one and the
+    /// job was committed.
+    pub(crate) fn out_fence(&self) -> Option<BorrowedFd<'_>> {
+        self.out_fence.as_ref().map(AsFd::as_fd)
+    }
+
+    /// Waits up to `timeout` for the display controller to finish writing the frame.
+    ///
+    /// Returns `Ok(false)` if it's still being written. Connectors without
+    /// `WRITEBACK_OUT_FENCE_PTR` can't be waited on here: their frame is done once the
+    /// page flip event was handled, see [`page_flip_complete`](Self::page_flip_complete).
+    pub(crate) fn wait(&self, timeout: Duration) -> Result<bool, WritebackError> {
+        if self.is_queued() {
+            return Ok(false);
+        }
+        if !self.properties.contains_key("WRITEBACK_OUT_FENCE_PTR") {
+            return Ok(self.stage == Stage::Flipped);
+        }
+        let Some(out_fence) = self.out_fence() else {
+            return Ok(false);
+        };
+        let mut pollfd = libc::pollfd {
+            fd: out_fence.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+        match unsafe { libc::poll(&mut pollfd, 1, timeout_ms) } {
+            -1 => Err(std::io::Error::last_os_error().into()),
+            0 => Ok(false),
+            _ => Ok(true),
+        }
+    }
+
+    /// Reads the written frame.
+    pub(crate) fn read(&self) -> Result<CapturedFrame, WritebackError> {
+        let (width, height, format) = (self.bo.width(), self.bo.height(), self.bo.format());
+        let frame = self.bo.map(0, 0, width, height, |mapped| {
+            CapturedFrame::from_pixels(width, height, format, mapped.buffer(), mapped.stride())
+        })??;
+        Ok(frame)
+    }
+}
+
+#[cfg(feature = "gbm")]
+impl Drop for WritebackJob {
+    fn drop(&mut self) {
+        if let Err(e) = self.device.card.destroy_framebuffer(self.fb) {
+            eprintln!("Warning: Failed to destroy writeback framebuffer: {}", e);
+        }
+    }
+}
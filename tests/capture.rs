@@ -0,0 +1,111 @@
+//! Frame readback conversion and image encoding tests.
+
+use drm::buffer::DrmFourcc;
+use easydrm::{CaptureError, CapturedFrame};
+
+/// A 2x2 frame: red, green / blue, white, as XRGB8888 with a padded stride.
+fn xrgb_frame() -> CapturedFrame {
+    let stride = 12;
+    let mut pixels = vec![0u8; stride * 2];
+    let colors: [[u8; 4]; 4] = [
+        [0x00, 0x00, 0xff, 0x00], // B G R X
+        [0x00, 0xff, 0x00, 0x00],
+        [0xff, 0x00, 0x00, 0x00],
+        [0xff, 0xff, 0xff, 0x00],
+    ];
+    for (i, color) in colors.iter().enumerate() {
+        let offset = (i / 2) * stride + (i % 2) * 4;
+        pixels[offset..offset + 4].copy_from_slice(color);
+    }
+    CapturedFrame::from_pixels(2, 2, DrmFourcc::Xrgb8888, &pixels, stride as u32).unwrap()
+}
+
+#[test]
+fn converts_padded_xrgb_rows_to_opaque_rgba() {
+    let frame = xrgb_frame();
+    assert_eq!(frame.size(), (2, 2));
+    assert_eq!(frame.source_format(), DrmFourcc::Xrgb8888);
+    assert_eq!(frame.pixel(0, 0), Some([255, 0, 0, 255]));
+    assert_eq!(frame.pixel(1, 0), Some([0, 255, 0, 255]));
+    assert_eq!(frame.pixel(0, 1), Some([0, 0, 255, 255]));
+    assert_eq!(frame.pixel(1, 1), Some([255, 255, 255, 255]));
+    assert_eq!(frame.pixel(2, 0), None);
+
+    let abgr = CapturedFrame::from_pixels(1, 1, DrmFourcc::Abgr8888, &[1, 2, 3, 4], 4).unwrap();
+    assert_eq!(abgr.rgba(), [1, 2, 3, 4]);
+
+    assert!(matches!(
+        CapturedFrame::from_pixels(2, 2, DrmFourcc::Xrgb8888, &[0; 12], 8),
+        Err(CaptureError::TooShort { .. })
+    ));
+    assert!(matches!(
+        CapturedFrame::from_pixels(1, 1, DrmFourcc::Nv12, &[0; 4], 4),
+        Err(CaptureError::UnsupportedFormat(DrmFourcc::Nv12))
+    ));
+}
+
+#[test]
+fn encodes_ppm() {
+    let ppm = xrgb_frame().encode_ppm();
+    let header = b"P6\n2 2\n255\n";
+    assert_eq!(&ppm[..header.len()], header);
+    assert_eq!(
+        &ppm[header.len()..],
+        [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255]
+    );
+}
+
+#[test]
+fn encodes_png_with_valid_chunks() {
+    let png = xrgb_frame().encode_png();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+    // Walk the chunks and check their CRCs
+    let mut offset = 8;
+    let mut kinds = Vec::new();
+    let mut idat = Vec::new();
+    while offset < png.len() {
+        let length = u32::from_be_bytes(png[offset..offset + 4].try_into().unwrap()) as usize;
+        let body = &png[offset + 4..offset + 8 + length];
+        let crc = u32::from_be_bytes(
+            png[offset + 8 + length..offset + 12 + length]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(crc, crc32(body));
+        kinds.push(String::from_utf8(body[..4].to_vec()).unwrap());
+        if &body[..4] == b"IDAT" {
+            idat.extend_from_slice(&body[4..]);
+        }
+        offset += 12 + length;
+    }
+    assert_eq!(kinds, ["IHDR", "IDAT", "IEND"]);
+
+    // A single stored deflate block holding filter bytes and RGBA rows
+    let expected_rows = [
+        0, 255, 0, 0, 255, 0, 255, 0, 255, //
+        0, 0, 0, 255, 255, 255, 255, 255, 255,
+    ];
+    assert_eq!(&idat[..2], [0x78, 0x01]);
+    assert_eq!(idat[2], 1);
+    assert_eq!(
+        u16::from_le_bytes([idat[3], idat[4]]) as usize,
+        expected_rows.len()
+    );
+    assert_eq!(&idat[7..7 + expected_rows.len()], expected_rows);
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,280 @@
+//! Display configuration parsing and matching tests.
+
+use easydrm::{
+    ColorSettings, ConfigError, DisplayConfig, EdidIdentity, Gamma, ModeBuilder, ModeSpec,
+    MonitorId, MonitorInfo, Rotation,
+};
+
+/// The example from the `config` module documentation
+const MODULE_DOC_EXAMPLE: &str = r#"
+[[output]]
+match = { connector = "HDMI-A-1" }
+mode = "1920x1080@60"
+rotation = 90
+position = [0, 0]
+
+[[output]]
+match = { manufacturer = "DEL", model = "DELL S2721DGF" }
+refresh = 144
+color = { gamma = 2.2, brightness = 0.9 }
+
+[[output]]
+match = { id = "GSM:7F44:16843009@DP-2" }
+enabled = false
+"#;
+
+fn dell_info() -> MonitorInfo {
+    let path = format!(
+        "{}/tests/data/edid/dp-gaming-range-limits.bin",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    MonitorInfo::from_edid(&std::fs::read(path).unwrap()).unwrap()
+}
+
+fn dell_id(connector: &str) -> MonitorId {
+    MonitorId::new(
+        Some(EdidIdentity {
+            manufacturer: "DEL".to_string(),
+            product_code: 0xA0F3,
+            serial: "CFV9N01A1B2L".to_string(),
+        }),
+        connector,
+    )
+}
+
+fn parse_error(toml: &str) -> String {
+    match DisplayConfig::from_toml_str(toml) {
+        Err(ConfigError::Parse(e)) => e.to_string(),
+        other => panic!("expected a parse error, got {other:?}"),
+    }
+}
+
+#[test]
+fn parses_module_doc_example() {
+    let config = DisplayConfig::from_toml_str(MODULE_DOC_EXAMPLE).unwrap();
+    assert_eq!(config.outputs.len(), 3);
+
+    let hdmi = &config.outputs[0];
+    assert_eq!(hdmi.matcher.connector.as_deref(), Some("HDMI-A-1"));
+    assert_eq!(
+        hdmi.mode,
+        Some(ModeSpec {
+            width: 1920,
+            height: 1080,
+            refresh: Some(60)
+        })
+    );
+    assert_eq!(hdmi.rotation, Some(Rotation::Rotate90));
+    assert_eq!(hdmi.position, Some((0, 0)));
+    assert_eq!(hdmi.enabled, None);
+
+    let dell = &config.outputs[1];
+    assert_eq!(dell.matcher.manufacturer.as_deref(), Some("DEL"));
+    assert_eq!(dell.matcher.model.as_deref(), Some("DELL S2721DGF"));
+    assert_eq!(dell.refresh, Some(144));
+    assert_eq!(
+        dell.color,
+        Some(ColorSettings {
+            gamma: Gamma::Uniform(2.2),
+            brightness: 0.9,
+            contrast: 1.0
+        })
+    );
+
+    let lg = &config.outputs[2];
+    assert_eq!(
+        lg.matcher.id,
+        Some("GSM:7F44:16843009@DP-2".parse().unwrap())
+    );
+    assert_eq!(lg.enabled, Some(false));
+}
+
+#[test]
+fn empty_config_has_no_rules() {
+    let config = DisplayConfig::from_toml_str("").unwrap();
+    assert!(config.outputs.is_empty());
+    assert!(config.rule_for(&dell_id("DP-1"), None).is_none());
+}
+
+#[test]
+fn rejects_unknown_fields() {
+    let error = parse_error("[[outputs]]\nmatch = {}\n");
+    assert!(error.contains("unknown field `outputs`"), "{error}");
+
+    let error = parse_error("[[output]]\nmatch = {}\nresolution = \"1920x1080\"\n");
+    assert!(error.contains("unknown field `resolution`"), "{error}");
+
+    let error = parse_error("[[output]]\nmatch = { name = \"HDMI-A-1\" }\n");
+    assert!(error.contains("unknown field `name`"), "{error}");
+
+    let error = parse_error("[[output]]\nmatch = {}\ncolor = { saturation = 1.2 }\n");
+    assert!(error.contains("unknown field `saturation`"), "{error}");
+}
+
+#[test]
+fn rejects_invalid_values() {
+    let error = parse_error("[[output]]\nmatch = {}\nrotation = 45\n");
+    assert!(error.contains("invalid rotation 45"), "{error}");
+
+    let error = parse_error("[[output]]\nmatch = {}\nmode = \"1920*1080\"\n");
+    assert!(error.contains("invalid mode"), "{error}");
+
+    let error = parse_error("[[output]]\nmatch = { id = \"DEL@\" }\n");
+    assert!(error.contains("invalid monitor id"), "{error}");
+}
+
+#[test]
+fn parses_rotations() {
+    for (degrees, rotation) in [
+        (0, Rotation::Normal),
+        (90, Rotation::Rotate90),
+        (180, Rotation::Rotate180),
+        (270, Rotation::Rotate270),
+    ] {
+        let toml = format!("[[output]]\nmatch = {{}}\nrotation = {degrees}\n");
+        let config = DisplayConfig::from_toml_str(&toml).unwrap();
+        assert_eq!(config.outputs[0].rotation, Some(rotation));
+        assert_eq!(rotation.degrees(), degrees);
+    }
+}
+
+#[test]
+fn parses_mode_specs() {
+    assert_eq!(
+        "1280x720".parse(),
+        Ok(ModeSpec {
+            width: 1280,
+            height: 720,
+            refresh: None
+        })
+    );
+    assert_eq!(
+        "3840x2160@120".parse::<ModeSpec>().unwrap().to_string(),
+        "3840x2160@120"
+    );
+    for invalid in ["", "1920", "1920x", "x1080", "1920x1080@", "1920x1080@60Hz"] {
+        assert!(invalid.parse::<ModeSpec>().is_err(), "{invalid:?}");
+    }
+}
+
+#[test]
+fn finds_modes_by_size_and_refresh() {
+    let modes =
+        [60.0, 144.0, 120.0].map(|refresh| ModeBuilder::new(2560, 1440, refresh).build().unwrap());
+    let fhd = ModeBuilder::new(1920, 1080, 60.0).build().unwrap();
+    let modes = [modes[0], fhd, modes[1], modes[2]];
+
+    let spec = |s: &str| s.parse::<ModeSpec>().unwrap();
+    assert_eq!(spec("2560x1440@120").find_in(&modes), Some(modes[3]));
+    assert_eq!(spec("1920x1080").find_in(&modes), Some(fhd));
+    // Without a refresh rate and no preferred mode, the fastest one wins
+    assert_eq!(spec("2560x1440").find_in(&modes), Some(modes[2]));
+    assert_eq!(spec("2560x1440@75").find_in(&modes), None);
+    assert_eq!(spec("1280x720").find_in(&modes), None);
+}
+
+#[test]
+fn first_matching_rule_wins() {
+    let config = DisplayConfig::from_toml_str(MODULE_DOC_EXAMPLE).unwrap();
+    let info = dell_info();
+
+    // The connector rule comes first, so it applies even though the EDID rule fits too
+    let rule = config.rule_for(&dell_id("HDMI-A-1"), Some(&info)).unwrap();
+    assert_eq!(rule.rotation, Some(Rotation::Rotate90));
+
+    // On any other connector the display is recognized by its EDID
+    let rule = config.rule_for(&dell_id("DP-1"), Some(&info)).unwrap();
+    assert_eq!(rule.refresh, Some(144));
+
+    // The model name comes from the EDID info, not the id
+    assert!(config.rule_for(&dell_id("DP-1"), None).is_none());
+
+    // An exact id only matches on the connector it names
+    let lg_edid = "GSM:7F44:16843009@DP-2"
+        .parse::<MonitorId>()
+        .unwrap()
+        .edid()
+        .cloned();
+    let lg = |connector| MonitorId::new(lg_edid.clone(), connector);
+    assert_eq!(
+        config.rule_for(&lg("DP-2"), None).unwrap().enabled,
+        Some(false)
+    );
+    assert!(config.rule_for(&lg("DP-3"), None).is_none());
+}
+
+#[test]
+fn matcher_fields_must_all_match() {
+    let config = DisplayConfig::from_toml_str(
+        r#"
+        [[output]]
+        match = { connector = "DP-1", manufacturer = "DEL", serial = "CFV9N01A1B2L" }
+        position = [1920, 0]
+
+        [[output]]
+        match = { product_code = 41203 }
+        position = [0, 0]
+
+        [[output]]
+        match = {}
+        enabled = false
+        "#,
+    )
+    .unwrap();
+    let position = |id: &MonitorId| config.rule_for(id, None).unwrap().position;
+
+    assert_eq!(position(&dell_id("DP-1")), Some((1920, 0)));
+    // Wrong connector, falls through to the product code rule
+    assert_eq!(position(&dell_id("DP-2")), Some((0, 0)));
+
+    // EDID criteria never match a monitor without an EDID, only the catch-all does
+    let rule = config
+        .rule_for(&MonitorId::new(None, "DP-1"), None)
+        .unwrap();
+    assert_eq!(rule.enabled, Some(false));
+}
+
+#[test]
+fn gamma_ramp_endpoints_and_monotonicity() {
+    let identity = ColorSettings::default().gamma_ramps(256);
+    for ramp in &identity {
+        assert_eq!(ramp.len(), 256);
+        assert_eq!(ramp[0], 0);
+        assert_eq!(ramp[255], u16::MAX);
+        assert_eq!(ramp[128], (128.0 / 255.0 * 65535.0f32).round() as u16);
+    }
+
+    let settings = ColorSettings {
+        gamma: Gamma::PerChannel([1.8, 2.2, 2.6]),
+        brightness: 1.0,
+        contrast: 1.0,
+    };
+    let [red, green, blue] = settings.gamma_ramps(1024);
+    for ramp in [&red, &green, &blue] {
+        assert_eq!(ramp[0], 0);
+        assert_eq!(ramp[1023], u16::MAX);
+        assert!(ramp.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+    // Higher gamma lifts the midtones more
+    assert!(red[512] < green[512] && green[512] < blue[512]);
+
+    let dimmed = ColorSettings {
+        brightness: 0.5,
+        ..ColorSettings::default()
+    }
+    .gamma_ramps(256);
+    assert_eq!(dimmed[0][0], 0);
+    assert_eq!(dimmed[0][255], 32768);
+    assert!(dimmed[0].windows(2).all(|pair| pair[0] <= pair[1]));
+
+    let contrast = ColorSettings {
+        contrast: 2.0,
+        ..ColorSettings::default()
+    }
+    .gamma_ramps(256);
+    // Values pushed past the range are clamped
+    assert_eq!(contrast[1][0], 0);
+    assert_eq!(contrast[1][32], 0);
+    assert_eq!(contrast[1][255], u16::MAX);
+    assert!(contrast[1].windows(2).all(|pair| pair[0] <= pair[1]));
+}
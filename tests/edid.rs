@@ -0,0 +1,130 @@
+//! EDID parser tests against the dumps in `tests/data/edid`.
+
+use easydrm::{EdidError, ManufactureDate, MonitorInfo, VrrRange};
+
+fn load(name: &str) -> MonitorInfo {
+    let path = format!("{}/tests/data/edid/{name}", env!("CARGO_MANIFEST_DIR"));
+    let edid = std::fs::read(&path).unwrap_or_else(|e| panic!("failed to read {path}: {e}"));
+    MonitorInfo::from_edid(&edid).unwrap_or_else(|e| panic!("failed to parse {path}: {e}"))
+}
+
+#[test]
+fn hdmi_tv_with_cta_extension() {
+    let info = load("hdmi-tv-hdr-vrr.bin");
+
+    assert_eq!(info.manufacturer, "GSM");
+    assert_eq!(info.product_code, 0x7F44);
+    assert_eq!(info.serial_number, Some(0x01010101));
+    assert_eq!(info.model_name.as_deref(), Some("LG TV SSCR2"));
+    assert_eq!(
+        info.manufacture_date,
+        ManufactureDate::Manufactured {
+            week: Some(12),
+            year: 2021
+        }
+    );
+    assert_eq!(info.edid_version, (1, 3));
+    assert_eq!(info.physical_size_mm, Some((1600, 900)));
+
+    let color = info.color_formats;
+    assert!(color.rgb444 && color.ycbcr444 && color.ycbcr422 && color.ycbcr420);
+    assert!(color.bt2020_rgb && color.bt2020_ycc);
+    assert!(!color.dci_p3);
+    assert_eq!(color.bits_per_component, None);
+
+    let hdr = info.hdr.expect("HDR static metadata");
+    assert!(hdr.eotf_sdr && hdr.eotf_hdr && hdr.eotf_pq && hdr.eotf_hlg);
+    assert!((hdr.max_luminance.unwrap() - 603.4).abs() < 1.0);
+    assert!((hdr.max_frame_average_luminance.unwrap() - 391.5).abs() < 1.0);
+    assert!((hdr.min_luminance.unwrap() - 0.536).abs() < 0.01);
+
+    // The HDMI Forum VSDB range wins over the (non-continuous) range limits
+    assert_eq!(
+        info.vrr_range,
+        Some(VrrRange {
+            min_hz: 40,
+            max_hz: 120
+        })
+    );
+}
+
+#[test]
+fn displayport_monitor_with_range_limits() {
+    let info = load("dp-gaming-range-limits.bin");
+
+    assert_eq!(info.manufacturer, "DEL");
+    assert_eq!(info.product_code, 0xA0F3);
+    assert_eq!(info.serial_number, Some(0x4C4B4A31));
+    assert_eq!(info.serial_string.as_deref(), Some("CFV9N01A1B2L"));
+    assert_eq!(info.model_name.as_deref(), Some("DELL S2721DGF"));
+    assert_eq!(info.edid_version, (1, 4));
+    assert_eq!(info.physical_size_mm, Some((597, 336)));
+    assert_eq!(info.color_formats.bits_per_component, Some(10));
+    assert!(info.color_formats.ycbcr444 && info.color_formats.ycbcr422);
+    assert!(!info.color_formats.ycbcr420);
+    assert_eq!(info.hdr, None);
+    assert_eq!(
+        info.vrr_range,
+        Some(VrrRange {
+            min_hz: 48,
+            max_hz: 165
+        })
+    );
+}
+
+#[test]
+fn embedded_panel_with_displayid_extension() {
+    let info = load("edp-panel-displayid.bin");
+
+    assert_eq!(info.manufacturer, "BOE");
+    assert_eq!(info.product_code, 0x0A1C);
+    assert_eq!(info.serial_number, None);
+    assert_eq!(info.manufacture_date, ManufactureDate::ModelYear(2018));
+    // No product name descriptor in the base block, taken from DisplayID
+    assert_eq!(info.model_name.as_deref(), Some("NV140FHM-N49"));
+    // DisplayID image size (0.1 mm) takes precedence over the base block (cm)
+    assert_eq!(info.physical_size_mm, Some((309, 174)));
+    assert_eq!(info.color_formats.bits_per_component, Some(8));
+    assert_eq!(info.vrr_range, None);
+}
+
+#[test]
+fn analog_projector_without_size_or_date() {
+    let info = load("vga-projector.bin");
+
+    assert_eq!(info.manufacturer, "EPS");
+    assert_eq!(info.model_name.as_deref(), Some("EPSON PJ"));
+    assert_eq!(info.manufacture_date, ManufactureDate::Unknown);
+    assert_eq!(info.physical_size_mm, None);
+    assert!(!info.color_formats.rgb444);
+    // Range limits of an analog display don't describe VRR support
+    assert_eq!(info.vrr_range, None);
+}
+
+#[test]
+fn rejects_invalid_blobs() {
+    let path = format!(
+        "{}/tests/data/edid/dp-gaming-range-limits.bin",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let edid = std::fs::read(path).unwrap();
+
+    assert_eq!(
+        MonitorInfo::from_edid(&edid[..100]),
+        Err(EdidError::TooShort(100))
+    );
+
+    let mut bad_header = edid.clone();
+    bad_header[0] = 0x42;
+    assert_eq!(
+        MonitorInfo::from_edid(&bad_header),
+        Err(EdidError::InvalidHeader)
+    );
+
+    let mut bad_checksum = edid;
+    bad_checksum[127] = bad_checksum[127].wrapping_add(1);
+    assert_eq!(
+        MonitorInfo::from_edid(&bad_checksum),
+        Err(EdidError::ChecksumMismatch)
+    );
+}
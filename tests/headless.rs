@@ -0,0 +1,746 @@
+//! Headless rendering tests, on an EGL device such as Mesa's llvmpipe.
+
+use std::os::fd::{FromRawFd, OwnedFd};
+use std::sync::{Arc, Mutex};
+
+use ash::vk;
+use easydrm::{
+    CursorBackend, EasyDRM, EasyDRMError, EasyDRMOptions, GlConfig, GlDebugSeverity, GlDebugSource,
+    GlDebugType, GlesContextError, ImageLayout, InteropError, RenderBackendError,
+    RenderBackendFactory, RenderBackendKind, RenderThreadError, VulkanImageDescriptor, gl,
+};
+
+#[test]
+fn virtual_monitors_render_and_read_back_headless() {
+    let mut easydrm = EasyDRM::init_with_options(
+        EasyDRMOptions::new()
+            .headless()
+            .ignore_kernel_cmdline()
+            .virtual_monitor(64, 32, 60.0)
+            .virtual_monitor(16, 16, 30.0),
+        |request| {
+            assert!(request.gl.is_some() && request.get_proc_address.is_some());
+            (request.width, request.height)
+        },
+    )
+    .expect("headless initialization needs an EGL device");
+    assert_eq!(easydrm.monitor_count(), 2);
+    assert!(easydrm.monitors().all(|monitor| monitor.is_virtual()));
+    assert_eq!(easydrm.refresh_rate_groups().len(), 2);
+
+    let connector = easydrm
+        .monitors()
+        .find(|monitor| *monitor.context() == (64, 32))
+        .map(|monitor| monitor.connector_id())
+        .unwrap();
+    let monitor = easydrm.get_monitor_mut(connector).unwrap();
+    assert_eq!(monitor.connector_name(), "VIRTUAL-1");
+    assert!(monitor.try_crtc().is_none());
+    assert!(monitor.try_primary_plane().is_none());
+
+    // Red, with a blue band over the bottom half (GL rows start at the bottom)
+    monitor.make_current().unwrap();
+    let gl = monitor.gl();
+    unsafe {
+        gl.ClearColor(1.0, 0.0, 0.0, 1.0);
+        gl.Clear(gl::COLOR_BUFFER_BIT);
+        gl.Enable(gl::SCISSOR_TEST);
+        gl.Scissor(0, 0, 64, 16);
+        gl.ClearColor(0.0, 0.0, 1.0, 1.0);
+        gl.Clear(gl::COLOR_BUFFER_BIT);
+        gl.Disable(gl::SCISSOR_TEST);
+    }
+    easydrm.swap_buffers().unwrap();
+
+    let monitor = easydrm.get_monitor(connector).unwrap();
+    assert!(!monitor.can_render());
+    let frame = monitor.capture_frame().unwrap();
+    assert_eq!(frame.size(), (64, 32));
+    assert_eq!(frame.pixel(0, 0), Some([255, 0, 0, 255]));
+    assert_eq!(frame.pixel(63, 15), Some([255, 0, 0, 255]));
+    assert_eq!(frame.pixel(0, 16), Some([0, 0, 255, 255]));
+    assert_eq!(frame.pixel(63, 31), Some([0, 0, 255, 255]));
+
+    // The simulated vblank completes the flip
+    while !easydrm.get_monitor(connector).unwrap().can_render() {
+        easydrm.poll_events().unwrap();
+    }
+}
+
+#[test]
+fn composited_cursor_is_drawn_at_its_hotspot() {
+    let mut easydrm = EasyDRM::init_with_options(
+        EasyDRMOptions::new()
+            .headless()
+            .ignore_kernel_cmdline()
+            .virtual_monitor(32, 16, 60.0),
+        |_| (),
+    )
+    .expect("headless initialization needs an EGL device");
+    let monitor = easydrm.monitors_mut().next().unwrap();
+    assert_eq!(monitor.cursor_backend(), CursorBackend::Composited);
+
+    // White 4x4 cursor whose hotspot sits one pixel in from its top-left corner
+    monitor
+        .set_cursor_image(&[0xFFFF_FFFF; 4 * 4], 4, 4, (1, 1))
+        .unwrap();
+    monitor.move_cursor(11, 5);
+    monitor.make_current().unwrap();
+    let gl = monitor.gl();
+    unsafe {
+        gl.ClearColor(0.0, 0.0, 0.0, 1.0);
+        gl.Clear(gl::COLOR_BUFFER_BIT);
+    }
+    easydrm.swap_buffers().unwrap();
+
+    let frame = easydrm.monitors().next().unwrap().capture_frame().unwrap();
+    assert_eq!(frame.pixel(10, 4), Some([255, 255, 255, 255]));
+    assert_eq!(frame.pixel(13, 7), Some([255, 255, 255, 255]));
+    assert_eq!(frame.pixel(9, 4), Some([0, 0, 0, 255]));
+    assert_eq!(frame.pixel(14, 7), Some([0, 0, 0, 255]));
+    assert_eq!(frame.pixel(10, 8), Some([0, 0, 0, 255]));
+}
+
+#[test]
+fn virtual_monitors_can_be_added_and_removed_at_runtime() {
+    let mut easydrm = EasyDRM::init_with_options(
+        EasyDRMOptions::new().headless().ignore_kernel_cmdline(),
+        |_| (),
+    )
+    .expect("headless initialization needs an EGL device");
+    assert!(!easydrm.has_monitors());
+
+    let connector = easydrm
+        .add_virtual_monitor(32, 32, 75.0)
+        .unwrap()
+        .connector_id();
+    assert_eq!(easydrm.monitor_count(), 1);
+    assert!(easydrm.refresh_rate_groups().contains_key(&75));
+
+    assert!(easydrm.remove_virtual_monitor(connector));
+    assert!(!easydrm.remove_virtual_monitor(connector));
+    assert!(!easydrm.has_monitors());
+    assert!(easydrm.refresh_rate_groups().is_empty());
+}
+
+#[test]
+fn backends_other_than_gles_need_a_drm_card() {
+    let factory: RenderBackendFactory =
+        Arc::new(|_| Err(RenderBackendError::Other("unreachable".into())));
+    for kind in [
+        RenderBackendKind::DumbBuffers,
+        RenderBackendKind::Custom(factory),
+    ] {
+        let result = EasyDRM::init_with_options(
+            EasyDRMOptions::new()
+                .headless()
+                .render_backend(kind)
+                .ignore_kernel_cmdline(),
+            |_| (),
+        );
+        assert!(matches!(result, Err(EasyDRMError::NoDevice)));
+    }
+}
+
+#[test]
+fn gl_config_selects_depth_and_stencil_buffers() {
+    let mut easydrm = EasyDRM::init_with_options(
+        EasyDRMOptions::new()
+            .headless()
+            .ignore_kernel_cmdline()
+            .gl_config(GlConfig::new().depth_bits(16).stencil_bits(8))
+            .virtual_monitor(16, 16, 60.0),
+        |_| (),
+    )
+    .expect("headless initialization needs an EGL device");
+    let monitor = easydrm.monitors_mut().next().unwrap();
+    monitor.make_current().unwrap();
+    let gl = monitor.gl();
+    let (mut depth_bits, mut stencil_bits) = (0, 0);
+    unsafe {
+        gl.GetIntegerv(gl::DEPTH_BITS, &mut depth_bits);
+        gl.GetIntegerv(gl::STENCIL_BITS, &mut stencil_bits);
+    }
+    assert!(depth_bits >= 16, "{} depth bits", depth_bits);
+    assert!(stencil_bits >= 8, "{} stencil bits", stencil_bits);
+}
+
+#[test]
+fn unmatched_gl_config_lists_available_configs() {
+    let result = EasyDRM::init_with_options(
+        EasyDRMOptions::new()
+            .headless()
+            .ignore_kernel_cmdline()
+            .gl_config(GlConfig::new().samples(255))
+            .virtual_monitor(16, 16, 60.0),
+        |_| (),
+    );
+    let Err(EasyDRMError::MonitorSetup(error)) = result else {
+        panic!("a 255x MSAA config shouldn't exist");
+    };
+    let message = error.to_string();
+    assert!(message.contains("255x MSAA"), "{}", message);
+    assert!(message.contains("available configs: "), "{}", message);
+}
+
+#[test]
+fn shared_contexts_see_objects_from_other_monitors_and_loader_threads() {
+    let mut easydrm = EasyDRM::init_with_options(
+        EasyDRMOptions::new()
+            .headless()
+            .ignore_kernel_cmdline()
+            .shared_gl_contexts()
+            .virtual_monitor(16, 16, 60.0),
+        |_| (),
+    )
+    .expect("headless initialization needs an EGL device");
+
+    // Upload on a loader thread, as an asset loader would
+    let loader = easydrm.create_resource_context().unwrap();
+    let texture = std::thread::spawn(move || {
+        loader.make_current().unwrap();
+        let gl = loader.gl();
+        let mut texture = 0;
+        unsafe {
+            gl.GenTextures(1, &mut texture);
+            gl.BindTexture(gl::TEXTURE_2D, texture);
+            gl.TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as i32,
+                1,
+                1,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                [255u8, 0, 0, 255].as_ptr().cast(),
+            );
+            gl.Finish();
+        }
+        texture
+    })
+    .join()
+    .unwrap();
+
+    // A monitor added afterwards joins the same share group
+    easydrm.add_virtual_monitor(8, 8, 30.0).unwrap();
+    for monitor in easydrm.monitors_mut() {
+        monitor.make_current().unwrap();
+        assert_eq!(unsafe { monitor.gl().IsTexture(texture) }, gl::TRUE);
+    }
+}
+
+#[test]
+fn resource_contexts_need_shared_contexts() {
+    let easydrm = EasyDRM::init_with_options(
+        EasyDRMOptions::new().headless().ignore_kernel_cmdline(),
+        |_| (),
+    )
+    .expect("headless initialization needs an EGL device");
+    assert!(matches!(
+        easydrm.create_resource_context(),
+        Err(GlesContextError::NotShared)
+    ));
+}
+
+#[test]
+fn render_threads_hand_frames_to_the_main_thread() {
+    let mut easydrm = EasyDRM::init_with_options(
+        EasyDRMOptions::new().headless().ignore_kernel_cmdline(),
+        |_| (),
+    )
+    .expect("headless initialization needs an EGL device");
+    let connector = easydrm
+        .add_virtual_monitor(16, 16, 60.0)
+        .unwrap()
+        .connector_id();
+
+    let render_thread = easydrm.take_render_thread(connector).unwrap();
+    assert_eq!(render_thread.connector_id(), connector);
+    let monitor = easydrm.get_monitor_mut(connector).unwrap();
+    assert!(matches!(
+        monitor.make_current(),
+        Err(GlesContextError::NoContext)
+    ));
+    assert!(matches!(
+        easydrm.take_render_thread(connector),
+        Err(RenderThreadError::Unsupported)
+    ));
+
+    // The first frame is red, the following ones green
+    let renderer = std::thread::spawn(move || {
+        let mut render_thread = render_thread;
+        let mut current = render_thread.make_current().unwrap();
+        let mut frames = 0;
+        loop {
+            match current.wait_for_flip() {
+                Ok(()) => {}
+                Err(RenderThreadError::Disconnected) => return frames,
+                Err(e) => panic!("{}", e),
+            }
+            let gl = current.gl();
+            unsafe {
+                match frames {
+                    0 => gl.ClearColor(1.0, 0.0, 0.0, 1.0),
+                    _ => gl.ClearColor(0.0, 1.0, 0.0, 1.0),
+                }
+                gl.Clear(gl::COLOR_BUFFER_BIT);
+            }
+            match current.swap_buffers() {
+                Ok(()) => frames += 1,
+                Err(RenderThreadError::Disconnected) => return frames,
+                Err(e) => panic!("{}", e),
+            }
+        }
+    });
+
+    // Present until the second frame, which the thread only renders after the flip of
+    // the first one, is captured
+    let mut seen_red = false;
+    loop {
+        easydrm.poll_events().unwrap();
+        easydrm.swap_buffers().unwrap();
+        let monitor = easydrm.get_monitor(connector).unwrap();
+        match monitor.capture_frame().ok().and_then(|f| f.pixel(8, 8)) {
+            Some([255, 0, 0, 255]) => seen_red = true,
+            Some([0, 255, 0, 255]) => break,
+            _ => {}
+        }
+    }
+    assert!(seen_red);
+
+    assert!(easydrm.remove_virtual_monitor(connector));
+    assert!(renderer.join().unwrap() >= 2);
+}
+
+#[test]
+fn gl_errors_are_logged_with_the_monitor_connector() {
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let logged = Arc::clone(&messages);
+    let mut easydrm = EasyDRM::init_with_options(
+        EasyDRMOptions::new()
+            .headless()
+            .ignore_kernel_cmdline()
+            .gl_debug_output(move |message| {
+                logged.lock().unwrap().push((
+                    message.connector,
+                    message.connector_name.to_string(),
+                    message.source,
+                    message.kind,
+                    message.severity,
+                ));
+            }),
+        |_| (),
+    )
+    .expect("headless initialization needs an EGL device");
+    easydrm.add_virtual_monitor(8, 8, 60.0).unwrap();
+    let monitor = easydrm.add_virtual_monitor(8, 8, 60.0).unwrap();
+    let connector = monitor.connector_id();
+
+    monitor.make_current().unwrap();
+    // Not a texture target, fails with GL_INVALID_ENUM
+    unsafe { monitor.gl().BindTexture(gl::RGBA, 0) };
+
+    let messages = messages.lock().unwrap();
+    assert!(
+        messages.contains(&(
+            connector,
+            "VIRTUAL-2".to_string(),
+            GlDebugSource::Api,
+            GlDebugType::Error,
+            GlDebugSeverity::High,
+        )),
+        "{:?}",
+        messages
+    );
+}
+
+/// Vulkan on Mesa's lavapipe, to produce images for the interop tests
+struct Lavapipe {
+    _entry: ash::Entry,
+    instance: ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    device: ash::Device,
+    queue_family: u32,
+    queue: vk::Queue,
+}
+
+impl Lavapipe {
+    /// Returns `None` if there's no Vulkan loader or lavapipe isn't installed.
+    fn new() -> Option<Self> {
+        let entry = unsafe { ash::Entry::load() }.ok()?;
+        let app_info = vk::ApplicationInfo::default().api_version(vk::API_VERSION_1_1);
+        let instance_info = vk::InstanceCreateInfo::default().application_info(&app_info);
+        let instance = unsafe { entry.create_instance(&instance_info, None) }.ok()?;
+
+        // lavapipe is the CPU device
+        let physical_device = unsafe { instance.enumerate_physical_devices() }
+            .unwrap_or_default()
+            .into_iter()
+            .find(|&physical_device| {
+                let properties =
+                    unsafe { instance.get_physical_device_properties(physical_device) };
+                properties.device_type == vk::PhysicalDeviceType::CPU
+            });
+        let Some(physical_device) = physical_device else {
+            unsafe { instance.destroy_instance(None) };
+            return None;
+        };
+        let queue_family =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) }
+                .iter()
+                .position(|family| family.queue_flags.contains(vk::QueueFlags::GRAPHICS))?
+                as u32;
+
+        let priorities = [1.0];
+        let queue_infos = [vk::DeviceQueueCreateInfo::default()
+            .queue_family_index(queue_family)
+            .queue_priorities(&priorities)];
+        let extensions = [
+            ash::khr::external_memory_fd::NAME.as_ptr(),
+            ash::khr::external_semaphore_fd::NAME.as_ptr(),
+        ];
+        let device_info = vk::DeviceCreateInfo::default()
+            .queue_create_infos(&queue_infos)
+            .enabled_extension_names(&extensions);
+        let device = unsafe { instance.create_device(physical_device, &device_info, None) }.ok()?;
+        let queue = unsafe { device.get_device_queue(queue_family, 0) };
+
+        Some(Lavapipe {
+            _entry: entry,
+            instance,
+            physical_device,
+            device,
+            queue_family,
+            queue,
+        })
+    }
+
+    fn memory_type(&self, type_bits: u32, flags: vk::MemoryPropertyFlags) -> u32 {
+        let properties = unsafe {
+            self.instance
+                .get_physical_device_memory_properties(self.physical_device)
+        };
+        (0..properties.memory_type_count)
+            .find(|&i| {
+                type_bits & (1 << i) != 0
+                    && properties.memory_types[i as usize]
+                        .property_flags
+                        .contains(flags)
+            })
+            .expect("no suitable memory type")
+    }
+
+    fn exportable_semaphore(&self) -> (vk::Semaphore, OwnedFd) {
+        let mut export_info = vk::ExportSemaphoreCreateInfo::default()
+            .handle_types(vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_FD);
+        let semaphore = unsafe {
+            self.device.create_semaphore(
+                &vk::SemaphoreCreateInfo::default().push_next(&mut export_info),
+                None,
+            )
+        }
+        .unwrap();
+        let fd = unsafe {
+            ash::khr::external_semaphore_fd::Device::new(&self.instance, &self.device)
+                .get_semaphore_fd(
+                    &vk::SemaphoreGetFdInfoKHR::default()
+                        .semaphore(semaphore)
+                        .handle_type(vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_FD),
+                )
+        }
+        .unwrap();
+        (semaphore, unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+}
+
+impl Drop for Lavapipe {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_device(None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+#[test]
+fn vulkan_images_are_blitted_upright_with_semaphores() {
+    let Some(vulkan) = Lavapipe::new() else {
+        eprintln!("Skipping: no Vulkan loader or lavapipe device");
+        return;
+    };
+    let device = &vulkan.device;
+    let (width, height) = (16u32, 8u32);
+
+    let mut easydrm = EasyDRM::init_with_options(
+        EasyDRMOptions::new()
+            .headless()
+            .ignore_kernel_cmdline()
+            .virtual_monitor(width as u16, height as u16, 60.0),
+        |_| (),
+    )
+    .expect("headless initialization needs an EGL device");
+    let connector = easydrm.monitors().next().unwrap().connector_id();
+
+    // Exportable image, in Vulkan's top-left origin: red | green over blue | white
+    let mut external_info = vk::ExternalMemoryImageCreateInfo::default()
+        .handle_types(vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD);
+    let image_info = vk::ImageCreateInfo::default()
+        .image_type(vk::ImageType::TYPE_2D)
+        .format(vk::Format::R8G8B8A8_UNORM)
+        .extent(vk::Extent3D {
+            width,
+            height,
+            depth: 1,
+        })
+        .mip_levels(1)
+        .array_layers(1)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .tiling(vk::ImageTiling::OPTIMAL)
+        .usage(
+            vk::ImageUsageFlags::TRANSFER_DST
+                | vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        )
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .push_next(&mut external_info);
+    let image = unsafe { device.create_image(&image_info, None) }.unwrap();
+    let requirements = unsafe { device.get_image_memory_requirements(image) };
+    let mut export_info = vk::ExportMemoryAllocateInfo::default()
+        .handle_types(vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD);
+    let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::default().image(image);
+    let allocate_info = vk::MemoryAllocateInfo::default()
+        .allocation_size(requirements.size)
+        .memory_type_index(vulkan.memory_type(
+            requirements.memory_type_bits,
+            vk::MemoryPropertyFlags::empty(),
+        ))
+        .push_next(&mut export_info)
+        .push_next(&mut dedicated_info);
+    let memory = unsafe { device.allocate_memory(&allocate_info, None) }.unwrap();
+    unsafe { device.bind_image_memory(image, memory, 0) }.unwrap();
+    let memory_fd = unsafe {
+        ash::khr::external_memory_fd::Device::new(&vulkan.instance, device).get_memory_fd(
+            &vk::MemoryGetFdInfoKHR::default()
+                .memory(memory)
+                .handle_type(vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD),
+        )
+    }
+    .unwrap();
+
+    let pixels: Vec<u8> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x < width / 2, y < height / 2)))
+        .flat_map(|quadrant| match quadrant {
+            (true, true) => [255, 0, 0, 255],
+            (false, true) => [0, 255, 0, 255],
+            (true, false) => [0, 0, 255, 255],
+            (false, false) => [255, 255, 255, 255],
+        })
+        .collect();
+    let staging_info = vk::BufferCreateInfo::default()
+        .size(pixels.len() as u64)
+        .usage(vk::BufferUsageFlags::TRANSFER_SRC);
+    let staging = unsafe { device.create_buffer(&staging_info, None) }.unwrap();
+    let staging_requirements = unsafe { device.get_buffer_memory_requirements(staging) };
+    let staging_memory = unsafe {
+        device.allocate_memory(
+            &vk::MemoryAllocateInfo::default()
+                .allocation_size(staging_requirements.size)
+                .memory_type_index(vulkan.memory_type(
+                    staging_requirements.memory_type_bits,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                )),
+            None,
+        )
+    }
+    .unwrap();
+    unsafe {
+        device
+            .bind_buffer_memory(staging, staging_memory, 0)
+            .unwrap();
+        let mapped = device
+            .map_memory(
+                staging_memory,
+                0,
+                vk::WHOLE_SIZE,
+                vk::MemoryMapFlags::empty(),
+            )
+            .unwrap();
+        std::ptr::copy_nonoverlapping(pixels.as_ptr(), mapped.cast(), pixels.len());
+        device.unmap_memory(staging_memory);
+    }
+
+    // Semaphores are optional in llvmpipe; without them, synchronize on the host
+    let (rendered, rendered_fd) = vulkan.exportable_semaphore();
+    let (blitted, blitted_fd) = vulkan.exportable_semaphore();
+    let monitor = easydrm.get_monitor_mut(connector).unwrap();
+    let gl_semaphores = match (
+        monitor.import_vulkan_semaphore(rendered_fd),
+        monitor.import_vulkan_semaphore(blitted_fd),
+    ) {
+        (Ok(rendered), Ok(blitted)) => Some((rendered, blitted)),
+        (Err(InteropError::ExtensionMissing(_)), _) => {
+            eprintln!("GL_EXT_semaphore_fd is missing, synchronizing on the host");
+            None
+        }
+        (Err(e), _) | (_, Err(e)) => panic!("failed to import semaphores: {e}"),
+    };
+
+    // Upload the pixels and hand the image over to GL as a color attachment
+    let pool = unsafe {
+        device.create_command_pool(
+            &vk::CommandPoolCreateInfo::default().queue_family_index(vulkan.queue_family),
+            None,
+        )
+    }
+    .unwrap();
+    let command_buffer = unsafe {
+        device.allocate_command_buffers(
+            &vk::CommandBufferAllocateInfo::default()
+                .command_pool(pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(1),
+        )
+    }
+    .unwrap()[0];
+    let color_range = vk::ImageSubresourceRange::default()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .level_count(1)
+        .layer_count(1);
+    unsafe {
+        device
+            .begin_command_buffer(command_buffer, &vk::CommandBufferBeginInfo::default())
+            .unwrap();
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[vk::ImageMemoryBarrier::default()
+                .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .old_layout(vk::ImageLayout::UNDEFINED)
+                .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(color_range)],
+        );
+        device.cmd_copy_buffer_to_image(
+            command_buffer,
+            staging,
+            image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &[vk::BufferImageCopy::default()
+                .image_subresource(
+                    vk::ImageSubresourceLayers::default()
+                        .aspect_mask(vk::ImageAspectFlags::COLOR)
+                        .layer_count(1),
+                )
+                .image_extent(vk::Extent3D {
+                    width,
+                    height,
+                    depth: 1,
+                })],
+        );
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[vk::ImageMemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .new_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .src_queue_family_index(vulkan.queue_family)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_EXTERNAL)
+                .image(image)
+                .subresource_range(color_range)],
+        );
+        device.end_command_buffer(command_buffer).unwrap();
+    }
+    let fence = unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None) }.unwrap();
+    let command_buffers = [command_buffer];
+    let signal = [rendered];
+    let mut submit = vk::SubmitInfo::default().command_buffers(&command_buffers);
+    if gl_semaphores.is_some() {
+        submit = submit.signal_semaphores(&signal);
+    } else {
+        unsafe {
+            device.queue_submit(vulkan.queue, &[submit], fence).unwrap();
+            device.wait_for_fences(&[fence], true, u64::MAX).unwrap();
+            device.reset_fences(&[fence]).unwrap();
+        }
+    }
+    if gl_semaphores.is_some() {
+        unsafe { device.queue_submit(vulkan.queue, &[submit], fence) }.unwrap();
+    }
+
+    let monitor = easydrm.get_monitor_mut(connector).unwrap();
+    let external_image = monitor
+        .import_vulkan_image(VulkanImageDescriptor {
+            fd: unsafe { OwnedFd::from_raw_fd(memory_fd) },
+            allocation_size: requirements.size,
+            offset: 0,
+            width,
+            height,
+            internal_format: gl::RGBA8,
+            dedicated: true,
+        })
+        .unwrap();
+    assert_eq!(external_image.size(), (width, height));
+    if let Some((rendered, _)) = &gl_semaphores {
+        rendered.wait(&external_image, ImageLayout::ColorAttachment);
+    }
+    monitor.blit_external_image(&external_image).unwrap();
+    if let Some((_, blitted)) = &gl_semaphores {
+        blitted.signal(&external_image, ImageLayout::ColorAttachment);
+    }
+    easydrm.swap_buffers().unwrap();
+
+    // Vulkan gets the image back once the blit is done
+    if gl_semaphores.is_some() {
+        let wait = [blitted];
+        let stages = [vk::PipelineStageFlags::ALL_COMMANDS];
+        let submit = vk::SubmitInfo::default()
+            .wait_semaphores(&wait)
+            .wait_dst_stage_mask(&stages);
+        unsafe {
+            device.wait_for_fences(&[fence], true, u64::MAX).unwrap();
+            device.reset_fences(&[fence]).unwrap();
+            device.queue_submit(vulkan.queue, &[submit], fence).unwrap();
+            device.wait_for_fences(&[fence], true, u64::MAX).unwrap();
+        }
+    }
+
+    // The blit keeps the top row on top and doesn't mirror the columns
+    let monitor = easydrm.get_monitor_mut(connector).unwrap();
+    let frame = monitor.capture_frame().unwrap();
+    assert_eq!(frame.size(), (width, height));
+    assert_eq!(frame.pixel(0, 0), Some([255, 0, 0, 255]));
+    assert_eq!(frame.pixel(width - 1, 0), Some([0, 255, 0, 255]));
+    assert_eq!(frame.pixel(0, height - 1), Some([0, 0, 255, 255]));
+    assert_eq!(
+        frame.pixel(width - 1, height - 1),
+        Some([255, 255, 255, 255])
+    );
+
+    // GL objects go away with the context current
+    monitor.make_current().unwrap();
+    drop(gl_semaphores);
+    drop(external_image);
+    unsafe {
+        device.destroy_fence(fence, None);
+        device.destroy_command_pool(pool, None);
+        device.destroy_semaphore(rendered, None);
+        device.destroy_semaphore(blitted, None);
+        device.destroy_buffer(staging, None);
+        device.free_memory(staging_memory, None);
+        device.destroy_image(image, None);
+        device.free_memory(memory, None);
+    }
+}
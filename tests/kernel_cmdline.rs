@@ -0,0 +1,77 @@
+//! `video=` kernel command line parsing tests.
+
+use easydrm::{ForcedState, KernelModeHints, Rotation, VideoModeHint};
+
+#[test]
+fn loads_per_connector_hints() {
+    let path = format!(
+        "{}/tests/data/cmdline/kiosk.txt",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let hints = KernelModeHints::load(&path).unwrap();
+
+    let hdmi = hints.for_connector("HDMI-A-1").unwrap();
+    assert_eq!(hdmi.size, Some((1280, 720)));
+    assert_eq!(hdmi.refresh, Some(60));
+    assert_eq!(hdmi.forced, None);
+
+    let dp = hints.for_connector("DP-1").unwrap();
+    assert_eq!(dp.size, None);
+    assert_eq!(dp.forced, Some(ForcedState::Off));
+
+    let dsi = hints.for_connector("DSI-1").unwrap();
+    assert_eq!(dsi.size, Some((800, 1280)));
+    assert_eq!(dsi.forced, Some(ForcedState::On));
+    assert_eq!(dsi.rotation, Some(Rotation::Rotate90));
+
+    assert_eq!(hints.for_connector("eDP-1"), None);
+}
+
+#[test]
+fn parses_mode_flags() {
+    let hint = VideoModeHint::parse("1920x1080MR-24@60im").unwrap();
+    assert_eq!(
+        hint,
+        VideoModeHint {
+            size: Some((1920, 1080)),
+            refresh: Some(60),
+            bpp: Some(24),
+            cvt: true,
+            reduced_blanking: true,
+            interlaced: true,
+            margins: true,
+            forced: None,
+            rotation: None,
+        }
+    );
+
+    // Named modes and garbage aren't supported
+    assert_eq!(VideoModeHint::parse("NTSC"), None);
+    assert_eq!(VideoModeHint::parse("1920xabc"), None);
+}
+
+#[test]
+fn global_hint_applies_to_connectors_without_their_own() {
+    let hints = KernelModeHints::parse("video=1024x768 video=HDMI-A-1:1920x1080@50");
+    assert_eq!(hints.for_connector("DP-2").unwrap().size, Some((1024, 768)));
+    assert_eq!(hints.for_connector("HDMI-A-1").unwrap().refresh, Some(50));
+
+    assert!(KernelModeHints::parse("root=/dev/sda1 quiet").is_empty());
+}
+
+#[test]
+fn force_flags() {
+    let hints = KernelModeHints::parse("video=DSI-1:e video=DVI-I-1:1024x768D video=DP-1:d");
+
+    let dsi = hints.for_connector("DSI-1").unwrap();
+    assert_eq!(dsi.forced, Some(ForcedState::On));
+    assert!(dsi.forces_on());
+
+    let dvi = hints.for_connector("DVI-I-1").unwrap();
+    assert_eq!(dvi.forced, Some(ForcedState::OnDigital));
+    assert!(dvi.forces_on());
+
+    let dp = hints.for_connector("DP-1").unwrap();
+    assert!(!dp.forces_on());
+    assert!(!VideoModeHint::parse("1280x720").unwrap().forces_on());
+}
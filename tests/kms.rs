@@ -0,0 +1,66 @@
+//! Tests on a display controller, e.g. vkms (`modprobe vkms enable_cursor=1`). They're
+//! skipped on machines without a DRM card.
+
+use drm::control::connector;
+use easydrm::{CursorBackend, EasyDRM, EasyDRMError, EasyDRMOptions};
+
+/// Initializes EasyDRM with CPU rendering, or returns `None` without a DRM card.
+fn cpu_rendered() -> Option<EasyDRM<()>> {
+    match EasyDRM::init_with_options(
+        EasyDRMOptions::new()
+            .cpu_rendering()
+            .ignore_kernel_cmdline(),
+        |_| (),
+    ) {
+        Ok(easydrm) => Some(easydrm),
+        Err(EasyDRMError::NoDevice) => None,
+        Err(e) => panic!("failed to initialize EasyDRM: {}", e),
+    }
+}
+
+/// Handles events until the last commit of `connector` reached the screen.
+fn wait_for_flip(easydrm: &mut EasyDRM<()>, connector: connector::Handle) {
+    while !easydrm.get_monitor(connector).unwrap().can_render() {
+        easydrm.poll_events().unwrap();
+    }
+}
+
+#[test]
+fn cursor_only_commits_reach_monitors_on_their_default_mode() {
+    let Some(mut easydrm) = cpu_rendered() else {
+        eprintln!("Skipping: no DRM card");
+        return;
+    };
+    let Some(connector) = easydrm
+        .monitors()
+        .find(|monitor| monitor.cursor_backend() != CursorBackend::Composited)
+        .map(|monitor| monitor.connector_id())
+    else {
+        eprintln!("Skipping: no monitor with a cursor plane");
+        return;
+    };
+
+    // The first frame sets the default mode
+    let monitor = easydrm.get_monitor_mut(connector).unwrap();
+    assert!(monitor.requested_mode().is_none());
+    monitor.pixels_mut().unwrap().fill(0);
+    monitor
+        .set_cursor_image(&[0xFFFF_FFFF; 16 * 16], 16, 16, (8, 8))
+        .unwrap();
+    easydrm.swap_buffers().unwrap();
+    wait_for_flip(&mut easydrm, connector);
+
+    // Moving the cursor over the static screen commits just the cursor plane, which
+    // waits for the next vblank like a page flip
+    easydrm
+        .get_monitor_mut(connector)
+        .unwrap()
+        .move_cursor(100, 50);
+    easydrm.commit_cursors().unwrap();
+    assert!(!easydrm.get_monitor(connector).unwrap().can_render());
+    wait_for_flip(&mut easydrm, connector);
+    assert_eq!(
+        easydrm.get_monitor(connector).unwrap().cursor_position(),
+        Some((100, 50))
+    );
+}
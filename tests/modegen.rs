@@ -0,0 +1,184 @@
+//! Mode generation tests against published VESA timings.
+
+use drm::control::{Mode, ModeFlags};
+use easydrm::{ModeBuilder, ModeGenError, ModeTiming, parse_modeline};
+
+/// Clock, size, hsync and vsync of a mode
+type Timings = (u32, (u16, u16), (u16, u16, u16), (u16, u16, u16));
+
+fn timings(mode: &Mode) -> Timings {
+    (mode.clock(), mode.size(), mode.hsync(), mode.vsync())
+}
+
+#[test]
+fn cvt_1920x1080_60() {
+    // VESA CVT 2.07M9: 173.00 MHz
+    let mode = ModeBuilder::new(1920, 1080, 60.0).build().unwrap();
+    assert_eq!(
+        timings(&mode),
+        (
+            173_000,
+            (1920, 1080),
+            (2048, 2248, 2576),
+            (1083, 1088, 1120)
+        )
+    );
+    assert_eq!(mode.vrefresh(), 60);
+    assert_eq!(mode.flags(), ModeFlags::NHSYNC | ModeFlags::PVSYNC);
+    assert_eq!(mode.name().to_str(), Ok("1920x1080"));
+}
+
+#[test]
+fn cvt_1024x768_60() {
+    // VESA CVT 0.79M3: 63.50 MHz, 4:3 uses a 4 line vsync
+    let mode = ModeBuilder::new(1024, 768, 60.0).build().unwrap();
+    assert_eq!(
+        timings(&mode),
+        (63_500, (1024, 768), (1072, 1176, 1328), (771, 775, 798))
+    );
+}
+
+#[test]
+fn cvt_reduced_blanking() {
+    // VESA CVT 2.07M9-R: 138.50 MHz
+    let mode = ModeBuilder::new(1920, 1080, 60.0)
+        .timing(ModeTiming::CvtReducedBlanking)
+        .build()
+        .unwrap();
+    assert_eq!(
+        timings(&mode),
+        (
+            138_500,
+            (1920, 1080),
+            (1968, 2000, 2080),
+            (1083, 1088, 1111)
+        )
+    );
+    assert_eq!(mode.flags(), ModeFlags::PHSYNC | ModeFlags::NVSYNC);
+
+    // DMT 1920x1200@60 reduced blanking (0x44): 154.00 MHz
+    let mode = ModeBuilder::new(1920, 1200, 60.0)
+        .timing(ModeTiming::CvtReducedBlanking)
+        .build()
+        .unwrap();
+    assert_eq!(
+        timings(&mode),
+        (
+            154_000,
+            (1920, 1200),
+            (1968, 2000, 2080),
+            (1203, 1209, 1235)
+        )
+    );
+}
+
+#[test]
+fn cvt_reduced_blanking_v2() {
+    // VESA CVT-RBv2 1920x1080@60: 133.32 MHz
+    let mode = ModeBuilder::new(1920, 1080, 60.0)
+        .timing(ModeTiming::CvtReducedBlankingV2)
+        .build()
+        .unwrap();
+    assert_eq!(
+        timings(&mode),
+        (
+            133_320,
+            (1920, 1080),
+            (1928, 1960, 2000),
+            (1097, 1105, 1111)
+        )
+    );
+}
+
+#[test]
+fn gtf_1920x1080_60() {
+    // `gtf 1920 1080 60`: 172.80 MHz
+    let mode = ModeBuilder::new(1920, 1080, 60.0)
+        .timing(ModeTiming::Gtf)
+        .build()
+        .unwrap();
+    let (clock, size, hsync, vsync) = timings(&mode);
+    assert!(clock.abs_diff(172_800) < 10, "clock {clock}");
+    assert_eq!(size, (1920, 1080));
+    assert_eq!(hsync, (2040, 2248, 2576));
+    assert_eq!(vsync, (1081, 1084, 1118));
+    assert_eq!(mode.vrefresh(), 60);
+}
+
+#[test]
+fn interlaced_cvt() {
+    let mode = ModeBuilder::new(1920, 1080, 60.0)
+        .interlaced(true)
+        .build()
+        .unwrap();
+    assert!(mode.flags().contains(ModeFlags::INTERLACE));
+    assert_eq!(mode.size(), (1920, 1080));
+    assert_eq!(
+        mode.vsync().2 % 2,
+        1,
+        "interlaced totals have an extra half line"
+    );
+    assert_eq!(mode.vrefresh(), 60);
+
+    assert!(matches!(
+        ModeBuilder::new(1920, 1080, 60.0)
+            .timing(ModeTiming::CvtReducedBlanking)
+            .interlaced(true)
+            .build(),
+        Err(ModeGenError::InvalidParameters(_))
+    ));
+}
+
+#[test]
+fn rejects_invalid_parameters() {
+    for builder in [
+        ModeBuilder::new(0, 1080, 60.0),
+        ModeBuilder::new(1920, 1080, 0.0),
+        ModeBuilder::new(1920, 1080, f64::NAN),
+        ModeBuilder::new(1920, 1080, 5000.0),
+        ModeBuilder::new(u16::MAX, 1080, 60.0),
+    ] {
+        assert!(builder.build().is_err(), "{builder:?}");
+    }
+}
+
+#[test]
+fn parses_modelines() {
+    let mode = parse_modeline(
+        r#"Modeline "1920x1080_60.00"  173.00  1920 2048 2248 2576  1080 1083 1088 1120 -hsync +vsync"#,
+    )
+    .unwrap();
+    let cvt = ModeBuilder::new(1920, 1080, 60.0).build().unwrap();
+    assert_eq!(timings(&mode), timings(&cvt));
+    assert_eq!(mode.flags(), cvt.flags());
+    assert_eq!(mode.name().to_str(), Ok("1920x1080_60.00"));
+
+    // CEA-861 1080i, without keyword and name
+    let mode =
+        parse_modeline("74.25 1920 2008 2052 2200 1080 1084 1094 1125 interlace +hsync +vsync")
+            .unwrap();
+    assert_eq!(mode.clock(), 74_250);
+    assert_eq!(mode.vrefresh(), 60);
+    assert_eq!(
+        mode.flags(),
+        ModeFlags::INTERLACE | ModeFlags::PHSYNC | ModeFlags::PVSYNC
+    );
+    assert_eq!(mode.name().to_str(), Ok("1920x1080"));
+
+    for invalid in [
+        "",
+        "Modeline \"unterminated 148.5 1920 2008 2052 2200 1080 1084 1089 1125",
+        "148.5 1920 2008 2052 2200 1080 1084 1089",
+        "148.5 1920 2008 2052 2200 1080 1084 1089 1125 +wobble",
+        "148.5 1920 1900 2052 2200 1080 1084 1089 1125",
+        "-1 1920 2008 2052 2200 1080 1084 1089 1125",
+    ] {
+        assert!(
+            matches!(
+                parse_modeline(invalid),
+                Err(ModeGenError::InvalidModeline(_))
+            ),
+            "{invalid:?}"
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! `MonitorId` string form and lookup tests.
+
+use easydrm::{EdidIdentity, MonitorId};
+
+fn edid(manufacturer: &str, product_code: u16, serial: &str) -> Option<EdidIdentity> {
+    Some(EdidIdentity {
+        manufacturer: manufacturer.to_string(),
+        product_code,
+        serial: serial.to_string(),
+    })
+}
+
+#[test]
+fn round_trips_through_string() {
+    let id = MonitorId::new(edid("DEL", 0xA0F3, "CFV9N01A1B2L"), "DP-2");
+    assert_eq!(id.to_string(), "DEL:A0F3:CFV9N01A1B2L@DP-2");
+    assert_eq!(id.to_string().parse::<MonitorId>().unwrap(), id);
+
+    let parsed: MonitorId = "GSM:7F44:16843009@HDMI-A-1".parse().unwrap();
+    assert_eq!(parsed.edid(), edid("GSM", 0x7F44, "16843009").as_ref());
+    assert_eq!(parsed.connector_name(), "HDMI-A-1");
+}
+
+#[test]
+fn serials_with_separators() {
+    for serial in ["SN:12:34", "user@host", "a:b@c", ""] {
+        let id = MonitorId::new(edid("AUO", 0x123D, serial), "eDP-1");
+        let parsed: MonitorId = id.to_string().parse().unwrap();
+        assert_eq!(parsed, id, "serial {serial:?}");
+    }
+}
+
+#[test]
+fn connector_without_edid() {
+    let id = MonitorId::new(None, "DSI-1");
+    assert_eq!(id.to_string(), "DSI-1");
+    let parsed: MonitorId = "DSI-1".parse().unwrap();
+    assert_eq!(parsed, id);
+    assert_eq!(parsed.edid(), None);
+
+    // Without an EDID there is nothing to recognize the display by
+    assert!(!id.same_display(&MonitorId::new(None, "DSI-1")));
+}
+
+#[test]
+fn rejects_malformed_ids() {
+    for s in [
+        "",
+        "DEL:A0F3:X@",
+        "DEL:A0F3@DP-2",
+        "DEL:ZZZZ:X@DP-2",
+        "DEL@DP-2",
+    ] {
+        assert!(s.parse::<MonitorId>().is_err(), "{s:?}");
+    }
+}
+
+#[test]
+fn finds_exact_match_then_moved_display() {
+    let dell = MonitorId::new(edid("DEL", 0xA0F3, "CFV9N01A1B2L"), "DP-2");
+    let lg = MonitorId::new(edid("GSM", 0x7F44, "16843009"), "HDMI-A-1");
+    let monitors = [(1, &dell), (2, &lg)];
+
+    assert_eq!(dell.find_in(monitors), Some(1));
+    assert_eq!(lg.find_in(monitors), Some(2));
+
+    // Same display, moved to another connector after an MST rebuild
+    let moved = MonitorId::new(edid("DEL", 0xA0F3, "CFV9N01A1B2L"), "DP-3");
+    assert_eq!(moved.find_in(monitors), Some(1));
+
+    let unknown = MonitorId::new(edid("BNQ", 0x7F30, "X"), "DP-2");
+    assert_eq!(unknown.find_in(monitors), None);
+
+    let no_edid = MonitorId::new(None, "DP-3");
+    assert_eq!(no_edid.find_in(monitors), None);
+}
+
+#[test]
+fn identical_panels_are_ambiguous() {
+    let left = MonitorId::new(edid("AOC", 0x2402, ""), "DP-1");
+    let right = MonitorId::new(edid("AOC", 0x2402, ""), "DP-2");
+    let monitors = [(1, &left), (2, &right)];
+
+    // The connector name still tells them apart
+    assert_eq!(left.find_in(monitors), Some(1));
+    assert_eq!(right.find_in(monitors), Some(2));
+
+    // But once it changed, either panel could be meant
+    let moved = MonitorId::new(edid("AOC", 0x2402, ""), "DP-3");
+    assert_eq!(moved.find_in(monitors), None);
+}
@@ -0,0 +1,95 @@
+//! `IN_FORMATS` blob parsing tests.
+
+use drm::buffer::{DrmFourcc, DrmModifier};
+use easydrm::PlaneFormats;
+
+const I915_X_TILED: u64 = 0x0100_0000_0000_0001;
+const I915_Y_TILED_CCS: u64 = 0x0100_0000_0000_0004;
+
+/// Builds a `struct drm_format_modifier_blob` like the kernel does.
+fn blob(formats: &[DrmFourcc], modifiers: &[(u64, u32, u64)]) -> Vec<u8> {
+    let formats_offset = 24u32;
+    let modifiers_offset = (formats_offset + formats.len() as u32 * 4).next_multiple_of(8);
+
+    let mut blob = Vec::new();
+    for value in [
+        1,
+        0,
+        formats.len() as u32,
+        formats_offset,
+        modifiers.len() as u32,
+        modifiers_offset,
+    ] {
+        blob.extend_from_slice(&value.to_ne_bytes());
+    }
+    for &format in formats {
+        blob.extend_from_slice(&(format as u32).to_ne_bytes());
+    }
+    blob.resize(modifiers_offset as usize, 0);
+    for &(mask, offset, modifier) in modifiers {
+        blob.extend_from_slice(&mask.to_ne_bytes());
+        blob.extend_from_slice(&offset.to_ne_bytes());
+        blob.extend_from_slice(&0u32.to_ne_bytes());
+        blob.extend_from_slice(&modifier.to_ne_bytes());
+    }
+    blob
+}
+
+#[test]
+fn modifiers_are_assigned_by_format_mask() {
+    let formats = [DrmFourcc::Xrgb8888, DrmFourcc::Argb8888, DrmFourcc::Nv12];
+    let blob = blob(
+        &formats,
+        &[
+            (0b111, 0, 0),                // linear for every format
+            (0b011, 0, I915_X_TILED),     // X-tiled for the RGB formats
+            (0b001, 0, I915_Y_TILED_CCS), // compressed for XRGB only
+        ],
+    );
+    let plane = PlaneFormats::from_in_formats_blob(&blob).expect("valid blob");
+
+    let mut listed: Vec<u32> = plane.formats().map(|f| f as u32).collect();
+    listed.sort();
+    let mut expected: Vec<u32> = formats.iter().map(|&f| f as u32).collect();
+    expected.sort();
+    assert_eq!(listed, expected);
+
+    assert_eq!(
+        plane.modifiers(DrmFourcc::Xrgb8888),
+        [
+            DrmModifier::Linear,
+            DrmModifier::from(I915_X_TILED),
+            DrmModifier::from(I915_Y_TILED_CCS)
+        ]
+    );
+    assert_eq!(
+        plane.modifiers(DrmFourcc::Argb8888),
+        [DrmModifier::Linear, DrmModifier::from(I915_X_TILED)]
+    );
+    assert_eq!(plane.modifiers(DrmFourcc::Nv12), [DrmModifier::Linear]);
+    assert!(plane.supports(DrmFourcc::Xrgb8888, DrmModifier::from(I915_Y_TILED_CCS)));
+    assert!(!plane.supports(DrmFourcc::Nv12, DrmModifier::from(I915_X_TILED)));
+    assert!(plane.modifiers(DrmFourcc::Rgb565).is_empty());
+}
+
+#[test]
+fn mask_offset_selects_later_formats() {
+    let formats: Vec<DrmFourcc> = std::iter::repeat_n(DrmFourcc::Rgb565, 64)
+        .chain([DrmFourcc::Xrgb2101010])
+        .collect();
+    let blob = blob(&formats, &[(0b1, 64, I915_X_TILED)]);
+    let plane = PlaneFormats::from_in_formats_blob(&blob).expect("valid blob");
+
+    assert_eq!(
+        plane.modifiers(DrmFourcc::Xrgb2101010),
+        [DrmModifier::from(I915_X_TILED)]
+    );
+    assert!(plane.modifiers(DrmFourcc::Rgb565).is_empty());
+}
+
+#[test]
+fn truncated_blob_is_rejected() {
+    let blob = blob(&[DrmFourcc::Xrgb8888], &[(0b1, 0, 0)]);
+    assert!(PlaneFormats::from_in_formats_blob(&blob[..blob.len() - 1]).is_none());
+    assert!(PlaneFormats::from_in_formats_blob(&blob[..16]).is_none());
+}